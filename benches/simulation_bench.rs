@@ -0,0 +1,80 @@
+//! Сравнение однопоточного ядра с асинхронной обёрткой
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use simpy_rs::core::{Duration, Priority, SharedSimulation, SimTime, Simulation};
+
+const EVENT_COUNTS: [u64; 2] = [10_000, 100_000];
+
+fn schedule_and_run(c: &mut Criterion) {
+    let mut group = c.benchmark_group("schedule_and_run");
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    for &count in EVENT_COUNTS.iter() {
+        group.throughput(Throughput::Elements(count));
+
+        group.bench_with_input(BenchmarkId::new("Simulation", count), &count, |b, &count| {
+            b.iter(|| {
+                let mut sim = Simulation::new();
+                for i in 0..count {
+                    sim.schedule_after(
+                        Duration::from_seconds((i % 1000) as f64).unwrap(),
+                        Priority::Normal,
+                        || {},
+                    ).unwrap();
+                }
+                sim.run_until(SimTime::from_seconds(1000.0).unwrap()).unwrap();
+                black_box(sim.now())
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("SharedSimulation", count), &count, |b, &count| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    let sim = SharedSimulation::new();
+                    for i in 0..count {
+                        sim.schedule_after(
+                            Duration::from_seconds((i % 1000) as f64).unwrap(),
+                            Priority::Normal,
+                            || {},
+                        ).await.unwrap();
+                    }
+                    // Пошаговый цикл, как в старом ядре: каждое обращение берёт блокировку
+                    while sim.has_events().await {
+                        sim.process_next_event().await.unwrap();
+                        black_box(sim.now().await);
+                    }
+                    black_box(sim.now().await)
+                })
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn step_loop(c: &mut Criterion) {
+    let mut group = c.benchmark_group("step_with_now");
+    let count = 100_000u64;
+    group.throughput(Throughput::Elements(count));
+
+    group.bench_function("Simulation", |b| {
+        b.iter(|| {
+            let mut sim = Simulation::new();
+            for i in 0..count {
+                sim.schedule_after(Duration::from_seconds(i as f64).unwrap(), Priority::Normal, || {})
+                    .unwrap();
+            }
+            while sim.has_events() {
+                sim.process_next_event().unwrap();
+                black_box(sim.now());
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, schedule_and_run, step_loop);
+criterion_main!(benches);
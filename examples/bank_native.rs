@@ -0,0 +1,41 @@
+use simpy_rs::prelude::*;
+use simpy_rs::lua::LogLevel;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Инициализируем логирование
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .init();
+
+    println!("🏦 Симуляция банка на Rust процессах");
+    println!("====================================\n");
+
+    let mut sim = Simulator::new();
+    sim.create_resource("кассир", 2).await;
+
+    // Генератор клиентов: новый клиент каждые 2 секунды
+    sim.spawn_process("generator", |ctx| async move {
+        for i in 1..=5 {
+            ctx.timeout(Duration::from_seconds(2.0)?).await?;
+            ctx.spawn(&format!("client_{}", i), move |ctx| async move {
+                let grant = ctx.request("кассир").await?;
+                ctx.log(&format!("Клиент {} ждал {}", i, grant.wait_time()), LogLevel::Info)?;
+
+                ctx.timeout(Duration::from_seconds(5.0)?).await?;
+                ctx.release("кассир")?;
+                ctx.log(&format!("Клиент {} обслужен в {}", i, ctx.now()), LogLevel::Info)?;
+                Ok(())
+            }).await?;
+        }
+        Ok(())
+    }).await?;
+
+    sim.run(60.0).await?;
+
+    let stats = sim.get_stats().await;
+    println!("\n📊 Статистика симуляции:");
+    println!("{}", serde_json::to_string_pretty(&stats)?);
+
+    Ok(())
+}
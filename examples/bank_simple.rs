@@ -1,5 +1,4 @@
 use simpy_rs::Simulator;
-use tracing_subscriber;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -1,5 +1,4 @@
 use simpy_rs::Simulator;
-use tracing_subscriber;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -12,7 +11,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("==================\n");
 
     // Создаем симулятор
-    let mut sim = Simulator::new();
+    let mut sim = Simulator::with_seed(2024);
 
     // Создаем ресурсы
     sim.create_resource("кассир", 2).await;
@@ -30,13 +29,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 log("Иду к кассиру", "debug")
                 request("кассир")
                 log("Получил кассира, обслуживаюсь", "info")
-                wait(math.random(3, 7))  -- обслуживание 3-7 секунд
+                wait(dist.uniform(3, 7))  -- обслуживание 3-7 секунд
                 release("кассир")
             else
                 log("Иду к банкомату", "debug")
                 request("банкомат")
                 log("Получил банкомат, обслуживаюсь", "info")
-                wait(math.random(1, 3))  -- обслуживание 1-3 секунды
+                wait(dist.triangular(1, 1.5, 3))  -- обслуживание 1-3 секунды
                 release("банкомат")
             end
 
@@ -51,7 +50,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             
             -- Создаем 5 клиентов с интервалами
             for i = 1, 5 do
-                wait(math.random(2, 5))  -- ждем 2-5 секунд
+                wait(dist.exp(3))  -- в среднем 3 секунды между клиентами
                 log("Создаю клиента " .. i, "info")
                 spawn("client_" .. i, "client")
             end
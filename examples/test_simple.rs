@@ -2,7 +2,6 @@
 extern crate simpy_rs;
 
 use simpy_rs::Simulator;
-use tracing_subscriber;
 use std::time::Duration;
 use tokio::time::sleep;
 
@@ -0,0 +1,218 @@
+//! Вероятностные распределения для интервалов прихода, обслуживания и т.п.
+//!
+//! Выборка всегда делается из переданного генератора, обычно это поток
+//! из `RandomStreams`, поэтому прогон с тем же seed повторяется
+
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::SimError;
+
+/// Распределение с проверенными параметрами
+#[derive(Debug, Clone, PartialEq)]
+pub enum Distribution {
+    /// Всегда одно и то же значение
+    Constant(f64),
+    /// Экспоненциальное со средним `mean`
+    Exponential { mean: f64 },
+    /// Нормальное
+    Normal { mean: f64, std_dev: f64 },
+    /// Логнормальное: `mu` и `sigma` — параметры логарифма величины
+    LogNormal { mu: f64, sigma: f64 },
+    /// Равномерное на [low, high)
+    Uniform { low: f64, high: f64 },
+    /// Треугольное с модой `mode`
+    Triangular { low: f64, mode: f64, high: f64 },
+    /// Эрланга: сумма `k` экспоненциальных фаз, общее среднее `mean`
+    Erlang { k: u32, mean: f64 },
+    /// Гамма с формой `shape` и масштабом `scale` (среднее shape * scale)
+    Gamma { shape: f64, scale: f64 },
+    /// Вейбулла с формой `shape` и масштабом `scale`
+    Weibull { shape: f64, scale: f64 },
+    /// Пуассона: целое число событий со средним `mean`
+    Poisson { mean: f64 },
+    /// Равновероятный выбор одного из наблюдавшихся значений
+    Empirical(Vec<f64>),
+    /// Значения с заданными весами; хранятся накопленные веса
+    Discrete { values: Vec<f64>, cumulative: Vec<f64> },
+}
+
+impl Distribution {
+    pub fn constant(value: f64) -> Result<Self, SimError> {
+        check(value.is_finite(), "constant: значение должно быть конечным")?;
+        Ok(Distribution::Constant(value))
+    }
+
+    pub fn exponential(mean: f64) -> Result<Self, SimError> {
+        check(positive(mean), "exponential: среднее должно быть больше нуля")?;
+        Ok(Distribution::Exponential { mean })
+    }
+
+    pub fn normal(mean: f64, std_dev: f64) -> Result<Self, SimError> {
+        check(mean.is_finite(), "normal: среднее должно быть конечным")?;
+        check(non_negative(std_dev), "normal: отклонение не может быть отрицательным")?;
+        Ok(Distribution::Normal { mean, std_dev })
+    }
+
+    pub fn log_normal(mu: f64, sigma: f64) -> Result<Self, SimError> {
+        check(mu.is_finite(), "lognormal: mu должно быть конечным")?;
+        check(non_negative(sigma), "lognormal: sigma не может быть отрицательной")?;
+        Ok(Distribution::LogNormal { mu, sigma })
+    }
+
+    pub fn uniform(low: f64, high: f64) -> Result<Self, SimError> {
+        check(low.is_finite() && high.is_finite() && low <= high, "uniform: нужно low <= high")?;
+        Ok(Distribution::Uniform { low, high })
+    }
+
+    pub fn triangular(low: f64, mode: f64, high: f64) -> Result<Self, SimError> {
+        check(
+            low.is_finite() && high.is_finite() && low <= mode && mode <= high && low < high,
+            "triangular: нужно low <= mode <= high и low < high",
+        )?;
+        Ok(Distribution::Triangular { low, mode, high })
+    }
+
+    pub fn erlang(k: u32, mean: f64) -> Result<Self, SimError> {
+        check(k > 0, "erlang: число фаз должно быть больше нуля")?;
+        check(positive(mean), "erlang: среднее должно быть больше нуля")?;
+        Ok(Distribution::Erlang { k, mean })
+    }
+
+    pub fn gamma(shape: f64, scale: f64) -> Result<Self, SimError> {
+        check(positive(shape) && positive(scale), "gamma: форма и масштаб должны быть больше нуля")?;
+        Ok(Distribution::Gamma { shape, scale })
+    }
+
+    pub fn weibull(shape: f64, scale: f64) -> Result<Self, SimError> {
+        check(positive(shape) && positive(scale), "weibull: форма и масштаб должны быть больше нуля")?;
+        Ok(Distribution::Weibull { shape, scale })
+    }
+
+    pub fn poisson(mean: f64) -> Result<Self, SimError> {
+        check(non_negative(mean), "poisson: среднее не может быть отрицательным")?;
+        Ok(Distribution::Poisson { mean })
+    }
+
+    pub fn empirical(values: Vec<f64>) -> Result<Self, SimError> {
+        check(!values.is_empty(), "empirical: нужно хотя бы одно значение")?;
+        check(values.iter().all(|v| v.is_finite()), "empirical: значения должны быть конечными")?;
+        Ok(Distribution::Empirical(values))
+    }
+
+    /// Таблица значений с весами; веса не обязаны давать в сумме единицу
+    pub fn discrete(values: Vec<f64>, weights: &[f64]) -> Result<Self, SimError> {
+        check(!values.is_empty(), "discrete: нужно хотя бы одно значение")?;
+        check(values.len() == weights.len(), "discrete: число значений и весов должно совпадать")?;
+        check(values.iter().all(|v| v.is_finite()), "discrete: значения должны быть конечными")?;
+        check(weights.iter().all(|&w| non_negative(w)), "discrete: веса не могут быть отрицательными")?;
+
+        let mut total = 0.0;
+        let cumulative: Vec<f64> = weights.iter().map(|w| { total += w; total }).collect();
+        check(total > 0.0, "discrete: сумма весов должна быть больше нуля")?;
+        Ok(Distribution::Discrete { values, cumulative })
+    }
+
+    pub fn sample(&self, rng: &mut StdRng) -> f64 {
+        match self {
+            Distribution::Constant(value) => *value,
+            Distribution::Exponential { mean } => exponential(rng, *mean),
+            Distribution::Normal { mean, std_dev } => mean + std_dev * standard_normal(rng),
+            Distribution::LogNormal { mu, sigma } => (mu + sigma * standard_normal(rng)).exp(),
+            Distribution::Uniform { low, high } => low + (high - low) * rng.gen::<f64>(),
+            Distribution::Triangular { low, mode, high } => {
+                // Обратная функция распределения
+                let u: f64 = rng.gen();
+                let split = (mode - low) / (high - low);
+                if u < split {
+                    low + (u * (high - low) * (mode - low)).sqrt()
+                } else {
+                    high - ((1.0 - u) * (high - low) * (high - mode)).sqrt()
+                }
+            }
+            Distribution::Erlang { k, mean } => {
+                let phase = mean / f64::from(*k);
+                (0..*k).map(|_| exponential(rng, phase)).sum()
+            }
+            Distribution::Gamma { shape, scale } => gamma(rng, *shape) * scale,
+            Distribution::Weibull { shape, scale } => {
+                let u: f64 = rng.gen();
+                scale * (-(1.0 - u).ln()).powf(1.0 / shape)
+            }
+            Distribution::Poisson { mean } => poisson(rng, *mean),
+            Distribution::Empirical(values) => values[rng.gen_range(0..values.len())],
+            Distribution::Discrete { values, cumulative } => {
+                let total = cumulative[cumulative.len() - 1];
+                let u = rng.gen::<f64>() * total;
+                let index = cumulative.partition_point(|&c| c <= u).min(values.len() - 1);
+                values[index]
+            }
+        }
+    }
+}
+
+fn check(condition: bool, message: &str) -> Result<(), SimError> {
+    if condition {
+        Ok(())
+    } else {
+        Err(SimError::SimulationError(message.to_string()))
+    }
+}
+
+fn positive(x: f64) -> bool {
+    x.is_finite() && x > 0.0
+}
+
+fn non_negative(x: f64) -> bool {
+    x.is_finite() && x >= 0.0
+}
+
+fn exponential(rng: &mut StdRng, mean: f64) -> f64 {
+    let u: f64 = rng.gen();
+    -mean * (1.0 - u).ln()
+}
+
+/// Стандартное нормальное по Боксу — Мюллеру
+fn standard_normal(rng: &mut StdRng) -> f64 {
+    let u1: f64 = rng.gen();
+    let u2: f64 = rng.gen();
+    (-2.0 * (1.0 - u1).ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+/// Гамма с единичным масштабом (Марсалья — Цанг)
+fn gamma(rng: &mut StdRng, shape: f64) -> f64 {
+    if shape < 1.0 {
+        // G(a) = G(a + 1) * U^(1/a)
+        let u: f64 = rng.gen();
+        return gamma(rng, shape + 1.0) * (1.0 - u).powf(1.0 / shape);
+    }
+    let d = shape - 1.0 / 3.0;
+    let c = 1.0 / (9.0 * d).sqrt();
+    loop {
+        let x = standard_normal(rng);
+        let v = (1.0 + c * x).powi(3);
+        if v <= 0.0 {
+            continue;
+        }
+        let u: f64 = rng.gen();
+        if (1.0 - u).ln() < 0.5 * x * x + d - d * v + d * v.ln() {
+            return d * v;
+        }
+    }
+}
+
+/// Пуассон: перемножение равномерных для малых средних,
+/// нормальное приближение для больших
+fn poisson(rng: &mut StdRng, mean: f64) -> f64 {
+    if mean > 500.0 {
+        return (mean + mean.sqrt() * standard_normal(rng)).round().max(0.0);
+    }
+    let limit = (-mean).exp();
+    let mut count = 0.0;
+    let mut product: f64 = rng.gen();
+    while product > limit {
+        count += 1.0;
+        product *= rng.gen::<f64>();
+    }
+    count
+}
@@ -1,6 +1,7 @@
 //! Система событий для симуляции
 
 use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
 use super::time::SimTime;
 
 /// Приоритет события (меньше = важнее)
@@ -11,54 +12,189 @@ pub enum Priority {
     Low = 2,
 }
 
-/// Событие в очереди симуляции
+/// Дескриптор запланированного события, позволяет отменить или перенести его
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EventHandle(u64);
+
+impl EventHandle {
+    pub fn id(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Идентификатор процесса, который можно разбудить событием
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ProcessId(u64);
+
+impl ProcessId {
+    pub const fn new(id: u64) -> Self {
+        ProcessId(id)
+    }
+
+    pub fn id(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Что происходит при наступлении события
+pub enum EventAction {
+    /// Выполнить callback внутри ядра
+    Callback(Box<dyn FnOnce() + Send>),
+    /// Возобновить процесс; обрабатывает тот, кто владеет процессами
+    Process(ProcessId),
+}
+
+/// Событие, извлечённое из очереди симуляции
 pub struct Event {
     pub time: SimTime,
     pub priority: Priority,
     pub id: u64,  // Для уникальности при сравнении
-    pub callback: Box<dyn FnOnce() + Send>,
+    pub action: EventAction,
 }
 
-impl Event {
-    pub fn new<F>(time: SimTime, priority: Priority, id: u64, callback: F) -> Self
-    where
-        F: FnOnce() + Send + 'static,
-    {
-        Self {
-            time,
-            priority,
-            id,
-            callback: Box::new(callback),
-        }
-    }
+/// Запись в куче: только ключ сортировки, сам callback хранится отдельно.
+/// После отмены или переноса запись остаётся в куче и пропускается при извлечении
+struct QueueEntry {
+    time: SimTime,
+    priority: Priority,
+    seq: u64,
+    id: u64,
 }
 
-impl PartialEq for Event {
+impl PartialEq for QueueEntry {
     fn eq(&self, other: &Self) -> bool {
-        self.time == other.time && self.priority == other.priority && self.id == other.id
+        self.time == other.time && self.priority == other.priority && self.seq == other.seq
     }
 }
 
-impl Eq for Event {}
+impl Eq for QueueEntry {}
 
-impl PartialOrd for Event {
+impl PartialOrd for QueueEntry {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl Ord for Event {
+impl Ord for QueueEntry {
     fn cmp(&self, other: &Self) -> Ordering {
         // Для BinaryHeap нам нужен обратный порядок (меньшее время = выше приоритет)
-        match other.time.partial_cmp(&self.time) {
-            Some(Ordering::Equal) => {
-                match other.priority.cmp(&self.priority) {
-                    Ordering::Equal => other.id.cmp(&self.id),
-                    other => other,
-                }
+        other.time.cmp(&self.time)
+            .then_with(|| other.priority.cmp(&self.priority))
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// Запланированное, ещё не выполненное событие
+struct PendingEvent {
+    time: SimTime,
+    priority: Priority,
+    seq: u64, // актуальная запись в куче
+    action: EventAction,
+}
+
+/// Очередь событий с поддержкой отмены и переноса
+#[derive(Default)]
+pub struct EventQueue {
+    heap: BinaryHeap<QueueEntry>,
+    pending: HashMap<u64, PendingEvent>,
+    counter: u64,
+}
+
+impl EventQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn next_seq(&mut self) -> u64 {
+        let seq = self.counter;
+        self.counter += 1;
+        seq
+    }
+
+    pub fn push(&mut self, time: SimTime, priority: Priority, action: EventAction) -> EventHandle {
+        let seq = self.next_seq();
+        self.heap.push(QueueEntry { time, priority, seq, id: seq });
+        self.pending.insert(seq, PendingEvent {
+            time,
+            priority,
+            seq,
+            action,
+        });
+        EventHandle(seq)
+    }
+
+    /// Извлечь ближайшее событие, пропуская отменённые и перенесённые записи
+    pub fn pop(&mut self) -> Option<Event> {
+        while let Some(entry) = self.heap.pop() {
+            let is_current = self.pending
+                .get(&entry.id)
+                .is_some_and(|pending| pending.seq == entry.seq);
+
+            if is_current {
+                let pending = self.pending.remove(&entry.id)?;
+                return Some(Event {
+                    time: pending.time,
+                    priority: pending.priority,
+                    id: entry.id,
+                    action: pending.action,
+                });
             }
-            Some(ordering) => ordering,
-            None => Ordering::Equal,
         }
+        None
+    }
+
+    /// Время ближайшего события (устаревшие записи на вершине кучи отбрасываются)
+    pub fn peek_time(&mut self) -> Option<SimTime> {
+        while let Some(entry) = self.heap.peek() {
+            let is_current = self.pending
+                .get(&entry.id)
+                .is_some_and(|pending| pending.seq == entry.seq);
+
+            if is_current {
+                return Some(entry.time);
+            }
+            self.heap.pop();
+        }
+        None
+    }
+
+    /// Отменить событие. Возвращает false, если оно уже выполнено или отменено
+    pub fn cancel(&mut self, handle: EventHandle) -> bool {
+        self.pending.remove(&handle.0).is_some()
+    }
+
+    /// Перенести событие на другое время. Возвращает false, если события нет в очереди
+    pub fn reschedule(&mut self, handle: EventHandle, time: SimTime) -> bool {
+        let seq = self.next_seq();
+        let Some(pending) = self.pending.get_mut(&handle.0) else {
+            return false;
+        };
+
+        pending.time = time;
+        pending.seq = seq;
+        self.heap.push(QueueEntry {
+            time,
+            priority: pending.priority,
+            seq,
+            id: handle.0,
+        });
+        true
+    }
+
+    pub fn contains(&self, handle: EventHandle) -> bool {
+        self.pending.contains_key(&handle.0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.heap.clear();
+        self.pending.clear();
     }
 }
@@ -1,9 +1,15 @@
 //! Ядро симуляции
 
 mod simulation;
+mod shared;
 mod event;
-mod time;
+mod random;
+mod distribution;
+pub mod time;
 
 pub use simulation::Simulation;
-pub use event::Priority;  // Добавляем экспорт Priority
+pub use shared::SharedSimulation;
+pub use event::{Priority, Event, EventAction, EventHandle, EventQueue, ProcessId};  // Добавляем экспорт Priority
 pub use time::{SimTime, Duration};
+pub use random::{RandomStreams, SharedRng};
+pub use distribution::Distribution;
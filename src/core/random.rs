@@ -0,0 +1,69 @@
+//! Воспроизводимые потоки случайных чисел
+//!
+//! Все потоки выводятся из одного master seed симулятора и имени потока,
+//! поэтому при том же seed каждый процесс и каждый именованный поток получают
+//! ту же последовательность независимо от того, в каком порядке к ним обращались
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Поток случайных чисел, общий для всех, кто его запросил
+pub type SharedRng = Rc<RefCell<StdRng>>;
+
+/// Именованные потоки, выведенные из master seed
+#[derive(Debug)]
+pub struct RandomStreams {
+    seed: u64,
+    streams: HashMap<String, SharedRng>,
+}
+
+impl RandomStreams {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            streams: HashMap::new(),
+        }
+    }
+
+    /// Master seed, из которого выведены все потоки
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Поток `name`; создаётся при первом обращении
+    pub fn stream(&mut self, name: &str) -> SharedRng {
+        let seed = self.seed;
+        self.streams
+            .entry(name.to_string())
+            .or_insert_with(|| Rc::new(RefCell::new(derive(seed, name, 0))))
+            .clone()
+    }
+
+    /// Перезапустить поток `name` с дополнительным seed `salt`
+    /// (например, из `math.randomseed` в Lua)
+    pub fn reseed(&mut self, name: &str, salt: u64) {
+        let rng = derive(self.seed, name, salt);
+        *self.stream(name).borrow_mut() = rng;
+    }
+}
+
+/// Генератор для потока `name`: FNV-1a от имени, смешанный с seed через splitmix64
+fn derive(seed: u64, name: &str, salt: u64) -> StdRng {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in name.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    StdRng::seed_from_u64(splitmix64(splitmix64(seed ^ hash) ^ salt))
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
@@ -0,0 +1,100 @@
+//! Асинхронная обёртка над ядром для совместного доступа
+
+use std::sync::Arc;
+use tokio::sync::{Mutex, MutexGuard};
+
+use super::event::{EventHandle, Priority, ProcessId};
+use super::simulation::Simulation;
+use super::time::{SimTime, Duration};
+use crate::SimError;
+
+/// Симуляция под асинхронной блокировкой.
+///
+/// Клоны разделяют одно ядро, поэтому обёртку можно передавать между задачами
+/// tokio. Каждый вызов берёт блокировку, так что для горячих циклов лучше
+/// работать с [`Simulation`] напрямую
+#[derive(Clone, Default)]
+pub struct SharedSimulation {
+    inner: Arc<Mutex<Simulation>>,
+}
+
+impl SharedSimulation {
+    pub fn new() -> Self {
+        Self::from_simulation(Simulation::new())
+    }
+
+    pub fn from_simulation(simulation: Simulation) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(simulation)),
+        }
+    }
+
+    /// Захватить ядро для нескольких операций подряд
+    pub async fn lock(&self) -> MutexGuard<'_, Simulation> {
+        self.inner.lock().await
+    }
+
+    pub async fn now(&self) -> SimTime {
+        self.inner.lock().await.now()
+    }
+
+    pub async fn set_time(&self, time: SimTime) {
+        self.inner.lock().await.set_time(time);
+    }
+
+    pub async fn schedule_after<F>(
+        &self,
+        delay: Duration,
+        priority: Priority,
+        callback: F,
+    ) -> Result<EventHandle, SimError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.inner.lock().await.schedule_after(delay, priority, callback)
+    }
+
+    pub async fn schedule_at<F>(
+        &self,
+        time: SimTime,
+        priority: Priority,
+        callback: F,
+    ) -> Result<EventHandle, SimError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.inner.lock().await.schedule_at(time, priority, callback)
+    }
+
+    pub async fn cancel(&self, handle: EventHandle) -> bool {
+        self.inner.lock().await.cancel(handle)
+    }
+
+    pub async fn reschedule(&self, handle: EventHandle, new_time: SimTime) -> Result<(), SimError> {
+        self.inner.lock().await.reschedule(handle, new_time)
+    }
+
+    pub async fn is_scheduled(&self, handle: EventHandle) -> bool {
+        self.inner.lock().await.is_scheduled(handle)
+    }
+
+    pub async fn run_until(&self, end_time: SimTime) -> Result<(), SimError> {
+        self.inner.lock().await.run_until(end_time)
+    }
+
+    pub async fn run_for(&self, duration: Duration) -> Result<(), SimError> {
+        self.inner.lock().await.run_for(duration)
+    }
+
+    pub async fn process_next_event(&self) -> Result<Option<ProcessId>, SimError> {
+        self.inner.lock().await.process_next_event()
+    }
+
+    pub async fn has_events(&self) -> bool {
+        self.inner.lock().await.has_events()
+    }
+
+    pub async fn clear_events(&self) {
+        self.inner.lock().await.clear_events();
+    }
+}
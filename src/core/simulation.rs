@@ -1,121 +1,208 @@
 //! Основное ядро симуляции
 
-use std::collections::BinaryHeap;
-use std::sync::Arc;
-use tokio::sync::Mutex;
-use tracing::{info, debug};
+use tracing::{info, debug, warn};
 
-use super::event::{Event, Priority};
+use super::event::{EventAction, EventHandle, EventQueue, Priority, ProcessId};
 use super::time::{SimTime, Duration};
 use crate::SimError;
 
-/// Основной симулятор
+/// Основной симулятор.
+///
+/// Однопоточное ядро: часы и очередь событий принадлежат симуляции напрямую,
+/// все операции синхронные и не берут блокировок. Для совместного доступа
+/// из асинхронного кода есть обёртка [`SharedSimulation`](super::SharedSimulation)
 pub struct Simulation {
-    current_time: Arc<Mutex<SimTime>>,
-    event_queue: Arc<Mutex<BinaryHeap<Event>>>,
-    event_counter: Arc<Mutex<u64>>,
+    current_time: SimTime,
+    event_queue: EventQueue,
 }
 
 impl Simulation {
     pub fn new() -> Self {
         Self {
-            current_time: Arc::new(Mutex::new(SimTime::ZERO)),
-            event_queue: Arc::new(Mutex::new(BinaryHeap::new())),
-            event_counter: Arc::new(Mutex::new(0)),
+            current_time: SimTime::ZERO,
+            event_queue: EventQueue::new(),
         }
     }
 
-    pub async fn now(&self) -> SimTime {
-        *self.current_time.lock().await
+    pub fn now(&self) -> SimTime {
+        self.current_time
     }
 
-    pub async fn set_time(&self, time: SimTime) {
-        let mut current = self.current_time.lock().await;
-        *current = time;
+    pub fn set_time(&mut self, time: SimTime) {
+        self.current_time = time;
     }
 
-
-    pub async fn schedule_after<F>(
-        &self,
+    pub fn schedule_after<F>(
+        &mut self,
         delay: Duration,
         priority: Priority,
         callback: F,
-    ) -> Result<(), SimError>
+    ) -> Result<EventHandle, SimError>
     where
         F: FnOnce() + Send + 'static,
     {
-        let current = self.now().await;
-        let event_time = current + SimTime::new(delay.as_seconds());
-        self.schedule_at(event_time, priority, callback).await
+        let event_time = self.time_after(delay)?;
+        self.schedule_at(event_time, priority, callback)
     }
 
-    pub async fn schedule_at<F>(
-        &self,
+    pub fn schedule_at<F>(
+        &mut self,
         time: SimTime,
         priority: Priority,
         callback: F,
-    ) -> Result<(), SimError>
+    ) -> Result<EventHandle, SimError>
     where
         F: FnOnce() + Send + 'static,
     {
-        let mut counter = self.event_counter.lock().await;
-        let id = *counter;
-        *counter += 1;
+        self.schedule_action(time, priority, EventAction::Callback(Box::new(callback)))
+    }
 
-        let event = Event::new(time, priority, id, callback);
+    /// Запланировать пробуждение процесса через `delay`
+    pub fn schedule_process_after(
+        &mut self,
+        delay: Duration,
+        priority: Priority,
+        process: ProcessId,
+    ) -> Result<EventHandle, SimError> {
+        let event_time = self.time_after(delay)?;
+        self.schedule_process_at(event_time, priority, process)
+    }
 
-        let mut queue = self.event_queue.lock().await;
-        queue.push(event);
+    /// Запланировать пробуждение процесса в момент `time`
+    pub fn schedule_process_at(
+        &mut self,
+        time: SimTime,
+        priority: Priority,
+        process: ProcessId,
+    ) -> Result<EventHandle, SimError> {
+        self.schedule_action(time, priority, EventAction::Process(process))
+    }
 
-        debug!("Событие запланировано на время {}", time);
-        Ok(())
+    fn schedule_action(
+        &mut self,
+        time: SimTime,
+        priority: Priority,
+        action: EventAction,
+    ) -> Result<EventHandle, SimError> {
+        if time < self.current_time {
+            return Err(SimError::SimulationError(format!(
+                "Нельзя запланировать событие в прошлом ({} < {})",
+                time, self.current_time
+            )));
+        }
+
+        let handle = self.event_queue.push(time, priority, action);
+
+        debug!("Событие {} запланировано на время {}", handle.id(), time);
+        Ok(handle)
     }
 
-    pub async fn run_until(&mut self, end_time: SimTime) -> Result<(), SimError> {
-        info!("Запуск симуляции до времени {}", end_time);
+    fn time_after(&self, delay: Duration) -> Result<SimTime, SimError> {
+        self.current_time.checked_add(delay).ok_or_else(|| {
+            SimError::SimulationError(format!(
+                "Время события {} + {} выходит за допустимый диапазон",
+                self.current_time, delay
+            ))
+        })
+    }
 
-        while self.now().await < end_time && self.has_events().await {
-            self.process_next_event().await?;
+    /// Отменить запланированное событие.
+    /// Возвращает false, если событие уже произошло или было отменено ранее
+    pub fn cancel(&mut self, handle: EventHandle) -> bool {
+        let cancelled = self.event_queue.cancel(handle);
+        if cancelled {
+            debug!("Событие {} отменено", handle.id());
         }
+        cancelled
+    }
 
-        info!("Симуляция завершена. Финальное время: {}", self.now().await);
+    /// Перенести запланированное событие на новое время
+    pub fn reschedule(&mut self, handle: EventHandle, new_time: SimTime) -> Result<(), SimError> {
+        if new_time < self.current_time {
+            return Err(SimError::SimulationError(format!(
+                "Нельзя перенести событие {} в прошлое ({} < {})",
+                handle.id(), new_time, self.current_time
+            )));
+        }
+
+        if !self.event_queue.reschedule(handle, new_time) {
+            return Err(SimError::SimulationError(format!(
+                "Событие {} не найдено в очереди", handle.id()
+            )));
+        }
+
+        debug!("Событие {} перенесено на время {}", handle.id(), new_time);
         Ok(())
     }
 
-    pub async fn run_for(&mut self, duration: Duration) -> Result<(), SimError> {
-        let start = self.now().await;
-        let end = start + SimTime::new(duration.as_seconds());
-        self.run_until(end).await
+    /// Ожидает ли событие выполнения
+    pub fn is_scheduled(&self, handle: EventHandle) -> bool {
+        self.event_queue.contains(handle)
     }
 
-    pub async fn process_next_event(&self) -> Result<(), SimError> {
-        let next_event = {
-            let mut queue = self.event_queue.lock().await;
-            queue.pop()
-        };
+    /// Выполнить все события до `end_time` включительно и перевести часы на `end_time`
+    pub fn run_until(&mut self, end_time: SimTime) -> Result<(), SimError> {
+        info!("Запуск симуляции до времени {}", end_time);
 
-        if let Some(event) = next_event {
-            {
-                let mut current = self.current_time.lock().await;
-                *current = event.time;
+        while self.event_queue.peek_time().is_some_and(|time| time <= end_time) {
+            if let Some(process) = self.process_next_event()? {
+                warn!("Пробуждение процесса {} пропущено: у ядра нет владельца процессов", process.id());
             }
+        }
+        if self.current_time < end_time {
+            self.current_time = end_time;
+        }
+
+        info!("Симуляция завершена. Финальное время: {}", self.current_time);
+        Ok(())
+    }
 
-            debug!("Обработка события в {}", event.time);
-            (event.callback)();
+    pub fn run_for(&mut self, duration: Duration) -> Result<(), SimError> {
+        let end = self.current_time.checked_add(duration).ok_or_else(|| {
+            SimError::SimulationError(format!(
+                "Время {} + {} выходит за допустимый диапазон",
+                self.current_time, duration
+            ))
+        })?;
+        self.run_until(end)
+    }
 
-            Ok(())
+    /// Извлечь ближайшее событие и перевести часы на его время.
+    /// Callback выполняется сразу, а процесс, который нужно возобновить,
+    /// возвращается вызывающему
+    pub fn process_next_event(&mut self) -> Result<Option<ProcessId>, SimError> {
+        if let Some(event) = self.event_queue.pop() {
+            self.current_time = event.time;
+
+            debug!("Обработка события {} в {}", event.id, event.time);
+            match event.action {
+                EventAction::Callback(callback) => {
+                    callback();
+                    Ok(None)
+                }
+                EventAction::Process(process) => Ok(Some(process)),
+            }
         } else {
             Err(SimError::SimulationError("Нет событий в очереди".to_string()))
         }
     }
 
-    pub async fn has_events(&self) -> bool {
-        !self.event_queue.lock().await.is_empty()
+    /// Время ближайшего события в очереди
+    pub fn peek_next_time(&mut self) -> Option<SimTime> {
+        self.event_queue.peek_time()
+    }
+
+    pub fn has_events(&self) -> bool {
+        !self.event_queue.is_empty()
+    }
+
+    /// Количество ожидающих событий
+    pub fn pending_events(&self) -> usize {
+        self.event_queue.len()
     }
 
-    pub async fn clear_events(&self) {
-        let mut queue = self.event_queue.lock().await;
-        queue.clear();
+    pub fn clear_events(&mut self) {
+        self.event_queue.clear();
         debug!("Очередь событий очищена");
     }
 }
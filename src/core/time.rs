@@ -1,61 +1,181 @@
 //! Управление временем симуляции
+//!
+//! Время хранится в целых тиках, поэтому порядок событий не зависит от
+//! платформы, а многократные `wait(0.1)` складываются без накопления ошибки.
+//! Длительность тика задаётся один раз на процесс через [`set_resolution`]
 
 use std::fmt;
-use std::ops::{Add, Sub};
+use std::ops::{Add, AddAssign, Sub};
+use std::sync::OnceLock;
 use serde::{Serialize, Deserialize};
 
+use crate::SimError;
+
+/// Разрешение по умолчанию: один тик = одна микросекунда
+pub const DEFAULT_TICKS_PER_SECOND: u64 = 1_000_000;
+
+static TICKS_PER_SECOND: OnceLock<u64> = OnceLock::new();
+
+/// Задать количество тиков в секунде модельного времени.
+///
+/// Разрешение фиксируется при первом использовании времени, поэтому его нужно
+/// задать до создания симуляции. Повторная установка другого значения — ошибка
+pub fn set_resolution(ticks_per_second: u64) -> Result<(), SimError> {
+    if ticks_per_second == 0 {
+        return Err(SimError::SimulationError(
+            "Разрешение времени должно быть больше нуля".to_string(),
+        ));
+    }
+
+    let current = *TICKS_PER_SECOND.get_or_init(|| ticks_per_second);
+    if current != ticks_per_second {
+        return Err(SimError::SimulationError(format!(
+            "Разрешение времени уже установлено: {} тиков в секунду",
+            current
+        )));
+    }
+    Ok(())
+}
+
+/// Текущее количество тиков в секунде
+pub fn resolution() -> u64 {
+    *TICKS_PER_SECOND.get_or_init(|| DEFAULT_TICKS_PER_SECOND)
+}
+
+fn seconds_to_ticks(seconds: f64) -> Result<u64, SimError> {
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(SimError::SimulationError(format!(
+            "Недопустимое значение времени: {}",
+            seconds
+        )));
+    }
+
+    let ticks = (seconds * resolution() as f64).round();
+    if ticks >= u64::MAX as f64 {
+        return Err(SimError::SimulationError(format!(
+            "Время {} выходит за допустимый диапазон",
+            seconds
+        )));
+    }
+    Ok(ticks as u64)
+}
+
+fn ticks_to_seconds(ticks: u64) -> f64 {
+    ticks as f64 / resolution() as f64
+}
+
 /// Тип для представления времени в симуляции
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
-pub struct SimTime(f64);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+pub struct SimTime(u64);
 
 impl SimTime {
-    pub const ZERO: SimTime = SimTime(0.0);
+    pub const ZERO: SimTime = SimTime(0);
+    pub const MAX: SimTime = SimTime(u64::MAX);
 
-    pub fn new(seconds: f64) -> Self {
-        SimTime(seconds.max(0.0))
+    pub const fn from_ticks(ticks: u64) -> Self {
+        SimTime(ticks)
     }
 
-    pub fn as_seconds(&self) -> f64 {
+    pub fn ticks(&self) -> u64 {
         self.0
     }
 
-    pub fn from_seconds(seconds: f64) -> Self {
-        SimTime(seconds)
+    /// Перевести секунды в тики. Отрицательные, бесконечные и NaN значения отклоняются
+    pub fn from_seconds(seconds: f64) -> Result<Self, SimError> {
+        seconds_to_ticks(seconds).map(SimTime)
+    }
+
+    pub fn as_seconds(&self) -> f64 {
+        ticks_to_seconds(self.0)
+    }
+
+    pub fn checked_add(self, duration: Duration) -> Option<SimTime> {
+        self.0.checked_add(duration.0).map(SimTime)
+    }
+
+    /// Сколько прошло от `earlier` до `self` (ноль, если `earlier` позже)
+    pub fn duration_since(self, earlier: SimTime) -> Duration {
+        Duration(self.0.saturating_sub(earlier.0))
     }
 }
 
-impl Add for SimTime {
+impl Add<Duration> for SimTime {
     type Output = Self;
 
-    fn add(self, other: Self) -> Self {
-        SimTime(self.0 + other.0)
+    fn add(self, other: Duration) -> Self {
+        SimTime(self.0.saturating_add(other.0))
+    }
+}
+
+impl AddAssign<Duration> for SimTime {
+    fn add_assign(&mut self, other: Duration) {
+        *self = *self + other;
     }
 }
 
 impl Sub for SimTime {
-    type Output = Self;
+    type Output = Duration;
 
-    fn sub(self, other: Self) -> Self {
-        SimTime((self.0 - other.0).max(0.0))
+    fn sub(self, other: Self) -> Duration {
+        self.duration_since(other)
     }
 }
 
 impl fmt::Display for SimTime {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:.3}s", self.0)
+        write!(f, "{:.3}s", self.as_seconds())
     }
 }
 
-/// Длительность для asynchronix
-#[derive(Debug, Clone, Copy)]
-pub struct Duration(f64);
+/// Длительность в тиках модельного времени
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+pub struct Duration(u64);
 
 impl Duration {
-    pub fn from_seconds(secs: f64) -> Self {
-        Duration(secs)
+    pub const ZERO: Duration = Duration(0);
+
+    pub const fn from_ticks(ticks: u64) -> Self {
+        Duration(ticks)
     }
 
-    pub fn as_seconds(&self) -> f64 {
+    pub fn ticks(&self) -> u64 {
         self.0
     }
+
+    /// Перевести секунды в тики. Отрицательные, бесконечные и NaN значения отклоняются
+    pub fn from_seconds(secs: f64) -> Result<Self, SimError> {
+        seconds_to_ticks(secs).map(Duration)
+    }
+
+    pub fn as_seconds(&self) -> f64 {
+        ticks_to_seconds(self.0)
+    }
+}
+
+impl Add for Duration {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Duration(self.0.saturating_add(other.0))
+    }
+}
+
+impl AddAssign for Duration {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Duration {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Duration(self.0.saturating_sub(other.0))
+    }
+}
+
+impl fmt::Display for Duration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.3}s", self.as_seconds())
+    }
 }
@@ -2,6 +2,8 @@
 
 use thiserror::Error;
 
+use crate::process::Interrupt;
+
 #[derive(Error, Debug)]
 pub enum SimError {
     #[error("Lua error: {0}")]
@@ -15,6 +17,9 @@ pub enum SimError {
 
     #[error("Process error: {0}")]
     ProcessError(String),
+
+    #[error("Process interrupted: {0}")]
+    Interrupted(Interrupt),
 }
 
 impl From<String> for SimError {
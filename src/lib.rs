@@ -2,6 +2,7 @@
 
 pub mod core;
 pub mod lua;
+pub mod process;
 pub mod resources;
 pub mod error;
 
@@ -10,7 +11,8 @@ pub use simulator::Simulator;
 pub use error::SimError;
 
 pub mod prelude {
-    pub use crate::core::SimTime;
+    pub use crate::core::{SimTime, Duration, Distribution};
+    pub use crate::process::{ProcessCtx, ProcessHandle, ProcessOutcome, ProcessResult};
     pub use crate::Simulator;
     pub use crate::SimError;
 }
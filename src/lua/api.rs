@@ -1,16 +1,34 @@
 //! API функции для Lua
 
-use mlua::{Lua, Result, Value};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use mlua::{FromLuaMulti, Function, Lua, Result, Table, UserData, UserDataMethods, Value, Variadic};
+use rand::rngs::StdRng;
+use rand::Rng;
 use tokio::sync::mpsc;
 use tracing::debug;
 
+use super::convert::lua_to_item;
 use super::process::{ProcessMessage, LogLevel};
+use crate::core::{Distribution, Duration, RandomStreams, SharedRng};
+use crate::resources::{ItemFilter, JointMode, RequestOptions};
+
+/// Процесс, который сейчас выполняется в общей Lua VM: API отправляет
+/// сообщения от его имени
+pub(crate) struct Caller {
+    pub name: String,
+    pub tx: mpsc::UnboundedSender<ProcessMessage>,
+}
+
+/// Текущий процесс VM; `None`, пока ни один процесс не выполняется
+pub(crate) type CurrentCaller = Rc<RefCell<Option<Caller>>>;
+
+/// Имя потока случайных чисел, из которого сейчас берёт `math.random`
+pub(crate) type StreamFn = Rc<dyn Fn() -> String>;
 
 /// Регистрация API функций в Lua
-pub fn register_api(
-    lua: &Lua,
-    tx: mpsc::UnboundedSender<ProcessMessage>,
-) -> Result<()> {
+pub(crate) fn register_api(lua: &Lua, caller: CurrentCaller) -> Result<()> {
     let globals = lua.globals();
 
     // Инициализируем переменную времени
@@ -25,48 +43,164 @@ pub fn register_api(
     globals.set("now", now_fn)?;
 
     // _rust_wait_start(seconds) - внутренняя функция для начала ожидания
-    let tx_wait = tx.clone();
+    let caller_wait = caller.clone();
     let wait_start_fn = lua.create_function(move |_, seconds: f64| {
         if seconds < 0.0 {
             return Err(mlua::Error::external("wait time cannot be negative"));
         }
+        let duration = Duration::from_seconds(seconds).map_err(mlua::Error::external)?;
 
         // Отправляем сообщение о wait
-        tx_wait.send(ProcessMessage::Wait(seconds))
-            .map_err(|e| mlua::Error::external(format!("failed to send wait: {}", e)))?;
+        send(&caller_wait, ProcessMessage::Wait(duration))?;
 
         Ok(())
     })?;
     globals.set("_rust_wait_start", wait_start_fn)?;
 
+    // Значения, с которыми процесс возобновлён: (статус, значение).
+    // Прерывание выбрасывается как ошибка-таблица {interrupted = true, cause = ..., ...},
+    // ошибка ядра (например, неизвестный контейнер) — как строка; обе можно поймать pcall
+    lua.load(r#"
+        function _resume_result(status, value)
+            if status == "interrupt" or status == "error" then
+                error(value, 0)
+            end
+            return value
+        end
+    "#).exec()?;
+
     // Определяем wait в Lua - она вызывает _rust_wait_start и делает yield
     lua.load(r#"
         function wait(seconds)
             _rust_wait_start(seconds)
-            coroutine.yield()
+            _resume_result(coroutine.yield())
+        end
+    "#).exec()?;
+
+    // _rust_request_start(resource, options) - внутренняя функция для постановки в очередь
+    let caller_request = caller.clone();
+    let request_start_fn = lua.create_function(move |_, (resource, units, options): (String, Option<i64>, Option<Table>)| {
+        let mut options = request_options(options)?;
+        if let Some(units) = units {
+            options.units = request_units(units)?;
+        }
+        send(&caller_request, ProcessMessage::Request(resource, options))?;
+        Ok(Value::Nil)
+    })?;
+    globals.set("_rust_request_start", request_start_fn)?;
+
+    // request(resource, options) - приостанавливает процесс до выдачи ресурса
+    // и возвращает таблицу с информацией о выдаче.
+    // options: {priority = n} - приоритет для приоритетных ресурсов,
+    // {preempt = false} - не вытеснять владельцев вытесняющего ресурса,
+    // {requeue = true} - при вытеснении во время wait вернуться в очередь и доработать
+    // остаток wait; вытесненный во время другой операции получает прерывание,
+    // {timeout = t} - уйти из очереди, если ресурс не выдан за t секунд,
+    // {units = n} - получить сразу n единиц (не больше наибольшей ёмкости ресурса);
+    // то же можно записать как request(resource, n, options),
+    // {attrs = {...}} - атрибуты запроса для дисциплины очереди (например, {service = 5}),
+    // {skills = {"english"}} - выдать только единицы пула с этими навыками; имя
+    // назначенной единицы вернётся в поле unit (все имена - в assigned).
+    // Если ресурс не получен (таймаут или отказ от очереди), возвращается
+    // {granted = false, reason = "timeout" | "balked", ...}
+    lua.load(r#"
+        function request(resource, units, options)
+            if type(units) ~= "number" then
+                units, options = nil, units
+            end
+            _rust_request_start(resource, units, options)
+            return _resume_result(coroutine.yield())
         end
     "#).exec()?;
 
-    // request(resource)
-    let tx_request = tx.clone();
-    let request_fn = lua.create_function(move |_, resource: String| {
-        tx_request.send(ProcessMessage::Request(resource))
-            .map_err(|e| mlua::Error::external(format!("failed to send request: {}", e)))?;
+    // _rust_request_joint_start(resources, mode) - совместный запрос
+    let caller_joint = caller.clone();
+    let joint_start_fn = lua.create_function(move |_, (resources, all): (Vec<String>, bool)| {
+        let mode = if all { JointMode::All } else { JointMode::Any };
+        send(&caller_joint, ProcessMessage::RequestJoint(resources, mode))?;
         Ok(Value::Nil)
     })?;
-    globals.set("request", request_fn)?;
+    globals.set("_rust_request_joint_start", joint_start_fn)?;
+
+    // request_all({"surgeon", "nurse", "or"}) - получить все ресурсы одновременно;
+    // пока запрос ждёт, ресурсы не удерживаются. Возвращает
+    // {granted = true, resources = {...}, requested_at, granted_at, wait_time}.
+    // request_any({"a", "b"}) - получить первый освободившийся; результат как у request
+    lua.load(r#"
+        function request_all(resources)
+            _rust_request_joint_start(resources, true)
+            return _resume_result(coroutine.yield())
+        end
+
+        function request_any(resources)
+            _rust_request_joint_start(resources, false)
+            return _resume_result(coroutine.yield())
+        end
+    "#).exec()?;
 
-    // release(resource)
-    let tx_release = tx.clone();
-    let release_fn = lua.create_function(move |_, resource: String| {
-        tx_release.send(ProcessMessage::Release(resource))
-            .map_err(|e| mlua::Error::external(format!("failed to send release: {}", e)))?;
+    // release(resource, units) - без units освобождается весь самый ранний захват.
+    // Освобождение чужого или неизвестного ресурса - ошибка, которую выбросит
+    // ближайшее ожидание; всё, что процесс держит при завершении, освобождается само
+    let caller_release = caller.clone();
+    let release_fn = lua.create_function(move |_, (resource, units): (String, Option<i64>)| {
+        let units = units.map(request_units).transpose()?;
+        send(&caller_release, ProcessMessage::Release(resource, units))?;
         Ok(Value::Nil)
     })?;
     globals.set("release", release_fn)?;
 
+    // _rust_put_start(target, value, priority) - число для контейнера или
+    // предмет для хранилища; что именно это за ресурс, решает ядро
+    let caller_put = caller.clone();
+    let put_start_fn = lua.create_function(move |_, (target, value, priority): (String, Value, Option<i64>)| {
+        let item = lua_to_item(&value)?;
+        send(&caller_put, ProcessMessage::PutItem(target, item, priority.unwrap_or(0)))?;
+        Ok(Value::Nil)
+    })?;
+    globals.set("_rust_put_start", put_start_fn)?;
+
+    let caller_get = caller.clone();
+    let get_start_fn = lua.create_function(move |_, (container, amount): (String, f64)| {
+        send(&caller_get, ProcessMessage::Get(container, amount))?;
+        Ok(Value::Nil)
+    })?;
+    globals.set("_rust_get_start", get_start_fn)?;
+
+    let caller_get_item = caller.clone();
+    let get_item_start_fn = lua.create_function(move |lua, (store, filter): (String, Option<mlua::Function>)| {
+        let filter = match filter {
+            Some(filter) => Some(ItemFilter::Lua(lua.create_registry_value(filter)?)),
+            None => None,
+        };
+        send(&caller_get_item, ProcessMessage::GetItem(store, filter))?;
+        Ok(Value::Nil)
+    })?;
+    globals.set("_rust_get_item_start", get_item_start_fn)?;
+
+    // put(container, amount) / get(container, amount) - ждут, пока в контейнере
+    // хватит места или содержимого, и возвращают таблицу
+    // {container, amount, level, requested_at, completed_at, wait_time}.
+    // put(store, item, priority) / get(store, filter) - положить предмет
+    // (таблицу, строку, число) и забрать первый предмет, для которого
+    // filter(item) истинно; get без фильтра забирает очередной предмет
+    lua.load(r#"
+        function put(target, value, priority)
+            _rust_put_start(target, value, priority)
+            return _resume_result(coroutine.yield())
+        end
+
+        function get(target, arg)
+            if type(arg) == "number" then
+                _rust_get_start(target, arg)
+            else
+                _rust_get_item_start(target, arg)
+            end
+            return _resume_result(coroutine.yield())
+        end
+    "#).exec()?;
+
     // log(message, level)
-    let tx_log = tx.clone();
+    let caller_log = caller.clone();
     let log_fn = lua.create_function(move |_, (message, level): (String, Option<String>)| {
         let log_level = match level.as_deref() {
             Some("warning") | Some("warn") => LogLevel::Warning,
@@ -75,23 +209,208 @@ pub fn register_api(
             _ => LogLevel::Info,
         };
 
-        tx_log.send(ProcessMessage::Log(message, log_level))
-            .map_err(|e| mlua::Error::external(format!("failed to send log: {}", e)))?;
+        send(&caller_log, ProcessMessage::Log(message, log_level))?;
 
         Ok(())
     })?;
     globals.set("log", log_fn)?;
 
-    // spawn(name, function_name)
-    let tx_spawn = tx.clone();
-    let spawn_fn = lua.create_function(move |_, (name, func_name): (String, String)| {
-        tx_spawn.send(ProcessMessage::Spawn(name, func_name))
-            .map_err(|e| mlua::Error::external(format!("failed to spawn: {}", e)))?;
+    let caller_spawn = caller.clone();
+    let spawn_start_fn = lua.create_function(move |_, (name, func_name, args): (Option<String>, String, Variadic<Value>)| {
+        let args = args.iter().map(lua_to_item).collect::<Result<Vec<_>>>()?;
+        send(&caller_spawn, ProcessMessage::Spawn(name, func_name, args))?;
+        Ok(Value::Nil)
+    })?;
+    globals.set("_rust_spawn_start", spawn_start_fn)?;
+
+    // spawn(name, function_name, ...) - остальные аргументы (числа, строки, таблицы)
+    // получит функция нового процесса; таблицы передаются копией.
+    // Возвращает ссылку на процесс - его имя; при name = nil имя
+    // генерируется: "<function_name>#<номер>". Занятое имя или неизвестная
+    // функция - ошибка. Новый процесс стартует в тот же момент, после вызвавшего
+    lua.load(r#"
+        function spawn(name, function_name, ...)
+            _rust_spawn_start(name, function_name, ...)
+            return _resume_result(coroutine.yield())
+        end
+    "#).exec()?;
+
+    // interrupt(process, cause) - прервать ожидание процесса: его wait/request/get/join
+    // выбросит {interrupted = true, cause = ..., remaining = ...}. Если операция
+    // процесса уже завершилась, он получит её результат, а прерывание выбросит
+    // его следующее ожидание; ещё не стартовавший процесс сразу завершается
+    // прерыванием. Прервать себя, неизвестный или завершившийся
+    // процесс нельзя - ошибку выбросит ближайшее ожидание
+    let caller_interrupt = caller.clone();
+    let interrupt_fn = lua.create_function(move |_, (process, cause): (String, Option<String>)| {
+        let cause = cause.unwrap_or_else(|| "interrupt".to_string());
+        send(&caller_interrupt, ProcessMessage::Interrupt(process, cause))?;
         Ok(())
     })?;
-    globals.set("spawn", spawn_fn)?;
+    globals.set("interrupt", interrupt_fn)?;
+
+    let join_start_fn = lua.create_function(move |_, process: String| {
+        send(&caller, ProcessMessage::Join(process))?;
+        Ok(Value::Nil)
+    })?;
+    globals.set("_rust_join_start", join_start_fn)?;
+
+    // join(process) / wait_for(process) - дождаться завершения процесса и получить
+    // значения, которые вернула его функция. Если процесс завершился ошибкой,
+    // join выбрасывает её; неизвестный процесс - тоже ошибка
+    lua.load(r#"
+        function join(process)
+            _rust_join_start(process)
+            local results = _resume_result(coroutine.yield())
+            return table.unpack(results, 1, results.n)
+        end
+
+        wait_for = join
+    "#).exec()?;
 
     debug!("Lua API functions registered");
 
     Ok(())
 }
+
+/// Отправить сообщение ядру от имени текущего процесса
+fn send(caller: &CurrentCaller, message: ProcessMessage) -> Result<()> {
+    let caller = caller.borrow();
+    let caller = caller
+        .as_ref()
+        .ok_or_else(|| mlua::Error::external("process API called outside of a process"))?;
+    caller.tx.send(message)
+        .map_err(|e| mlua::Error::external(format!("failed to send message: {}", e)))
+}
+
+/// Разбор таблицы параметров `request`
+fn request_options(options: Option<Table>) -> Result<RequestOptions> {
+    let mut result = RequestOptions::default();
+
+    if let Some(options) = options {
+        if let Some(priority) = options.get::<_, Option<i32>>("priority")? {
+            result.priority = priority;
+        }
+        if let Some(preempt) = options.get::<_, Option<bool>>("preempt")? {
+            result.preempt = preempt;
+        }
+        if let Some(requeue) = options.get::<_, Option<bool>>("requeue")? {
+            result.requeue = requeue;
+        }
+        if let Some(timeout) = options.get::<_, Option<f64>>("timeout")? {
+            if timeout < 0.0 {
+                return Err(mlua::Error::external("request timeout cannot be negative"));
+            }
+            result.timeout = Some(Duration::from_seconds(timeout).map_err(mlua::Error::external)?);
+        }
+        if let Some(units) = options.get::<_, Option<i64>>("units")? {
+            result.units = request_units(units)?;
+        }
+        let attributes: Value = options.get("attrs")?;
+        result.attributes = lua_to_item(&attributes)?;
+        if let Some(skills) = options.get::<_, Option<Vec<String>>>("skills")? {
+            result.skills = skills;
+        }
+    }
+
+    Ok(result)
+}
+
+/// Число единиц в `request`/`release` должно быть положительным
+fn request_units(units: i64) -> Result<usize> {
+    usize::try_from(units)
+        .ok()
+        .filter(|&units| units > 0)
+        .ok_or_else(|| mlua::Error::external(format!("units must be positive, got {}", units)))
+}
+
+/// Замена `math.random` и `math.randomseed` потоком с именем `stream()` из общих
+/// потоков симулятора, а также `random_stream(name)` — именованный поток,
+/// общий для всех процессов: `random_stream("arrivals"):random(1, 6)`,
+/// и таблица `dist` с распределениями: `dist.exp(mean)`, `dist.normal(mean, sd)`, ...
+pub(crate) fn register_random(lua: &Lua, streams: Rc<RefCell<RandomStreams>>, stream: StreamFn) -> Result<()> {
+    let math: Table = lua.globals().get("math")?;
+
+    // Поток выбирается при каждом вызове: в общей VM это поток выполняющегося процесса
+    let current: Rc<dyn Fn() -> SharedRng> = {
+        let streams = streams.clone();
+        let stream = stream.clone();
+        Rc::new(move || streams.borrow_mut().stream(&stream()))
+    };
+
+    let random_stream = current.clone();
+    let random_fn = lua.create_function(move |_, (m, n): (Option<i64>, Option<i64>)| {
+        random_value(&mut random_stream().borrow_mut(), m, n)
+    })?;
+    math.set("random", random_fn)?;
+
+    let reseed_streams = streams.clone();
+    let randomseed_fn = lua.create_function(move |_, seed: Option<i64>| {
+        reseed_streams.borrow_mut().reseed(&stream(), seed.unwrap_or(0) as u64);
+        Ok(())
+    })?;
+    math.set("randomseed", randomseed_fn)?;
+
+    let stream_fn = lua.create_function(move |_, name: String| {
+        Ok(LuaRng(streams.borrow_mut().stream(&name)))
+    })?;
+    lua.globals().set("random_stream", stream_fn)?;
+
+    // dist.*(...) - значение из распределения по тому же потоку, что и math.random
+    let dist = lua.create_table()?;
+    dist.set("constant", dist_fn(lua, &current, Distribution::constant)?)?;
+    dist.set("exp", dist_fn(lua, &current, Distribution::exponential)?)?;
+    dist.set("normal", dist_fn(lua, &current, |(mean, sd)| Distribution::normal(mean, sd))?)?;
+    dist.set("lognormal", dist_fn(lua, &current, |(mu, sigma)| Distribution::log_normal(mu, sigma))?)?;
+    dist.set("uniform", dist_fn(lua, &current, |(low, high)| Distribution::uniform(low, high))?)?;
+    dist.set("triangular", dist_fn(lua, &current, |(low, mode, high)| Distribution::triangular(low, mode, high))?)?;
+    dist.set("erlang", dist_fn(lua, &current, |(k, mean)| Distribution::erlang(k, mean))?)?;
+    dist.set("gamma", dist_fn(lua, &current, |(shape, scale)| Distribution::gamma(shape, scale))?)?;
+    dist.set("weibull", dist_fn(lua, &current, |(shape, scale)| Distribution::weibull(shape, scale))?)?;
+    dist.set("poisson", dist_fn(lua, &current, Distribution::poisson)?)?;
+    dist.set("empirical", dist_fn(lua, &current, Distribution::empirical)?)?;
+    dist.set("discrete", dist_fn(lua, &current, |(values, weights): (Vec<f64>, Vec<f64>)| {
+        Distribution::discrete(values, &weights)
+    })?)?;
+    lua.globals().set("dist", dist)?;
+
+    Ok(())
+}
+
+/// Lua-функция, которая строит распределение из аргументов и сразу берёт из него значение
+fn dist_fn<'lua, A, F>(lua: &'lua Lua, rng: &Rc<dyn Fn() -> SharedRng>, build: F) -> Result<Function<'lua>>
+where
+    A: FromLuaMulti<'lua>,
+    F: Fn(A) -> std::result::Result<Distribution, crate::SimError> + 'static,
+{
+    let rng = rng.clone();
+    lua.create_function(move |_, args: A| {
+        let distribution = build(args).map_err(mlua::Error::external)?;
+        Ok(distribution.sample(&mut rng().borrow_mut()))
+    })
+}
+
+/// Именованный поток случайных чисел в Lua
+struct LuaRng(SharedRng);
+
+impl UserData for LuaRng {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("random", |_, this, (m, n): (Option<i64>, Option<i64>)| {
+            random_value(&mut this.0.borrow_mut(), m, n)
+        });
+    }
+}
+
+/// Семантика `math.random` из Lua 5.4: без аргументов — число из [0, 1),
+/// `random(m)` — целое из [1, m], `random(m, n)` — целое из [m, n]
+fn random_value(rng: &mut StdRng, m: Option<i64>, n: Option<i64>) -> Result<Value<'static>> {
+    let (low, high) = match (m, n) {
+        (None, _) => return Ok(Value::Number(rng.gen::<f64>())),
+        (Some(m), None) => (1, m),
+        (Some(m), Some(n)) => (m, n),
+    };
+    if low > high {
+        return Err(mlua::Error::external("bad argument to 'random' (interval is empty)"));
+    }
+    Ok(Value::Integer(rng.gen_range(low..=high)))
+}
@@ -0,0 +1,105 @@
+//! Перевод значений Lua в предметы хранилищ и обратно
+
+use mlua::{Lua, Result as LuaResult, Value};
+use serde_json::{Map, Number};
+
+use crate::resources::Item;
+
+/// Глубже этого вложенность таблиц считается циклом
+const MAX_DEPTH: usize = 64;
+
+/// Lua-значение в предмет: таблица, все ключи которой целые из 1..#t,
+/// становится массивом, остальные таблицы — объектом (числовые ключи
+/// превращаются в строки)
+pub(crate) fn lua_to_item(value: &Value) -> LuaResult<Item> {
+    to_item(value, 0)
+}
+
+fn to_item(value: &Value, depth: usize) -> LuaResult<Item> {
+    if depth > MAX_DEPTH {
+        return Err(mlua::Error::external("item is nested too deeply (cyclic table?)"));
+    }
+
+    Ok(match value {
+        Value::Nil => Item::Null,
+        Value::Boolean(b) => Item::Bool(*b),
+        Value::Integer(i) => Item::Number((*i).into()),
+        Value::Number(n) => Number::from_f64(*n)
+            .map(Item::Number)
+            .ok_or_else(|| mlua::Error::external(format!("item number {} is not finite", n)))?,
+        Value::String(s) => Item::String(s.to_str()?.to_string()),
+        Value::Table(table) => {
+            let len = table.raw_len();
+            let mut entries = Vec::new();
+            for pair in table.clone().pairs::<Value, Value>() {
+                entries.push(pair?);
+            }
+
+            // Массив — только если все ключи целые из 1..=len; дырки
+            // становятся null, а любой другой ключ делает таблицу объектом
+            let index = |key: &Value| match key {
+                Value::Integer(i) if (1..=len as i64).contains(i) => Some(*i as usize - 1),
+                _ => None,
+            };
+            let is_array = len > 0 && entries.iter().all(|(key, _)| index(key).is_some());
+            if is_array {
+                let mut array = vec![Item::Null; len];
+                for (key, value) in &entries {
+                    if let Some(i) = index(key) {
+                        array[i] = to_item(value, depth + 1)?;
+                    }
+                }
+                Item::Array(array)
+            } else {
+                let mut object = Map::new();
+                for (key, value) in &entries {
+                    let key = match key {
+                        Value::String(s) => s.to_str()?.to_string(),
+                        Value::Integer(i) => i.to_string(),
+                        other => {
+                            return Err(mlua::Error::external(format!(
+                                "item table key of type {} is not supported",
+                                other.type_name()
+                            )))
+                        }
+                    };
+                    object.insert(key, to_item(value, depth + 1)?);
+                }
+                Item::Object(object)
+            }
+        }
+        other => {
+            return Err(mlua::Error::external(format!(
+                "value of type {} cannot be stored as an item",
+                other.type_name()
+            )))
+        }
+    })
+}
+
+/// Предмет в Lua-значение
+pub(crate) fn item_to_lua<'lua>(lua: &'lua Lua, item: &Item) -> LuaResult<Value<'lua>> {
+    Ok(match item {
+        Item::Null => Value::Nil,
+        Item::Bool(b) => Value::Boolean(*b),
+        Item::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Number(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Item::String(s) => Value::String(lua.create_string(s)?),
+        Item::Array(array) => {
+            let table = lua.create_table_with_capacity(array.len(), 0)?;
+            for (i, value) in array.iter().enumerate() {
+                table.raw_set(i + 1, item_to_lua(lua, value)?)?;
+            }
+            Value::Table(table)
+        }
+        Item::Object(object) => {
+            let table = lua.create_table_with_capacity(0, object.len())?;
+            for (key, value) in object {
+                table.raw_set(key.as_str(), item_to_lua(lua, value)?)?;
+            }
+            Value::Table(table)
+        }
+    })
+}
@@ -1,32 +1,68 @@
 //! Движок для управления Lua процессами
 
-use mlua::Result as LuaResult;
+use mlua::{Lua, Result as LuaResult};
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 use tokio::sync::mpsc;
-use tracing::{info, debug};
+use tracing::{info, debug, error};
 
+use crate::core::{Duration, RandomStreams};
+use crate::resources::Item;
+
+use super::api::{self, Caller, CurrentCaller};
+use super::convert::item_to_lua;
 use super::process::{LuaProcess, ProcessMessage, ProcessState, LuaCommand};
 
+/// Lua-процессы симулятора. Все они — корутины одной VM: скрипт выполняется
+/// один раз при загрузке, его глобальные таблицы видны всем процессам,
+/// а `spawn` только создаёт новую корутину
 pub struct LuaEngine {
+    lua: Lua,
+    /// Процесс, от имени которого сейчас работает API
+    caller: CurrentCaller,
     processes: HashMap<String, LuaProcess>,
     process_receivers: HashMap<String, mpsc::UnboundedReceiver<ProcessMessage>>,
-    scripts: HashMap<String, String>, // Храним загруженные скрипты
 }
 
 impl LuaEngine {
     pub fn new() -> Self {
+        Self::with_streams(Rc::new(RefCell::new(RandomStreams::new(0))))
+    }
+
+    /// Движок, процессы которого берут случайные числа из общих потоков `streams`
+    pub fn with_streams(streams: Rc<RefCell<RandomStreams>>) -> Self {
+        let lua = Lua::new();
+        let caller: CurrentCaller = Rc::new(RefCell::new(None));
+
+        // math.random каждого процесса берёт числа из его потока "process:<имя>",
+        // код вне процессов — из потока "lua"
+        let current = caller.clone();
+        let stream = Rc::new(move || match &*current.borrow() {
+            Some(caller) => format!("process:{}", caller.name),
+            None => "lua".to_string(),
+        });
+        api::register_api(&lua, caller.clone())
+            .and_then(|()| api::register_random(&lua, streams, stream))
+            .expect("failed to register Lua API");
+
         Self {
+            lua,
+            caller,
             processes: HashMap::new(),
             process_receivers: HashMap::new(),
-            scripts: HashMap::new(),
         }
     }
 
+    /// Выполнить `script` в общей VM и создать процесс, вызывающий его функцию
+    /// `function_name` с аргументами `args`.
+    /// Повторная загрузка скрипта заново выполняет его верхний уровень
     pub fn create_process(
         &mut self,
         name: String,
         script_content: &str,
         function_name: &str,
+        args: Vec<Item>,
     ) -> LuaResult<()> {
         if self.processes.contains_key(&name) {
             return Err(mlua::Error::external(format!(
@@ -35,43 +71,41 @@ impl LuaEngine {
             )));
         }
 
-        let (process, receiver) = LuaProcess::new(
-            name.clone(),
-            script_content,
-            function_name,
-        )?;
+        // Верхний уровень скрипта выполняется от имени создаваемого процесса
+        let (tx, receiver) = mpsc::unbounded_channel();
+        let caller = Caller { name: name.clone(), tx: tx.clone() };
+        self.with_caller(caller, |lua| lua.load(script_content).exec())?;
+
+        let function: mlua::Function = self.lua.globals().get(function_name)?;
+        let process = LuaProcess::new(name.clone(), &self.lua, function, args, tx)?;
 
         self.processes.insert(name.clone(), process);
         self.process_receivers.insert(name.clone(), receiver);
-        
-        // Сохраняем скрипт для возможности создания новых процессов
-        if !self.scripts.contains_key(function_name) {
-            self.scripts.insert(function_name.to_string(), script_content.to_string());
-        }
 
         info!("Создан процесс: {}", name);
         Ok(())
     }
 
+    /// Новый процесс из уже загруженной глобальной функции `function_name`
     pub fn spawn_process(
         &mut self,
         name: String,
         function_name: &str,
+        args: Vec<Item>,
     ) -> Result<(), String> {
         if self.processes.contains_key(&name) {
             return Err(format!("Process with name '{}' already exists", name));
         }
 
-        // Ищем скрипт по имени функции
-        let script_content = self.scripts.get(function_name)
-            .ok_or_else(|| format!("Function '{}' not found in loaded scripts", function_name))?
-            .clone();
+        let function: Option<mlua::Function> = self.lua.globals()
+            .get(function_name)
+            .map_err(|e| format!("Failed to create process: {}", e))?;
+        let function = function
+            .ok_or_else(|| format!("Function '{}' not found in loaded scripts", function_name))?;
 
-        let (process, receiver) = LuaProcess::new(
-            name.clone(),
-            &script_content,
-            function_name,
-        ).map_err(|e| format!("Failed to create process: {}", e))?;
+        let (tx, receiver) = mpsc::unbounded_channel();
+        let process = LuaProcess::new(name.clone(), &self.lua, function, args, tx)
+            .map_err(|e| format!("Failed to create process: {}", e))?;
 
         self.processes.insert(name.clone(), process);
         self.process_receivers.insert(name.clone(), receiver);
@@ -82,12 +116,47 @@ impl LuaEngine {
 
     pub async fn start_process(&mut self, name: &str) -> LuaResult<()> {
         // Просто возобновляем корутину один раз
-        if let Some(process) = self.processes.get_mut(name) {
-            process.resume()?;
+        if let Some(result) = self.resume_process(name) {
+            result?;
         }
         Ok(())
     }
 
+    /// Возобновить корутину процесса (см. `LuaProcess::resume`);
+    /// `None`, если такого процесса нет
+    pub fn resume_process(&mut self, name: &str) -> Option<LuaResult<bool>> {
+        let process = self.processes.get_mut(name)?;
+        if let Err(e) = self.lua.globals().set("_process_name", name) {
+            return Some(Err(e));
+        }
+
+        let previous = self.caller.replace(Some(process.caller()));
+        let result = process.resume(&self.lua);
+        *self.caller.borrow_mut() = previous;
+
+        if !matches!(result, Ok(false)) {
+            // Корутина завершилась: освобождаем ключи registry, брошенные процессом
+            self.lua.expire_registry_values();
+        }
+        Some(result)
+    }
+
+    /// Значения, которые вернула функция завершившегося процесса
+    pub fn take_result(&mut self, name: &str) -> Vec<Item> {
+        self.processes
+            .get_mut(name)
+            .map(LuaProcess::take_result)
+            .unwrap_or_default()
+    }
+
+    /// Выполнить `f` в общей VM от имени `caller`
+    fn with_caller<R>(&self, caller: Caller, f: impl FnOnce(&Lua) -> R) -> R {
+        let previous = self.caller.replace(Some(caller));
+        let result = f(&self.lua);
+        *self.caller.borrow_mut() = previous;
+        result
+    }
+
     pub async fn process_messages(&mut self) -> Vec<(String, ProcessMessage)> {
         let mut messages = Vec::new();
 
@@ -101,30 +170,59 @@ impl LuaEngine {
         messages
     }
 
-    pub fn cleanup_finished(&mut self) {
-        let finished: Vec<String> = self.processes
-            .iter()
-            .filter(|(_, p)| matches!(p.state(), ProcessState::Finished))
-            .map(|(name, _)| name.clone())
-            .collect();
+    /// Забрать сообщения одного процесса в порядке их отправки
+    pub fn take_messages(&mut self, name: &str) -> Vec<ProcessMessage> {
+        let mut messages = Vec::new();
+
+        if let Some(receiver) = self.process_receivers.get_mut(name) {
+            while let Ok(msg) = receiver.try_recv() {
+                debug!("Сообщение от {}: {:?}", name, msg);
+                messages.push(msg);
+            }
+        }
+
+        messages
+    }
 
-        for name in finished {
-            self.processes.remove(&name);
-            self.process_receivers.remove(&name);
-            info!("Процесс {} удален", name);
+    /// Удалить завершившийся процесс вместе с его очередью сообщений
+    pub fn remove_process(&mut self, name: &str) {
+        if self.processes.remove(name).is_some() {
+            self.process_receivers.remove(name);
+            debug!("Процесс {} удален", name);
         }
     }
 
-    pub fn send_command(&mut self, process_name: &str, _command: LuaCommand) -> Result<(), String> {
-        if self.processes.contains_key(process_name) {
-            // Команды больше не используются с корутинами
-            // Процессы возобновляются через ready_queue
+    /// Передать команду процессу. Она будет доставлена в корутину
+    /// при следующем возобновлении процесса из ready_queue
+    pub fn send_command(&mut self, process_name: &str, command: LuaCommand) -> Result<(), String> {
+        if let Some(process) = self.processes.get_mut(process_name) {
+            process.set_command(command);
             Ok(())
         } else {
             Err(format!("Process '{}' not found", process_name))
         }
     }
 
+    /// Проверить предмет Lua-фильтром процесса. Ошибка в фильтре
+    /// логируется и считается несовпадением
+    pub fn matches_filter(&self, process_name: &str, key: &mlua::RegistryKey, item: &Item) -> bool {
+        let Some(process) = self.processes.get(process_name) else {
+            return false;
+        };
+        let matched = self.with_caller(process.caller(), |lua| {
+            let filter: mlua::Function = lua.registry_value(key)?;
+            let matched: mlua::Value = filter.call(item_to_lua(lua, item)?)?;
+            Ok::<_, mlua::Error>(!matches!(matched, mlua::Value::Nil | mlua::Value::Boolean(false)))
+        });
+        match matched {
+            Ok(matched) => matched,
+            Err(e) => {
+                error!("Ошибка в фильтре процесса {}: {}", process_name, e);
+                false
+            }
+        }
+    }
+
     pub fn active_processes(&self) -> Vec<String> {
         self.processes.keys().cloned().collect()
     }
@@ -133,7 +231,11 @@ impl LuaEngine {
         self.processes.get(name).map(|p| p.state())
     }
 
-    pub fn set_process_waiting(&mut self, name: &str, duration: f64) {
+    pub fn has_pending_command(&self, name: &str) -> bool {
+        self.processes.get(name).is_some_and(|p| p.has_pending_command())
+    }
+
+    pub fn set_process_waiting(&mut self, name: &str, duration: Duration) {
         if let Some(process) = self.processes.get_mut(name) {
             process.set_waiting(duration);
         }
@@ -145,6 +247,16 @@ impl LuaEngine {
         }
     }
 
+    pub fn set_process_state(&mut self, name: &str, state: ProcessState) {
+        if let Some(process) = self.processes.get_mut(name) {
+            process.set_state(state);
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.processes.contains_key(name)
+    }
+
     pub fn set_process_active(&mut self, name: &str) {
         if let Some(process) = self.processes.get_mut(name) {
             process.set_active();
@@ -156,10 +268,9 @@ impl LuaEngine {
         self.process_receivers.clear();
     }
 
+    /// Обновить `now()` для всех процессов
     pub fn update_time(&mut self, time: f64) {
-        for process in self.processes.values_mut() {
-            let _ = process.update_time(time);
-        }
+        let _ = self.lua.globals().set("_current_time", time);
     }
 
     pub fn get_process_mut(&mut self, name: &str) -> Option<&mut LuaProcess> {
@@ -0,0 +1,65 @@
+//! Lua-функции, которые ядро вызывает вне процессов (правила balking, поломки)
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use mlua::{Lua, RegistryKey, Result as LuaResult};
+
+use super::api::register_random;
+use super::convert::item_to_lua;
+use crate::core::RandomStreams;
+use crate::resources::Item;
+
+/// Функция из отдельного скрипта со своей Lua VM; аргументы передаются таблицей
+pub struct LuaFunction {
+    lua: Lua,
+    key: RegistryKey,
+}
+
+impl LuaFunction {
+    pub fn new(script: &str, function_name: &str) -> LuaResult<Self> {
+        Self::load(Lua::new(), script, function_name)
+    }
+
+    /// Функция, в которой `math.random` берёт числа из потока `stream`
+    pub fn with_streams(
+        script: &str,
+        function_name: &str,
+        streams: Rc<RefCell<RandomStreams>>,
+        stream: &str,
+    ) -> LuaResult<Self> {
+        let lua = Lua::new();
+        let stream = stream.to_string();
+        register_random(&lua, streams, Rc::new(move || stream.clone()))?;
+        Self::load(lua, script, function_name)
+    }
+
+    fn load(lua: Lua, script: &str, function_name: &str) -> LuaResult<Self> {
+        lua.load(script).exec()?;
+        let function: mlua::Function = lua.globals().get(function_name)?;
+        let key = lua.create_registry_value(function)?;
+        Ok(Self { lua, key })
+    }
+
+    fn call<'lua>(&'lua self, args: &Item) -> LuaResult<mlua::Value<'lua>> {
+        let function: mlua::Function = self.lua.registry_value(&self.key)?;
+        function.call(item_to_lua(&self.lua, args)?)
+    }
+
+    /// Вызвать как условие: истинно всё, кроме nil и false
+    pub fn call_bool(&self, args: &Item) -> LuaResult<bool> {
+        Ok(!matches!(self.call(args)?, mlua::Value::Nil | mlua::Value::Boolean(false)))
+    }
+
+    /// Вызвать функцию, возвращающую число
+    pub fn call_f64(&self, args: &Item) -> LuaResult<f64> {
+        match self.call(args)? {
+            mlua::Value::Integer(i) => Ok(i as f64),
+            mlua::Value::Number(n) => Ok(n),
+            other => Err(mlua::Error::external(format!(
+                "expected a number, got {}",
+                other.type_name()
+            ))),
+        }
+    }
+}
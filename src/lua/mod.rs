@@ -3,6 +3,9 @@
 mod engine;
 mod process;
 mod api;
+mod convert;
+mod function;
 
 pub use engine::LuaEngine;
+pub use function::LuaFunction;
 pub use process::{LuaProcess, ProcessMessage, ProcessState, LuaCommand, LogLevel};
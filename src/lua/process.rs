@@ -1,22 +1,58 @@
 //! Представление Lua-процесса в симуляции
 
-use mlua::{Lua, Result as LuaResult};
+use mlua::{IntoLuaMulti, Lua, MultiValue, Result as LuaResult};
 use tokio::sync::mpsc;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
-use super::api;
+use super::api::Caller;
+use crate::core::Duration;
+use crate::process::Interrupt;
+use crate::SimError;
+use super::convert::{item_to_lua, lua_to_item};
+use crate::resources::{
+    ContainerOp, Denial, DenialReason, Grant, Item, ItemFilter, JointGrant, JointMode, RequestOptions, StoreOp,
+};
 
 /// Сообщения от Lua процесса к ядру симуляции
 #[derive(Debug)]
 pub enum ProcessMessage {
-    Wait(f64),
-    Request(String),
-    Release(String),
+    Wait(Duration),
+    Request(String, RequestOptions),
+    /// Совместный запрос нескольких ресурсов
+    RequestJoint(Vec<String>, JointMode),
+    /// Освободить ресурс: заданное число единиц или весь самый ранний захват
+    Release(String, Option<usize>),
+    /// Положить количество в контейнер
+    Put(String, f64),
+    /// Забрать количество из контейнера
+    Get(String, f64),
+    /// Положить предмет в хранилище с приоритетом (для PriorityStore)
+    PutItem(String, Item, i64),
+    /// Забрать предмет из хранилища, при необходимости по фильтру
+    GetItem(String, Option<ItemFilter>),
     Finished,
-    Spawn(String, String),
+    /// Дождаться завершения процесса с этим именем
+    Join(String),
+    /// Прервать ожидание процесса: имя процесса и причина
+    Interrupt(String, String),
+    /// Создать Lua-процесс: имя (`None` — сгенерировать), функция и её аргументы
+    Spawn(Option<String>, String, Vec<Item>),
+    /// Создать процесс на Rust, порождённый через `ProcessCtx::spawn` под этим именем
+    SpawnNative(String),
     Log(String, LogLevel),
 }
 
+impl ProcessMessage {
+    /// Ждёт ли процесс ответа ядра на это сообщение. Release, Log и Interrupt
+    /// отправляются без yield и копятся до следующего ожидания
+    pub fn awaits_reply(&self) -> bool {
+        !matches!(
+            self,
+            Self::Release(..) | Self::Log(..) | Self::Interrupt(..) | Self::Finished
+        )
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum LogLevel {
     Info,
@@ -25,12 +61,23 @@ pub enum LogLevel {
     Debug,
 }
 
-/// Команды от ядра симуляции к Lua процессу
+/// Команды от ядра симуляции к процессу (Lua или Rust)
 #[derive(Debug)]
 pub enum LuaCommand {
     Resume,
-    ResourceGranted(String),
-    Error(String),
+    ResourceGranted(Grant),
+    /// Процесс ушёл без ресурса: отказался от очереди или не дождался
+    RequestDenied(Denial),
+    JointGranted(JointGrant),
+    ContainerDone(ContainerOp),
+    StoreDone(StoreOp),
+    /// Процесс, которого ждали через `join`, завершился с этими значениями
+    Joined(Vec<Item>),
+    /// Порождённый процесс создан под этим именем
+    Spawned(String),
+    Interrupt(Interrupt),
+    /// Операция процесса не удалась: ошибка вернётся из ожидания
+    Error(SimError),
     Terminate,
 }
 
@@ -38,62 +85,49 @@ pub enum LuaCommand {
 #[derive(Debug, PartialEq)]
 pub enum ProcessState {
     Active,
-    Waiting(f64),
+    Waiting(Duration),
     WaitingForResource(String),
+    /// Ждёт завершения другого процесса (`join`)
+    WaitingForProcess(String),
     Finished,
 }
 
-/// Представляет один процесс, написанный на Lua
+/// Представляет один процесс, написанный на Lua: корутину в общей VM движка
 pub struct LuaProcess {
     name: String,
-    lua: Lua,
-    coroutine_key: mlua::RegistryKey,
+    /// Корутина процесса; удаляется из registry, когда процесс завершился
+    coroutine_key: Option<mlua::RegistryKey>,
     state: ProcessState,
     tx: mpsc::UnboundedSender<ProcessMessage>,
+    /// Аргументы функции процесса, передаются при первом resume
+    args: Option<Vec<Item>>,
+    /// Значения, которые вернула функция процесса
+    result: Vec<Item>,
+    /// Команда, значения которой вернёт `coroutine.yield` при следующем resume
+    pending_command: Option<LuaCommand>,
 }
 
 impl LuaProcess {
+    /// Процесс, выполняющий `function(args...)` в `lua`; сообщения он отправляет в `tx`
     pub fn new(
         name: String,
-        script_content: &str,
-        function_name: &str,
-    ) -> LuaResult<(Self, mpsc::UnboundedReceiver<ProcessMessage>)> {
-        let (process_tx, process_rx) = mpsc::unbounded_channel();
+        lua: &Lua,
+        function: mlua::Function,
+        args: Vec<Item>,
+        tx: mpsc::UnboundedSender<ProcessMessage>,
+    ) -> LuaResult<Self> {
+        let thread = lua.create_thread(function)?;
+        let coroutine_key = lua.create_registry_value(thread)?;
 
-        // Создаём Lua
-        let lua = Lua::new();
-        
-        // Устанавливаем имя процесса в глобальной переменной
-        {
-            let globals = lua.globals();
-            globals.set("_process_name", name.clone())?;
-            globals.set("_current_time", 0.0)?;
-        }
-        
-        // Регистрируем API
-        api::register_api(&lua, process_tx.clone())?;
-
-        // Загружаем скрипт
-        lua.load(script_content).exec()?;
-
-        // Создаем корутину из функции и сохраняем в registry
-        let coroutine_key = {
-            let globals = lua.globals();
-            let func: mlua::Function = globals.get(function_name)?;
-            let thread = lua.create_thread(func)?;
-            lua.create_registry_value(thread)?
-        };
-
-        Ok((
-            Self {
-                name,
-                lua,
-                coroutine_key,
-                state: ProcessState::Active,
-                tx: process_tx,
-            },
-            process_rx,
-        ))
+        Ok(Self {
+            name,
+            coroutine_key: Some(coroutine_key),
+            state: ProcessState::Active,
+            tx,
+            args: Some(args),
+            result: Vec::new(),
+            pending_command: None,
+        })
     }
 
     /// Возобновляет выполнение корутины
@@ -101,19 +135,41 @@ impl LuaProcess {
     /// - Ok(true) - корутина завершена
     /// - Ok(false) - корутина приостановлена (yield)
     /// - Err(e) - ошибка выполнения
-    pub fn resume(&mut self) -> LuaResult<bool> {
+    pub fn resume(&mut self, lua: &Lua) -> LuaResult<bool> {
+        let Some(key) = &self.coroutine_key else {
+            return Ok(true);
+        };
         if self.state == ProcessState::Finished {
+            self.finish(lua);
             return Ok(true);
         }
 
-        let coroutine: mlua::Thread = self.lua.registry_value(&self.coroutine_key)?;
+        let coroutine: mlua::Thread = lua.registry_value(key)?;
         let status = coroutine.status();
         
         match status {
             mlua::ThreadStatus::Resumable => {
+                // Значения, которые получит ожидающий yield внутри Lua
+                let args = if let Some(args) = self.args.take() {
+                    // Прерванный до старта процесс завершается прерыванием, не начав работу
+                    if let Some(LuaCommand::Interrupt(interrupt)) = self.pending_command.take() {
+                        error!("Процесс {} прерван до старта: {}", self.name, interrupt);
+                        self.finish(lua);
+                        return Err(mlua::Error::external(SimError::Interrupted(interrupt)));
+                    }
+                    args.iter()
+                        .map(|arg| item_to_lua(lua, arg))
+                        .collect::<LuaResult<MultiValue>>()?
+                } else {
+                    match self.pending_command.take() {
+                        Some(command) => command_to_lua(lua, command)?,
+                        None => MultiValue::new(),
+                    }
+                };
+
                 // Пытаемся возобновить корутину
-                match coroutine.resume::<_, mlua::Value>(()) {
-                    Ok(_) => {
+                match coroutine.resume::<_, MultiValue>(args) {
+                    Ok(values) => {
                         // Проверяем новый статус
                         let new_status = coroutine.status();
                         match new_status {
@@ -124,38 +180,65 @@ impl LuaProcess {
                             }
                             mlua::ThreadStatus::Unresumable => {
                                 // Корутина завершилась
-                                self.state = ProcessState::Finished;
+                                self.result = values.iter().map(|value| {
+                                    lua_to_item(value).unwrap_or_else(|e| {
+                                        warn!("Процесс {} вернул значение, которое нельзя передать: {}", self.name, e);
+                                        Item::Null
+                                    })
+                                }).collect();
+                                self.finish(lua);
                                 let _ = self.tx.send(ProcessMessage::Finished);
                                 info!("Процесс {} завершен", self.name);
                                 Ok(true)
                             }
                             mlua::ThreadStatus::Error => {
                                 error!("Процесс {} завершился с ошибкой", self.name);
-                                self.state = ProcessState::Finished;
+                                self.finish(lua);
                                 Ok(true)
                             }
                         }
                     }
                     Err(e) => {
                         error!("Ошибка в процессе {}: {}", self.name, e);
-                        self.state = ProcessState::Finished;
+                        self.finish(lua);
                         Err(e)
                     }
                 }
             }
             mlua::ThreadStatus::Unresumable => {
                 // Корутина уже завершена
-                self.state = ProcessState::Finished;
+                self.finish(lua);
                 Ok(true)
             }
             mlua::ThreadStatus::Error => {
                 error!("Процесс {} в состоянии ошибки", self.name);
-                self.state = ProcessState::Finished;
+                self.finish(lua);
                 Ok(true)
             }
         }
     }
 
+    /// Отметить процесс завершённым и отдать его корутину сборщику мусора
+    fn finish(&mut self, lua: &Lua) {
+        self.state = ProcessState::Finished;
+        if let Some(key) = self.coroutine_key.take() {
+            let _ = lua.remove_registry_value(key);
+        }
+    }
+
+    /// От чьего имени API общей VM отправляет сообщения, пока выполняется процесс
+    pub(crate) fn caller(&self) -> Caller {
+        Caller {
+            name: self.name.clone(),
+            tx: self.tx.clone(),
+        }
+    }
+
+    /// Забрать значения, которые вернула функция завершившегося процесса
+    pub fn take_result(&mut self) -> Vec<Item> {
+        std::mem::take(&mut self.result)
+    }
+
     pub fn state(&self) -> &ProcessState {
         &self.state
     }
@@ -164,7 +247,7 @@ impl LuaProcess {
         &self.name
     }
 
-    pub fn set_waiting(&mut self, duration: f64) {
+    pub fn set_waiting(&mut self, duration: Duration) {
         self.state = ProcessState::Waiting(duration);
     }
 
@@ -172,6 +255,20 @@ impl LuaProcess {
         self.state = ProcessState::WaitingForResource(resource);
     }
 
+    /// Передать процессу команду, которая будет доставлена при следующем resume
+    pub fn set_command(&mut self, command: LuaCommand) {
+        self.pending_command = Some(command);
+    }
+
+    /// Есть ли команда, которую процесс ещё не получил
+    pub fn has_pending_command(&self) -> bool {
+        self.pending_command.is_some()
+    }
+
+    pub fn set_state(&mut self, state: ProcessState) {
+        self.state = state;
+    }
+
     pub fn set_active(&mut self) {
         self.state = ProcessState::Active;
     }
@@ -179,10 +276,105 @@ impl LuaProcess {
     pub fn terminate(&mut self) {
         self.state = ProcessState::Finished;
     }
+}
 
-    pub fn update_time(&mut self, time: f64) -> LuaResult<()> {
-        let globals = self.lua.globals();
-        globals.set("_current_time", time)?;
-        Ok(())
+/// Преобразует команду ядра в значения, возвращаемые из `coroutine.yield`:
+/// статус (`"ok"`, `"interrupt"` или `"error"`) и значение для Lua-обёрток
+fn command_to_lua(lua: &Lua, command: LuaCommand) -> LuaResult<MultiValue<'_>> {
+    match command {
+        LuaCommand::Resume => Ok(MultiValue::new()),
+        LuaCommand::ResourceGranted(grant) => {
+            let info = lua.create_table()?;
+            info.set("granted", true)?;
+            info.set("resource", grant.resource.as_str())?;
+            info.set("units", grant.units)?;
+            if let Some(unit) = grant.assigned.first() {
+                info.set("unit", unit.as_str())?;
+                info.set("assigned", grant.assigned.clone())?;
+            }
+            info.set("requested_at", grant.requested_at.as_seconds())?;
+            info.set("granted_at", grant.granted_at.as_seconds())?;
+            info.set("wait_time", grant.wait_time().as_seconds())?;
+            ("ok", info).into_lua_multi(lua)
+        }
+        LuaCommand::JointGranted(joint) => match joint.mode {
+            // request_any возвращает то же, что request для выбранного ресурса
+            JointMode::Any => match joint.grants.into_iter().next() {
+                Some(grant) => command_to_lua(lua, LuaCommand::ResourceGranted(grant)),
+                None => Ok(MultiValue::new()),
+            },
+            JointMode::All => {
+                let info = lua.create_table()?;
+                info.set("granted", true)?;
+                let names = lua.create_table()?;
+                for (i, grant) in joint.grants.iter().enumerate() {
+                    names.set(i + 1, grant.resource.as_str())?;
+                }
+                info.set("resources", names)?;
+                if let Some(grant) = joint.grants.first() {
+                    info.set("requested_at", grant.requested_at.as_seconds())?;
+                    info.set("granted_at", grant.granted_at.as_seconds())?;
+                    info.set("wait_time", grant.wait_time().as_seconds())?;
+                }
+                ("ok", info).into_lua_multi(lua)
+            }
+        },
+        LuaCommand::RequestDenied(denial) => {
+            let info = lua.create_table()?;
+            info.set("granted", false)?;
+            info.set("reason", match denial.reason {
+                DenialReason::Balked => "balked",
+                DenialReason::TimedOut => "timeout",
+            })?;
+            info.set("resource", denial.resource.as_str())?;
+            info.set("requested_at", denial.requested_at.as_seconds())?;
+            info.set("wait_time", denial.wait_time().as_seconds())?;
+            ("ok", info).into_lua_multi(lua)
+        }
+        LuaCommand::ContainerDone(op) => {
+            let info = lua.create_table()?;
+            info.set("container", op.container.as_str())?;
+            info.set("amount", op.amount)?;
+            info.set("level", op.level)?;
+            info.set("requested_at", op.requested_at.as_seconds())?;
+            info.set("completed_at", op.completed_at.as_seconds())?;
+            info.set("wait_time", op.wait_time().as_seconds())?;
+            ("ok", info).into_lua_multi(lua)
+        }
+        LuaCommand::StoreDone(op) => {
+            let item = match &op.item {
+                Some(item) => item_to_lua(lua, item)?,
+                None => mlua::Value::Nil,
+            };
+            ("ok", item).into_lua_multi(lua)
+        }
+        LuaCommand::Interrupt(interrupt) => {
+            // Lua-обёртки wait/request выбрасывают такую таблицу как ошибку
+            let info = lua.create_table()?;
+            info.set("interrupted", true)?;
+            info.set("cause", interrupt.cause.as_str())?;
+            info.set("time", interrupt.interrupted_at.as_seconds())?;
+            if let Some(remaining) = interrupt.remaining {
+                info.set("remaining", remaining.as_seconds())?;
+            }
+            if let Some(preemption) = interrupt.preemption {
+                info.set("resource", preemption.resource.as_str())?;
+                info.set("by", preemption.by.as_str())?;
+                info.set("usage_since", preemption.usage_since.as_seconds())?;
+            }
+            ("interrupt", info).into_lua_multi(lua)
+        }
+        LuaCommand::Joined(values) => {
+            // join распаковывает таблицу обратно в несколько значений
+            let results = lua.create_table()?;
+            results.set("n", values.len())?;
+            for (i, value) in values.iter().enumerate() {
+                results.set(i + 1, item_to_lua(lua, value)?)?;
+            }
+            ("ok", results).into_lua_multi(lua)
+        }
+        LuaCommand::Spawned(name) => ("ok", name).into_lua_multi(lua),
+        LuaCommand::Error(error) => ("error", error.to_string()).into_lua_multi(lua),
+        LuaCommand::Terminate => Ok(MultiValue::new()),
     }
 }
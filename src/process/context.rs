@@ -0,0 +1,311 @@
+//! Контекст процесса: ожидание, ресурсы и порождение новых процессов
+
+use std::cell::{Cell, RefCell, RefMut};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+use rand::rngs::StdRng;
+use tokio::sync::mpsc;
+
+use crate::core::{Distribution, Duration, RandomStreams, SharedRng, SimTime};
+use crate::lua::{LogLevel, LuaCommand, ProcessMessage};
+use crate::resources::{ContainerOp, Denial, Grant, Item, ItemFilter, JointGrant, JointMode, RequestOptions};
+use crate::SimError;
+
+use super::handle::{ProcessHandle, ProcessResult};
+
+pub(crate) type ProcessFuture = Pin<Box<dyn Future<Output = Result<Vec<Item>, SimError>>>>;
+pub(crate) type ProcessFactory = Box<dyn FnOnce(ProcessCtx) -> ProcessFuture>;
+
+/// Процесс, созданный через `ProcessCtx::spawn` и ещё не переданный движку
+pub(crate) struct PendingSpawn {
+    pub name: String,
+    pub factory: ProcessFactory,
+}
+
+/// Состояние, общее для процесса и движка
+#[derive(Default)]
+pub(crate) struct ProcessShared {
+    /// Команда, которую получит ожидающий future при следующем poll
+    pub command: RefCell<Option<LuaCommand>>,
+}
+
+/// Контекст, который получает процесс на Rust.
+///
+/// Все ожидания идут по модельным часам: `timeout` и `request` приостанавливают
+/// процесс до соответствующего события в симуляции. Другие future (например,
+/// `tokio::time::sleep`) внутри процесса использовать нельзя — симуляция о них
+/// не знает и процесс не будет возобновлён
+#[derive(Clone)]
+pub struct ProcessCtx {
+    name: String,
+    tx: mpsc::UnboundedSender<ProcessMessage>,
+    clock: Rc<Cell<SimTime>>,
+    shared: Rc<ProcessShared>,
+    spawner: Rc<RefCell<Vec<PendingSpawn>>>,
+    streams: Rc<RefCell<RandomStreams>>,
+    rng: SharedRng,
+}
+
+impl ProcessCtx {
+    pub(crate) fn new(
+        name: String,
+        tx: mpsc::UnboundedSender<ProcessMessage>,
+        clock: Rc<Cell<SimTime>>,
+        shared: Rc<ProcessShared>,
+        spawner: Rc<RefCell<Vec<PendingSpawn>>>,
+        streams: Rc<RefCell<RandomStreams>>,
+    ) -> Self {
+        let rng = streams.borrow_mut().stream(&format!("process:{}", name));
+        Self { name, tx, clock, shared, spawner, streams, rng }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Текущее модельное время
+    pub fn now(&self) -> SimTime {
+        self.clock.get()
+    }
+
+    /// Поток случайных чисел процесса, выведенный из seed симулятора.
+    /// Ссылку нельзя держать через `.await`
+    pub fn rng(&self) -> RefMut<'_, StdRng> {
+        self.rng.borrow_mut()
+    }
+
+    /// Значение из распределения по потоку процесса (Lua: `dist.*`)
+    pub fn sample(&self, distribution: &Distribution) -> f64 {
+        distribution.sample(&mut self.rng.borrow_mut())
+    }
+
+    /// Именованный поток, общий для всех процессов (Lua: `random_stream(name)`)
+    pub fn stream(&self, name: &str) -> SharedRng {
+        self.streams.borrow_mut().stream(name)
+    }
+
+    /// Приостановить процесс на `duration` модельного времени.
+    /// Если процесс прервут раньше, вернётся `SimError::Interrupted`
+    pub async fn timeout(&self, duration: Duration) -> Result<(), SimError> {
+        self.suspend(ProcessMessage::Wait(duration)).await?;
+        Ok(())
+    }
+
+    /// Встать в очередь к ресурсу и дождаться его выдачи
+    pub async fn request(&self, resource: &str) -> Result<Grant, SimError> {
+        self.request_with(resource, RequestOptions::default()).await
+    }
+
+    /// Запросить ресурс с параметрами (например, приоритетом).
+    /// Отказ от очереди или уход по таймауту возвращаются как `SimError::ResourceError`
+    pub async fn request_with(&self, resource: &str, options: RequestOptions) -> Result<Grant, SimError> {
+        self.try_request(resource, options).await?.map_err(|denial| {
+            SimError::ResourceError(format!(
+                "Процесс {} не получил ресурс {}: {:?}",
+                denial.process, denial.resource, denial.reason
+            ))
+        })
+    }
+
+    /// Запросить ресурс, который можно и не получить: внутренний `Err`
+    /// означает, что процесс отказался от очереди или не дождался `timeout`
+    pub async fn try_request(&self, resource: &str, options: RequestOptions) -> Result<Result<Grant, Denial>, SimError> {
+        let message = ProcessMessage::Request(resource.to_string(), options);
+        match self.suspend(message).await? {
+            Some(LuaCommand::ResourceGranted(grant)) => Ok(Ok(grant)),
+            Some(LuaCommand::RequestDenied(denial)) => Ok(Err(denial)),
+            _ => Err(SimError::ProcessError(format!(
+                "Процесс {} возобновлён без выдачи ресурса {}",
+                self.name, resource
+            ))),
+        }
+    }
+
+    /// Получить все перечисленные ресурсы одновременно. Пока запрос ждёт,
+    /// ресурсы не удерживаются, поэтому взаимной блокировки не возникает
+    pub async fn request_all(&self, resources: &[&str]) -> Result<Vec<Grant>, SimError> {
+        Ok(self.request_joint(resources, JointMode::All).await?.grants)
+    }
+
+    /// Получить первый освободившийся из перечисленных ресурсов
+    pub async fn request_any(&self, resources: &[&str]) -> Result<Grant, SimError> {
+        let joint = self.request_joint(resources, JointMode::Any).await?;
+        joint.grants.into_iter().next().ok_or_else(|| {
+            SimError::ProcessError(format!("Процесс {} не получил ни одного ресурса", self.name))
+        })
+    }
+
+    async fn request_joint(&self, resources: &[&str], mode: JointMode) -> Result<JointGrant, SimError> {
+        let names = resources.iter().map(|r| r.to_string()).collect();
+        match self.suspend(ProcessMessage::RequestJoint(names, mode)).await? {
+            Some(LuaCommand::JointGranted(joint)) => Ok(joint),
+            _ => Err(SimError::ProcessError(format!(
+                "Процесс {} возобновлён без выдачи ресурсов {:?}",
+                self.name, resources
+            ))),
+        }
+    }
+
+    /// Положить `amount` в контейнер, дождавшись свободного места
+    pub async fn put(&self, container: &str, amount: f64) -> Result<ContainerOp, SimError> {
+        self.container_op(ProcessMessage::Put(container.to_string(), amount), container).await
+    }
+
+    /// Забрать `amount` из контейнера, дождавшись нужного уровня
+    pub async fn get(&self, container: &str, amount: f64) -> Result<ContainerOp, SimError> {
+        self.container_op(ProcessMessage::Get(container.to_string(), amount), container).await
+    }
+
+    async fn container_op(&self, message: ProcessMessage, container: &str) -> Result<ContainerOp, SimError> {
+        match self.suspend(message).await? {
+            Some(LuaCommand::ContainerDone(op)) => Ok(op),
+            _ => Err(SimError::ProcessError(format!(
+                "Процесс {} возобновлён без операции с контейнером {}",
+                self.name, container
+            ))),
+        }
+    }
+
+    /// Положить предмет в хранилище, дождавшись свободного места
+    pub async fn put_item(&self, store: &str, item: Item) -> Result<(), SimError> {
+        self.put_item_with_priority(store, item, 0).await
+    }
+
+    /// Положить предмет в PriorityStore: меньший приоритет выдаётся раньше
+    pub async fn put_item_with_priority(&self, store: &str, item: Item, priority: i64) -> Result<(), SimError> {
+        self.suspend(ProcessMessage::PutItem(store.to_string(), item, priority)).await?;
+        Ok(())
+    }
+
+    /// Забрать очередной предмет из хранилища
+    pub async fn get_item(&self, store: &str) -> Result<Item, SimError> {
+        self.store_get(store, None).await
+    }
+
+    /// Забрать из FilterStore первый предмет, для которого `filter` истинен
+    pub async fn get_item_filtered<F>(&self, store: &str, filter: F) -> Result<Item, SimError>
+    where
+        F: Fn(&Item) -> bool + 'static,
+    {
+        self.store_get(store, Some(ItemFilter::Native(Box::new(filter)))).await
+    }
+
+    async fn store_get(&self, store: &str, filter: Option<ItemFilter>) -> Result<Item, SimError> {
+        match self.suspend(ProcessMessage::GetItem(store.to_string(), filter)).await? {
+            Some(LuaCommand::StoreDone(op)) => op.item.ok_or_else(|| {
+                SimError::ProcessError(format!("Хранилище {} не вернуло предмет", store))
+            }),
+            _ => Err(SimError::ProcessError(format!(
+                "Процесс {} возобновлён без предмета из хранилища {}",
+                self.name, store
+            ))),
+        }
+    }
+
+    /// Освободить ранее полученный ресурс (все единицы самого раннего захвата).
+    /// Если процесс его не держит, `SimError::ResourceError` вернётся из
+    /// ближайшего ожидания. Не освобождённое до завершения процесса
+    /// освобождается автоматически
+    pub fn release(&self, resource: &str) -> Result<(), SimError> {
+        self.send(ProcessMessage::Release(resource.to_string(), None))
+    }
+
+    /// Вернуть `units` единиц ресурса, оставив остальные за процессом
+    pub fn release_units(&self, resource: &str, units: usize) -> Result<(), SimError> {
+        self.send(ProcessMessage::Release(resource.to_string(), Some(units)))
+    }
+
+    /// Прервать ожидание другого процесса с причиной `cause`: у процесса на Rust
+    /// ожидание вернёт `SimError::Interrupted`, в Lua — выбросит таблицу прерывания.
+    /// Если процесс нельзя прервать, ошибка вернётся из ближайшего ожидания
+    pub fn interrupt(&self, process: &ProcessHandle, cause: &str) -> Result<(), SimError> {
+        self.send(ProcessMessage::Interrupt(process.name().to_string(), cause.to_string()))
+    }
+
+    /// Записать сообщение в лог симуляции от имени процесса
+    pub fn log(&self, message: &str, level: LogLevel) -> Result<(), SimError> {
+        self.send(ProcessMessage::Log(message.to_string(), level))
+    }
+
+    /// Породить новый процесс. Он стартует в текущий момент модельного времени,
+    /// после того как текущий процесс продолжит работу и снова приостановится.
+    /// Если имя уже занято, возвращается `SimError::ProcessError`
+    pub async fn spawn<F, Fut, R>(&self, name: &str, process: F) -> Result<ProcessHandle, SimError>
+    where
+        F: FnOnce(ProcessCtx) -> Fut + 'static,
+        Fut: Future<Output = Result<R, SimError>> + 'static,
+        R: ProcessResult,
+    {
+        self.spawner.borrow_mut().push(PendingSpawn {
+            name: name.to_string(),
+            factory: factory(process),
+        });
+        match self.suspend(ProcessMessage::SpawnNative(name.to_string())).await? {
+            Some(LuaCommand::Spawned(name)) => Ok(ProcessHandle::new(&name)),
+            _ => Err(SimError::ProcessError(format!(
+                "Процесс {} возобновлён без создания процесса {}",
+                self.name, name
+            ))),
+        }
+    }
+
+    /// Дождаться завершения процесса и получить значения, которые вернула
+    /// его функция (Lua или Rust). Ошибка процесса возвращается как `SimError::ProcessError`
+    pub async fn join(&self, process: &ProcessHandle) -> Result<Vec<Item>, SimError> {
+        match self.suspend(ProcessMessage::Join(process.name().to_string())).await? {
+            Some(LuaCommand::Joined(values)) => Ok(values),
+            _ => Err(SimError::ProcessError(format!(
+                "Процесс {} возобновлён без завершения {}",
+                self.name, process
+            ))),
+        }
+    }
+
+    fn send(&self, message: ProcessMessage) -> Result<(), SimError> {
+        self.tx.send(message)
+            .map_err(|e| SimError::ProcessError(format!("failed to send message: {}", e)))
+    }
+
+    /// Отправить сообщение ядру и уступить управление до возобновления процесса
+    async fn suspend(&self, message: ProcessMessage) -> Result<Option<LuaCommand>, SimError> {
+        self.send(message)?;
+        Suspend { yielded: false }.await;
+
+        match self.shared.command.borrow_mut().take() {
+            Some(LuaCommand::Error(error)) => Err(error),
+            Some(LuaCommand::Interrupt(interrupt)) => Err(SimError::Interrupted(interrupt)),
+            command => Ok(command),
+        }
+    }
+}
+
+/// Фабрика процесса, значения которого переводятся в результат для `join`
+pub(crate) fn factory<F, Fut, R>(process: F) -> ProcessFactory
+where
+    F: FnOnce(ProcessCtx) -> Fut + 'static,
+    Fut: Future<Output = Result<R, SimError>> + 'static,
+    R: ProcessResult,
+{
+    Box::new(move |ctx| Box::pin(async move { process(ctx).await.map(R::into_values) }))
+}
+
+/// Future, который один раз возвращает Pending: процесс ждёт, пока ядро
+/// не поставит его в ready_queue и не опросит снова
+struct Suspend {
+    yielded: bool,
+}
+
+impl Future for Suspend {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            Poll::Ready(())
+        } else {
+            self.yielded = true;
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Движок для управления процессами на Rust
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::future::Future;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info};
+
+use super::context::{factory, PendingSpawn, ProcessCtx, ProcessFactory, ProcessFuture, ProcessShared};
+use super::handle::ProcessResult;
+use crate::core::{RandomStreams, SimTime};
+use crate::lua::{LuaCommand, ProcessMessage, ProcessState};
+use crate::resources::Item;
+use crate::SimError;
+
+/// Процесс, написанный на Rust как `async fn(ctx: ProcessCtx)`
+pub struct NativeProcess {
+    name: String,
+    future: Option<ProcessFuture>,
+    shared: Rc<ProcessShared>,
+    state: ProcessState,
+    tx: mpsc::UnboundedSender<ProcessMessage>,
+    /// Значения, которые вернул процесс
+    result: Vec<Item>,
+    /// Опрашивался ли уже future процесса
+    started: bool,
+}
+
+impl NativeProcess {
+    /// Возобновляет выполнение процесса
+    /// Возвращает:
+    /// - Ok(true) - процесс завершен
+    /// - Ok(false) - процесс приостановлен в ожидании события симуляции
+    /// - Err(e) - процесс завершился с ошибкой
+    pub fn resume(&mut self) -> Result<bool, SimError> {
+        let Some(future) = self.future.as_mut() else {
+            return Ok(true);
+        };
+
+        // Прерванный до старта процесс завершается прерыванием, не начав работу
+        if !self.started {
+            self.started = true;
+            let command = self.shared.command.borrow_mut().take();
+            if let Some(LuaCommand::Interrupt(interrupt)) = command {
+                error!("Процесс {} прерван до старта: {}", self.name, interrupt);
+                self.future = None;
+                self.state = ProcessState::Finished;
+                let _ = self.tx.send(ProcessMessage::Finished);
+                return Err(SimError::Interrupted(interrupt));
+            }
+        }
+
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        match future.as_mut().poll(&mut cx) {
+            Poll::Pending => {
+                debug!("Процесс {} приостановлен", self.name);
+                Ok(false)
+            }
+            Poll::Ready(result) => {
+                self.future = None;
+                self.state = ProcessState::Finished;
+                let _ = self.tx.send(ProcessMessage::Finished);
+
+                match result {
+                    Ok(values) => {
+                        info!("Процесс {} завершен", self.name);
+                        self.result = values;
+                        Ok(true)
+                    }
+                    Err(e) => {
+                        error!("Ошибка в процессе {}: {}", self.name, e);
+                        Err(e)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Забрать значения, которые вернул завершившийся процесс
+    pub fn take_result(&mut self) -> Vec<Item> {
+        std::mem::take(&mut self.result)
+    }
+
+    pub fn state(&self) -> &ProcessState {
+        &self.state
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_state(&mut self, state: ProcessState) {
+        self.state = state;
+    }
+
+    /// Передать процессу команду, которая будет доставлена при следующем resume
+    pub fn set_command(&mut self, command: LuaCommand) {
+        *self.shared.command.borrow_mut() = Some(command);
+    }
+
+    /// Есть ли команда, которую процесс ещё не получил
+    pub fn has_pending_command(&self) -> bool {
+        self.shared.command.borrow().is_some()
+    }
+}
+
+/// Движок процессов на Rust. Часы общие для всех контекстов, так что
+/// обновление времени не зависит от числа процессов
+pub struct NativeEngine {
+    processes: HashMap<String, NativeProcess>,
+    process_receivers: HashMap<String, mpsc::UnboundedReceiver<ProcessMessage>>,
+    clock: Rc<Cell<SimTime>>,
+    spawner: Rc<RefCell<Vec<PendingSpawn>>>,
+    streams: Rc<RefCell<RandomStreams>>,
+}
+
+impl NativeEngine {
+    pub fn new() -> Self {
+        Self::with_streams(Rc::new(RefCell::new(RandomStreams::new(0))))
+    }
+
+    /// Движок, процессы которого берут случайные числа из общих потоков `streams`
+    pub fn with_streams(streams: Rc<RefCell<RandomStreams>>) -> Self {
+        Self {
+            processes: HashMap::new(),
+            process_receivers: HashMap::new(),
+            clock: Rc::new(Cell::new(SimTime::ZERO)),
+            spawner: Rc::new(RefCell::new(Vec::new())),
+            streams,
+        }
+    }
+
+    pub fn create_process<F, Fut, R>(&mut self, name: String, process: F) -> Result<(), SimError>
+    where
+        F: FnOnce(ProcessCtx) -> Fut + 'static,
+        Fut: Future<Output = Result<R, SimError>> + 'static,
+        R: ProcessResult,
+    {
+        self.insert_process(name, factory(process))
+    }
+
+    pub(crate) fn insert_process(&mut self, name: String, factory: ProcessFactory) -> Result<(), SimError> {
+        if self.processes.contains_key(&name) {
+            return Err(SimError::ProcessError(format!(
+                "Process with name '{}' already exists",
+                name
+            )));
+        }
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let shared = Rc::new(ProcessShared::default());
+        let ctx = ProcessCtx::new(
+            name.clone(),
+            tx.clone(),
+            self.clock.clone(),
+            shared.clone(),
+            self.spawner.clone(),
+            self.streams.clone(),
+        );
+
+        let process = NativeProcess {
+            name: name.clone(),
+            future: Some(factory(ctx)),
+            shared,
+            state: ProcessState::Active,
+            tx,
+            result: Vec::new(),
+            started: false,
+        };
+
+        self.processes.insert(name.clone(), process);
+        self.process_receivers.insert(name.clone(), rx);
+
+        info!("Создан процесс: {}", name);
+        Ok(())
+    }
+
+    /// Забрать процесс, порождённый через `ProcessCtx::spawn` под именем `name`;
+    /// создаёт его в движке вызывающий, проверив, что имя свободно
+    pub(crate) fn take_spawn(&mut self, name: &str) -> Option<ProcessFactory> {
+        let mut spawner = self.spawner.borrow_mut();
+        let index = spawner.iter().position(|spawn: &PendingSpawn| spawn.name == name)?;
+        Some(spawner.remove(index).factory)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.processes.contains_key(name)
+    }
+
+    /// Удалить завершившийся процесс вместе с его очередью сообщений
+    pub fn remove_process(&mut self, name: &str) {
+        if self.processes.remove(name).is_some() {
+            self.process_receivers.remove(name);
+            debug!("Процесс {} удален", name);
+        }
+    }
+
+    /// Забрать сообщения одного процесса в порядке их отправки
+    pub fn take_messages(&mut self, name: &str) -> Vec<ProcessMessage> {
+        let mut messages = Vec::new();
+
+        if let Some(receiver) = self.process_receivers.get_mut(name) {
+            while let Ok(msg) = receiver.try_recv() {
+                debug!("Сообщение от {}: {:?}", name, msg);
+                messages.push(msg);
+            }
+        }
+
+        messages
+    }
+
+    /// Передать команду процессу. Она будет доставлена при следующем poll
+    pub fn send_command(&mut self, process_name: &str, command: LuaCommand) -> Result<(), String> {
+        if let Some(process) = self.processes.get_mut(process_name) {
+            process.set_command(command);
+            Ok(())
+        } else {
+            Err(format!("Process '{}' not found", process_name))
+        }
+    }
+
+    pub fn set_process_state(&mut self, name: &str, state: ProcessState) {
+        if let Some(process) = self.processes.get_mut(name) {
+            process.set_state(state);
+        }
+    }
+
+    pub fn active_processes(&self) -> Vec<String> {
+        self.processes.keys().cloned().collect()
+    }
+
+    pub fn process_state(&self, name: &str) -> Option<&ProcessState> {
+        self.processes.get(name).map(|p| p.state())
+    }
+
+    pub fn has_pending_command(&self, name: &str) -> bool {
+        self.processes.get(name).is_some_and(|p| p.has_pending_command())
+    }
+
+    pub fn update_time(&mut self, time: SimTime) {
+        self.clock.set(time);
+    }
+
+    pub fn get_process_mut(&mut self, name: &str) -> Option<&mut NativeProcess> {
+        self.processes.get_mut(name)
+    }
+}
+
+impl Default for NativeEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
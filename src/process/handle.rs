@@ -0,0 +1,60 @@
+//! Ссылки на процессы и результаты их завершения
+
+use std::fmt;
+
+use crate::resources::Item;
+
+/// Ссылка на процесс, по которой его можно дождаться (`join`).
+/// В Lua роль ссылки играет имя процесса, которое возвращает `spawn`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ProcessHandle(String);
+
+impl ProcessHandle {
+    /// Ссылка на процесс с именем `name`, например созданный из Lua
+    pub fn new(name: &str) -> Self {
+        Self(name.to_string())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ProcessHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Чем закончился процесс
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProcessOutcome {
+    /// Процесс завершился; значения, которые вернула его функция
+    Finished(Vec<Item>),
+    /// Процесс завершился ошибкой
+    Failed(String),
+}
+
+/// Что может вернуть процесс на Rust: значения получит `join`, как
+/// значения, которые вернула Lua-функция
+pub trait ProcessResult {
+    fn into_values(self) -> Vec<Item>;
+}
+
+impl ProcessResult for () {
+    fn into_values(self) -> Vec<Item> {
+        Vec::new()
+    }
+}
+
+impl ProcessResult for Item {
+    fn into_values(self) -> Vec<Item> {
+        vec![self]
+    }
+}
+
+impl ProcessResult for Vec<Item> {
+    fn into_values(self) -> Vec<Item> {
+        self
+    }
+}
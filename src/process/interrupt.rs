@@ -0,0 +1,36 @@
+//! Прерывания процессов
+
+use std::fmt;
+
+use crate::core::{Duration, SimTime};
+
+/// Прерывание, доставляемое ожидающему процессу.
+///
+/// В Lua оно выбрасывается как ошибка-таблица из `wait`/`request` и может быть
+/// перехвачено через `pcall`, в Rust — возвращается как `SimError::Interrupted`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interrupt {
+    pub cause: String,
+    /// Момент прерывания
+    pub interrupted_at: SimTime,
+    /// Сколько оставалось ждать, если процесс был в `wait`/`timeout`
+    pub remaining: Option<Duration>,
+    /// Подробности, если процесс вытеснен с ресурса
+    pub preemption: Option<Preemption>,
+}
+
+/// Вытеснение процесса с ресурса более приоритетным запросом
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preemption {
+    pub resource: String,
+    /// Процесс, который забрал ресурс
+    pub by: String,
+    /// С какого момента вытесненный процесс владел ресурсом
+    pub usage_since: SimTime,
+}
+
+impl fmt::Display for Interrupt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} в {}", self.cause, self.interrupted_at)
+    }
+}
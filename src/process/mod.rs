@@ -0,0 +1,11 @@
+//! Процессы на Rust, управляемые модельным временем
+
+mod context;
+mod engine;
+mod handle;
+mod interrupt;
+
+pub use context::ProcessCtx;
+pub use engine::{NativeEngine, NativeProcess};
+pub use handle::{ProcessHandle, ProcessOutcome, ProcessResult};
+pub use interrupt::{Interrupt, Preemption};
@@ -0,0 +1,94 @@
+//! Поломки и ремонт ресурсов (MTBF/MTTR)
+
+use std::fmt;
+
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::core::{Distribution, Duration};
+use crate::SimError;
+
+/// Функция, выбирающая очередной интервал
+pub type SampleFn = Box<dyn FnMut(&mut StdRng) -> Result<Duration, SimError>>;
+
+/// Источник случайных интервалов: время до отказа или длительность ремонта
+pub enum DurationSampler {
+    /// Всегда один и тот же интервал
+    Fixed(Duration),
+    /// Экспоненциальное распределение с заданным средним (MTBF / MTTR)
+    Exponential(Duration),
+    /// Любое распределение из `Distribution`, значения в секундах.
+    /// Отрицательные значения (например, у нормального) усекаются до нуля
+    Distribution(Distribution),
+    /// Произвольная функция, например обёртка над Lua
+    Custom(SampleFn),
+}
+
+impl DurationSampler {
+    pub fn sample(&mut self, rng: &mut StdRng) -> Result<Duration, SimError> {
+        match self {
+            DurationSampler::Fixed(duration) => Ok(*duration),
+            DurationSampler::Exponential(mean) => {
+                let u: f64 = rng.gen();
+                Duration::from_seconds(-mean.as_seconds() * (1.0 - u).ln())
+            }
+            DurationSampler::Distribution(distribution) => {
+                Duration::from_seconds(distribution.sample(rng).max(0.0))
+            }
+            DurationSampler::Custom(sample) => sample(rng),
+        }
+    }
+}
+
+impl fmt::Debug for DurationSampler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DurationSampler::Fixed(duration) => write!(f, "Fixed({})", duration),
+            DurationSampler::Exponential(mean) => write!(f, "Exponential({})", mean),
+            DurationSampler::Distribution(distribution) => write!(f, "{:?}", distribution),
+            DurationSampler::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+/// Что происходит с процессами, владеющими ресурсом в момент поломки
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FailurePolicy {
+    /// Текущее обслуживание завершается, новые выдачи ждут ремонта
+    Finish,
+    /// Обслуживание приостанавливается: текущий `wait` владельца
+    /// продлевается на время ремонта
+    #[default]
+    Delay,
+    /// Владельцы теряют ресурс и получают прерывание с причиной `"breakdown"`;
+    /// запрос с `requeue` возвращается в очередь
+    Interrupt,
+}
+
+/// Модель отказов ресурса: время работы до отказа отсчитывается от конца ремонта
+#[derive(Debug)]
+pub struct Breakdown {
+    pub time_to_failure: DurationSampler,
+    pub repair_time: DurationSampler,
+    pub policy: FailurePolicy,
+}
+
+impl Breakdown {
+    pub fn new(time_to_failure: DurationSampler, repair_time: DurationSampler) -> Self {
+        Self {
+            time_to_failure,
+            repair_time,
+            policy: FailurePolicy::default(),
+        }
+    }
+
+    /// Экспоненциальные отказы и ремонты со средними MTBF и MTTR
+    pub fn exponential(mtbf: Duration, mttr: Duration) -> Self {
+        Self::new(DurationSampler::Exponential(mtbf), DurationSampler::Exponential(mttr))
+    }
+
+    pub fn with_policy(mut self, policy: FailurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
@@ -0,0 +1,175 @@
+//! Контейнер: непрерывное количество (топливо, запас на складе, заряд)
+
+use std::collections::VecDeque;
+
+use super::stats::TimeWeighted;
+use crate::core::{Duration, SimTime};
+
+/// Направление операции с контейнером
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerOpKind {
+    Put,
+    Get,
+}
+
+/// Завершённая операция `put`/`get`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerOp {
+    pub process: String,
+    pub container: String,
+    pub kind: ContainerOpKind,
+    pub amount: f64,
+    pub requested_at: SimTime,
+    pub completed_at: SimTime,
+    /// Уровень контейнера сразу после операции
+    pub level: f64,
+}
+
+impl ContainerOp {
+    pub fn wait_time(&self) -> Duration {
+        self.completed_at - self.requested_at
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PendingOp {
+    process: String,
+    amount: f64,
+    requested_at: SimTime,
+}
+
+/// Контейнер с ёмкостью и текущим уровнем.
+///
+/// `put` ждёт, пока хватит свободного места, `get` — пока хватит содержимого.
+/// Обе очереди обслуживаются строго по порядку: большой запрос в голове
+/// очереди не обгоняется меньшими
+#[derive(Debug, Clone)]
+pub struct Container {
+    name: String,
+    capacity: f64,
+    level: f64,
+    put_queue: VecDeque<PendingOp>,
+    get_queue: VecDeque<PendingOp>,
+    level_stats: TimeWeighted,
+    total_put: f64,
+    total_get: f64,
+}
+
+impl Container {
+    pub(crate) fn new(name: &str, capacity: f64, level: f64, now: SimTime) -> Self {
+        Self {
+            name: name.to_string(),
+            capacity,
+            level,
+            put_queue: VecDeque::new(),
+            get_queue: VecDeque::new(),
+            level_stats: TimeWeighted::new(now, level),
+            total_put: 0.0,
+            total_get: 0.0,
+        }
+    }
+
+    pub fn capacity(&self) -> f64 {
+        self.capacity
+    }
+
+    pub fn level(&self) -> f64 {
+        self.level
+    }
+
+    /// Поставить `put` в очередь и выполнить всё, что стало возможным
+    pub(crate) fn put(&mut self, process: &str, amount: f64, now: SimTime) -> Vec<ContainerOp> {
+        self.put_queue.push_back(PendingOp {
+            process: process.to_string(),
+            amount,
+            requested_at: now,
+        });
+        self.trigger(now)
+    }
+
+    /// Поставить `get` в очередь и выполнить всё, что стало возможным
+    pub(crate) fn get(&mut self, process: &str, amount: f64, now: SimTime) -> Vec<ContainerOp> {
+        self.get_queue.push_back(PendingOp {
+            process: process.to_string(),
+            amount,
+            requested_at: now,
+        });
+        self.trigger(now)
+    }
+
+    /// Снять ожидающие операции процесса. Снятая операция могла стоять
+    /// первой и держать очередь, поэтому возвращаются ставшие возможными
+    pub(crate) fn withdraw(&mut self, process: &str, now: SimTime) -> Vec<ContainerOp> {
+        self.put_queue.retain(|op| op.process != process);
+        self.get_queue.retain(|op| op.process != process);
+        self.trigger(now)
+    }
+
+    pub(crate) fn has_waiting(&self) -> bool {
+        !self.put_queue.is_empty() || !self.get_queue.is_empty()
+    }
+
+    /// Выполнять головы очередей, пока хоть одна продвигается:
+    /// каждый put может разблокировать get и наоборот
+    fn trigger(&mut self, now: SimTime) -> Vec<ContainerOp> {
+        let mut done = Vec::new();
+
+        loop {
+            let mut progressed = false;
+
+            if self.put_queue.front().is_some_and(|op| self.level + op.amount <= self.capacity) {
+                if let Some(op) = self.put_queue.pop_front() {
+                    self.level += op.amount;
+                    self.total_put += op.amount;
+                    done.push(self.complete(op, ContainerOpKind::Put, now));
+                    progressed = true;
+                }
+            }
+
+            if self.get_queue.front().is_some_and(|op| op.amount <= self.level) {
+                if let Some(op) = self.get_queue.pop_front() {
+                    self.level -= op.amount;
+                    self.total_get += op.amount;
+                    done.push(self.complete(op, ContainerOpKind::Get, now));
+                    progressed = true;
+                }
+            }
+
+            if !progressed {
+                break;
+            }
+        }
+
+        if !done.is_empty() {
+            self.level_stats.update(now, self.level);
+        }
+        done
+    }
+
+    fn complete(&self, op: PendingOp, kind: ContainerOpKind, now: SimTime) -> ContainerOp {
+        ContainerOp {
+            process: op.process,
+            container: self.name.clone(),
+            kind,
+            amount: op.amount,
+            requested_at: op.requested_at,
+            completed_at: now,
+            level: self.level,
+        }
+    }
+
+    pub(crate) fn stats(&self, now: SimTime) -> serde_json::Value {
+        serde_json::json!({
+            "name": self.name,
+            "capacity": self.capacity,
+            "level": self.level,
+            "mean_level": self.level_stats.mean(now),
+            "min_level": self.level_stats.min(),
+            "max_level": self.level_stats.max(),
+            "put_queue_length": self.put_queue.len(),
+            "get_queue_length": self.get_queue.len(),
+            "total_put": self.total_put,
+            "total_get": self.total_get,
+        })
+    }
+}
@@ -0,0 +1,103 @@
+//! Дисциплины очереди ресурса: в каком порядке обслуживаются ожидающие
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::{Item, QueuedRequest};
+use crate::core::SimTime;
+
+/// Запрос в очереди, как его видят ключ и компаратор дисциплины
+#[derive(Debug, Clone, Copy)]
+pub struct Waiting<'a> {
+    pub resource: &'a str,
+    pub process: &'a str,
+    pub priority: i32,
+    pub units: usize,
+    /// Атрибуты, переданные с запросом (`RequestOptions::attributes`)
+    pub attributes: &'a Item,
+    pub requested_at: SimTime,
+}
+
+pub type KeyFn = Box<dyn Fn(&Waiting) -> f64>;
+pub type CompareFn = Box<dyn Fn(&Waiting, &Waiting) -> Ordering>;
+
+/// Порядок обслуживания запросов с одинаковым приоритетом.
+///
+/// Приоритетные ресурсы по-прежнему сначала обслуживают более важные запросы,
+/// дисциплина упорядочивает запросы внутри одного приоритета. Вытесненный
+/// запрос с `requeue` всегда встаёт первым среди своего приоритета
+#[derive(Default)]
+pub enum QueueDiscipline {
+    /// В порядке поступления
+    #[default]
+    Fifo,
+    /// Последний пришедший обслуживается первым
+    Lifo,
+    /// Случайный порядок из собственного генератора
+    Random(Box<StdRng>),
+    /// Меньшее значение числового атрибута запроса — раньше;
+    /// запросы без атрибута обслуживаются после остальных
+    ShortestJob(String),
+    /// Меньший ключ — раньше; ключ вычисляется один раз при постановке в очередь
+    Key(KeyFn),
+    /// `Ordering::Less` — первый запрос обслуживается раньше второго
+    Compare(CompareFn),
+}
+
+impl QueueDiscipline {
+    /// Случайный порядок, воспроизводимый при одном и том же `seed`
+    pub fn random(seed: u64) -> Self {
+        QueueDiscipline::Random(Box::new(StdRng::seed_from_u64(seed)))
+    }
+
+    /// Кратчайшая работа первой по атрибуту `attribute`
+    pub fn shortest_job(attribute: &str) -> Self {
+        QueueDiscipline::ShortestJob(attribute.to_string())
+    }
+
+    /// Ключ сортировки для `ShortestJob` и `Key`
+    pub(super) fn key(&self, waiting: &Waiting) -> f64 {
+        match self {
+            QueueDiscipline::ShortestJob(attribute) => waiting.attributes
+                .get(attribute)
+                .and_then(Item::as_f64)
+                .unwrap_or(f64::INFINITY),
+            QueueDiscipline::Key(key) => key(waiting),
+            _ => 0.0,
+        }
+    }
+
+    /// Куда встать новому запросу среди `group` — запросов его приоритета,
+    /// уже упорядоченных этой дисциплиной
+    pub(super) fn position(&mut self, resource: &str, group: &[&QueuedRequest], new: &QueuedRequest) -> usize {
+        match self {
+            QueueDiscipline::Fifo => group.len(),
+            QueueDiscipline::Lifo => 0,
+            // Вставка в случайное место равносильна случайному выбору при обслуживании
+            QueueDiscipline::Random(rng) => rng.gen_range(0..=group.len()),
+            QueueDiscipline::ShortestJob(_) | QueueDiscipline::Key(_) => {
+                group.partition_point(|q| q.key <= new.key)
+            }
+            QueueDiscipline::Compare(compare) => {
+                let new = new.waiting(resource);
+                group.partition_point(|q| compare(&q.waiting(resource), &new) != Ordering::Greater)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for QueueDiscipline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueueDiscipline::Fifo => f.write_str("Fifo"),
+            QueueDiscipline::Lifo => f.write_str("Lifo"),
+            QueueDiscipline::Random(_) => f.write_str("Random"),
+            QueueDiscipline::ShortestJob(attribute) => f.debug_tuple("ShortestJob").field(attribute).finish(),
+            QueueDiscipline::Key(_) => f.write_str("Key(..)"),
+            QueueDiscipline::Compare(_) => f.write_str("Compare(..)"),
+        }
+    }
+}
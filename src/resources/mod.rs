@@ -3,32 +3,418 @@
 use std::collections::{HashMap, VecDeque};
 use serde::{Serialize, Deserialize};
 
+use crate::core::{Duration, SimTime};
+use crate::process::Preemption;
+
+mod breakdown;
+mod container;
+mod discipline;
+mod pool;
+mod schedule;
+mod stats;
+mod store;
+
+pub use container::{Container, ContainerOp, ContainerOpKind};
+pub use breakdown::{Breakdown, DurationSampler, FailurePolicy};
+pub use discipline::{CompareFn, KeyFn, QueueDiscipline, Waiting};
+pub use pool::Unit;
+pub use schedule::{CapacityDropPolicy, CapacitySchedule};
+pub use store::{Item, ItemFilter, Store, StoreKind, StoreOp};
+pub use stats::{Tally, TimeWeighted};
+
+/// Порядок обслуживания очереди ресурса
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResourceKind {
+    /// Обычный ресурс: очередь в порядке прихода
+    Standard,
+    /// Очередь по приоритету запроса (меньше = важнее), при равенстве — по приходу
+    Priority,
+    /// Как Priority, но важный запрос отбирает единицу у наименее важного владельца
+    Preemptive,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Resource {
     name: String,
+    kind: ResourceKind,
     capacity: usize,
-    available: usize,
+    in_use: usize, // может превышать ёмкость после её снижения
     queue_length: usize,
     total_requests: u64,
-    total_wait_time: f64, // суммарное время ожидания
+    total_preemptions: u64,
+    total_reneged: u64,
+    total_balked: u64,
+    total_wait_time: Duration, // суммарное время ожидания
+    busy_stats: TimeWeighted,  // занятые единицы по времени
+    capacity_stats: TimeWeighted, // ёмкость по расписанию
+    queue_stats: TimeWeighted, // длина очереди по времени
+    wait_times: Tally,         // ожидание каждого выданного запроса, с
+    hold_times: Tally,         // владение до release или вытеснения, с
+    down_since: Option<SimTime>, // ресурс сломан с этого момента
+    failures: u64,
+    up_stats: TimeWeighted,    // 1 — в строю, 0 — в ремонте
+    downtime: Tally,           // длительность каждого ремонта, с
+    total_downtime: Duration,
+    pool: Option<pool::Pool>,  // именованные единицы, если ресурс — пул
+    scheduled_peak: usize,     // наибольшая ёмкость по расписанию смен
 }
 
 impl Resource {
-    fn new(name: &str, capacity: usize) -> Self {
+    fn new(name: &str, kind: ResourceKind, capacity: usize, now: SimTime) -> Self {
         Self {
             name: name.to_string(),
+            kind,
             capacity,
-            available: capacity,
+            in_use: 0,
             queue_length: 0,
             total_requests: 0,
-            total_wait_time: 0.0,
+            total_preemptions: 0,
+            total_reneged: 0,
+            total_balked: 0,
+            total_wait_time: Duration::ZERO,
+            busy_stats: TimeWeighted::new(now, 0.0),
+            capacity_stats: TimeWeighted::new(now, capacity as f64),
+            queue_stats: TimeWeighted::new(now, 0.0),
+            wait_times: Tally::new(),
+            hold_times: Tally::new(),
+            down_since: None,
+            failures: 0,
+            up_stats: TimeWeighted::new(now, 1.0),
+            downtime: Tally::new(),
+            total_downtime: Duration::ZERO,
+            pool: None,
+            scheduled_peak: 0,
+        }
+    }
+
+    fn available(&self) -> usize {
+        if self.down_since.is_some() {
+            return 0;
+        }
+        self.capacity.saturating_sub(self.in_use)
+    }
+
+    /// Свободные единицы: индексы единиц пула или, у обычного ресурса,
+    /// условные номера свободных мест
+    fn free_units(&self) -> Vec<usize> {
+        match &self.pool {
+            _ if self.down_since.is_some() => Vec::new(),
+            Some(pool) => pool.free(),
+            None => (0..self.available()).collect(),
+        }
+    }
+
+    /// Единицы из `candidates`, подходящие запросу с навыками `skills`
+    fn matching(&self, candidates: &[usize], skills: &[String]) -> Vec<usize> {
+        match &self.pool {
+            Some(pool) => pool.matching(candidates, skills),
+            None => candidates.to_vec(),
+        }
+    }
+
+    /// Занять выбранные единицы; возвращает имена единиц пула
+    fn occupy(&mut self, picked: &[usize], now: SimTime) -> Vec<String> {
+        self.in_use += picked.len();
+        match &mut self.pool {
+            Some(pool) => pool.occupy(picked, now),
+            None => Vec::new(),
+        }
+    }
+
+    /// Освободить `units` единиц, среди которых единицы пула `assigned`
+    fn vacate(&mut self, assigned: &[usize], units: usize, now: SimTime) {
+        self.in_use -= units;
+        if let Some(pool) = &mut self.pool {
+            pool.vacate(assigned, now);
+        }
+    }
+
+    /// Учесть выдачу единицы запросу, ждавшему с `requested_at`
+    fn record_grant(&mut self, requested_at: SimTime, now: SimTime) {
+        self.total_requests += 1;
+        self.record_wait(requested_at, now);
+    }
+
+    /// Учесть ожидание без нового запроса (повторная выдача вытесненному)
+    fn record_wait(&mut self, requested_at: SimTime, now: SimTime) {
+        let wait = now - requested_at;
+        self.total_wait_time += wait;
+        self.wait_times.record(wait.as_seconds());
+    }
+
+    /// Зафиксировать текущую занятость и длину очереди
+    fn observe(&mut self, now: SimTime, queue_length: usize) {
+        self.queue_length = queue_length;
+        self.busy_stats.update(now, self.in_use as f64);
+        self.capacity_stats.update(now, self.capacity as f64);
+        self.queue_stats.update(now, queue_length as f64);
+    }
+
+    fn stats(&self, now: SimTime) -> serde_json::Value {
+        // Занятость считается относительно ёмкости по расписанию
+        let ratio = |busy: f64, capacity: f64| if capacity > 0.0 { busy / capacity } else { 0.0 };
+
+        serde_json::json!({
+            "name": self.name,
+            "kind": self.kind,
+            "capacity": self.capacity,
+            "available": self.available(),
+            "in_use": self.in_use,
+            "utilization": ratio(self.in_use as f64, self.capacity as f64),
+            "mean_capacity": self.capacity_stats.mean(now),
+            "mean_utilization": ratio(self.busy_stats.mean(now), self.capacity_stats.mean(now)),
+            "queue_length": self.queue_length,
+            "mean_queue_length": self.queue_stats.mean(now),
+            "max_queue_length": self.queue_stats.max(),
+            "total_requests": self.total_requests,
+            "total_preemptions": self.total_preemptions,
+            "total_reneged": self.total_reneged,
+            "total_balked": self.total_balked,
+            "total_wait_time": self.total_wait_time.as_seconds(),
+            "wait_time": self.wait_times.summary(),
+            "hold_time": self.hold_times.summary(),
+            "broken": self.down_since.is_some(),
+            "failures": self.failures,
+            "availability": self.up_stats.mean(now),
+            // Незавершённый ремонт учитывается до текущего момента
+            "total_downtime": (self.total_downtime + self.down_since.map_or(Duration::ZERO, |since| now - since)).as_seconds(),
+            "downtime": self.downtime.summary(),
+            "units": self.pool.as_ref().map(|pool| pool.stats(now)),
+        })
+    }
+}
+
+/// Параметры запроса ресурса
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestOptions {
+    /// Приоритет запроса (меньше = важнее); учитывается приоритетными ресурсами
+    pub priority: i32,
+    /// Может ли запрос вытеснить владельца вытесняющего ресурса
+    pub preempt: bool,
+    /// При вытеснении вернуть процесс в очередь вместо доставки прерывания.
+    /// Действует, только пока процесс в `wait`: вытесненный во время другой
+    /// операции всё равно получает прерывание
+    pub requeue: bool,
+    /// Сколько процесс готов ждать в очереди, прежде чем уйти (reneging)
+    pub timeout: Option<Duration>,
+    /// Сколько единиц ресурса нужно одновременно
+    pub units: usize,
+    /// Произвольные атрибуты запроса (например, длительность работы)
+    /// для дисциплин очереди
+    pub attributes: Item,
+    /// Навыки, которыми должна обладать каждая выданная единица пула
+    pub skills: Vec<String>,
+}
+
+impl RequestOptions {
+    pub fn with_priority(priority: i32) -> Self {
+        Self { priority, ..Self::default() }
+    }
+
+    pub fn with_units(units: usize) -> Self {
+        Self { units, ..Self::default() }
+    }
+
+    pub fn with_skills(skills: &[&str]) -> Self {
+        let skills = skills.iter().map(|skill| skill.to_string()).collect();
+        Self { skills, ..Self::default() }
+    }
+}
+
+impl Default for RequestOptions {
+    fn default() -> Self {
+        Self {
+            priority: 0,
+            preempt: true,
+            requeue: false,
+            timeout: None,
+            units: 1,
+            attributes: Item::Null,
+            skills: Vec::new(),
+        }
+    }
+}
+
+/// Выдача ресурса процессу
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grant {
+    pub process: String,
+    pub resource: String,
+    /// Сколько единиц выдано
+    pub units: usize,
+    /// Имена выданных единиц пула (пусто у обычного ресурса)
+    pub assigned: Vec<String>,
+    pub requested_at: SimTime,
+    pub granted_at: SimTime,
+}
+
+impl Grant {
+    /// Сколько процесс простоял в очереди
+    pub fn wait_time(&self) -> Duration {
+        self.granted_at - self.requested_at
+    }
+}
+
+/// Почему запрос остался без ресурса
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DenialReason {
+    /// Процесс не стал вставать в очередь (balking)
+    Balked,
+    /// Процесс ушёл из очереди по таймауту (reneging)
+    TimedOut,
+}
+
+/// Запрос, ушедший без ресурса
+#[derive(Debug, Clone, PartialEq)]
+pub struct Denial {
+    pub process: String,
+    pub resource: String,
+    pub reason: DenialReason,
+    pub requested_at: SimTime,
+    pub denied_at: SimTime,
+}
+
+impl Denial {
+    pub fn wait_time(&self) -> Duration {
+        self.denied_at - self.requested_at
+    }
+}
+
+/// Состояние ресурса в момент, когда запросу пришлось бы встать в очередь
+#[derive(Debug, Clone, Copy)]
+pub struct Arrival<'a> {
+    pub resource: &'a str,
+    pub process: &'a str,
+    pub priority: i32,
+    pub queue_length: usize,
+    pub available: usize,
+    pub capacity: usize,
+    pub now: SimTime,
+}
+
+/// Правило, по которому пришедший процесс отказывается вставать в очередь
+pub enum BalkRule {
+    /// Очередь длиннее заданной — уходим
+    MaxQueue(usize),
+    /// Произвольное условие: `true` — уходим
+    Predicate(Box<dyn Fn(&Arrival) -> bool>),
+}
+
+impl BalkRule {
+    fn balks(&self, arrival: &Arrival) -> bool {
+        match self {
+            BalkRule::MaxQueue(max) => arrival.queue_length >= *max,
+            BalkRule::Predicate(predicate) => predicate(arrival),
+        }
+    }
+}
+
+/// Процесс, у которого отобрали ресурс
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preempted {
+    pub process: String,
+    /// Причина прерывания: `"preempted"` или `"capacity"` при снижении ёмкости
+    pub cause: String,
+    pub preemption: Preemption,
+    /// Процесс уже снова стоит в очереди (запрос был с `requeue`)
+    pub requeued: bool,
+}
+
+/// Как очередь обслуживает запросы на несколько единиц
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BatchPolicy {
+    /// Строго по очереди: пока первому не хватает единиц, следующие ждут,
+    /// поэтому большой запрос не голодает
+    #[default]
+    Strict,
+    /// Свободные единицы получает первый запрос, которому их хватает;
+    /// загрузка выше, но большой запрос могут обгонять бесконечно
+    Backfill,
+}
+
+/// Как совместный запрос выбирает ресурсы
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JointMode {
+    /// Все ресурсы сразу или ни одного
+    All,
+    /// Первый освободившийся из перечисленных
+    Any,
+}
+
+/// Выдача по совместному запросу: все ресурсы для `All`, один для `Any`
+#[derive(Debug, Clone, PartialEq)]
+pub struct JointGrant {
+    pub process: String,
+    pub mode: JointMode,
+    pub grants: Vec<Grant>,
+}
+
+/// Совместный запрос нескольких ресурсов. Пока он ждёт, единицы
+/// не резервируются, поэтому частичный захват и взаимная блокировка невозможны.
+/// Освободившиеся единицы сначала достаются очереди самого ресурса
+#[derive(Debug, Clone)]
+struct JointRequest {
+    process: String,
+    resources: Vec<String>,
+    mode: JointMode,
+    requested_at: SimTime,
+}
+
+/// Запрос, ожидающий в очереди ресурса
+#[derive(Debug, Clone)]
+struct QueuedRequest {
+    process: String,
+    options: RequestOptions,
+    requested_at: SimTime,
+    /// Ключ дисциплины очереди, вычисленный при постановке
+    key: f64,
+    /// Вытесненный владелец, вернувшийся в очередь: его повторная выдача —
+    /// не новый запрос, а ожидание отсчитывается от момента вытеснения
+    requeued: bool,
+}
+
+impl QueuedRequest {
+    fn waiting<'a>(&'a self, resource: &'a str) -> Waiting<'a> {
+        Waiting {
+            resource,
+            process: &self.process,
+            priority: self.options.priority,
+            units: self.options.units,
+            attributes: &self.options.attributes,
+            requested_at: self.requested_at,
         }
     }
 }
 
+/// Текущий владелец единицы ресурса
+#[derive(Debug, Clone)]
+struct Holder {
+    process: String,
+    options: RequestOptions,
+    units: usize,
+    assigned: Vec<usize>, // индексы единиц пула
+    since: SimTime,
+}
+
 pub struct ResourceManager {
     resources: HashMap<String, Resource>,
-    request_queues: HashMap<String, VecDeque<String>>, // resource -> очередь процессов
+    request_queues: HashMap<String, VecDeque<QueuedRequest>>, // resource -> очередь процессов
+    holders: HashMap<String, Vec<Holder>>, // resource -> владельцы в порядке получения
+    preempted: Vec<Preempted>,
+    containers: HashMap<String, Container>,
+    stores: HashMap<String, Store>,
+    balk_rules: HashMap<String, BalkRule>,
+    batch_policies: HashMap<String, BatchPolicy>,
+    disciplines: HashMap<String, QueueDiscipline>,
+    joint_queue: VecDeque<JointRequest>,
+    joint_granted: Vec<JointGrant>,
+    /// Выдачи, ставшие возможными после ухода заблокировавшего очередь запроса
+    unblocked: Vec<Grant>,
+    /// Операции с контейнерами, которые задерживал снятый с очереди процесс
+    unblocked_ops: Vec<ContainerOp>,
+    /// Сколько запросов и операций каждого процесса стоит в очередях:
+    /// `withdraw` не обходит очереди ради процесса, которого в них нет
+    queued: HashMap<String, usize>,
 }
 
 impl ResourceManager {
@@ -36,72 +422,953 @@ impl ResourceManager {
         Self {
             resources: HashMap::new(),
             request_queues: HashMap::new(),
+            holders: HashMap::new(),
+            preempted: Vec::new(),
+            containers: HashMap::new(),
+            stores: HashMap::new(),
+            balk_rules: HashMap::new(),
+            batch_policies: HashMap::new(),
+            disciplines: HashMap::new(),
+            joint_queue: VecDeque::new(),
+            joint_granted: Vec::new(),
+            unblocked: Vec::new(),
+            unblocked_ops: Vec::new(),
+            queued: HashMap::new(),
         }
     }
 
-    pub fn create(&mut self, name: &str, capacity: usize) {
-        self.resources.insert(name.to_string(), Resource::new(name, capacity));
+    /// Создать ресурс; статистика по времени ведётся с момента `now`
+    pub fn create(&mut self, name: &str, capacity: usize, now: SimTime) {
+        self.create_with_kind(name, ResourceKind::Standard, capacity, now);
+    }
+
+    /// Создать ресурс с очередью по приоритету запросов
+    pub fn create_priority(&mut self, name: &str, capacity: usize, now: SimTime) {
+        self.create_with_kind(name, ResourceKind::Priority, capacity, now);
+    }
+
+    /// Создать приоритетный ресурс с вытеснением владельцев
+    pub fn create_preemptive(&mut self, name: &str, capacity: usize, now: SimTime) {
+        self.create_with_kind(name, ResourceKind::Preemptive, capacity, now);
+    }
+
+    fn create_with_kind(&mut self, name: &str, kind: ResourceKind, capacity: usize, now: SimTime) {
+        self.resources.insert(name.to_string(), Resource::new(name, kind, capacity, now));
         self.request_queues.insert(name.to_string(), VecDeque::new());
+        self.holders.insert(name.to_string(), Vec::new());
     }
 
-    /// Попытка получить ресурс. Возвращает true, если ресурс получен немедленно
-    pub fn request(&mut self, resource_name: &str) -> bool {
-        if let Some(resource) = self.resources.get_mut(resource_name) {
-            if resource.available > 0 {
-                resource.available -= 1;
-                resource.total_requests += 1;
-                true
-            } else {
-                // Встаем в очередь
-                if let Some(queue) = self.request_queues.get_mut(resource_name) {
-                    resource.queue_length = queue.len() + 1;
-                }
-                false
+    /// Создать пул именованных единиц с атрибутами; ёмкость равна их числу
+    pub fn create_pool(&mut self, name: &str, units: Vec<Unit>, now: SimTime) -> Result<(), String> {
+        if units.is_empty() {
+            return Err(format!("Pool '{}' must have at least one unit", name));
+        }
+        for (i, unit) in units.iter().enumerate() {
+            if units[..i].iter().any(|u| u.name == unit.name) {
+                return Err(format!("Pool '{}': duplicate unit '{}'", name, unit.name));
             }
-        } else {
-            false
         }
+        self.create_with_kind(name, ResourceKind::Standard, units.len(), now);
+        if let Some(resource) = self.resources.get_mut(name) {
+            resource.pool = Some(pool::Pool::new(units, now));
+        }
+        Ok(())
+    }
+
+    /// Является ли ресурс пулом именованных единиц
+    pub fn is_pool(&self, resource_name: &str) -> bool {
+        self.resources.get(resource_name).is_some_and(|r| r.pool.is_some())
     }
 
-    /// Освободить ресурс
-    pub fn release(&mut self, resource_name: &str) {
+    /// Проверить запрос до постановки в очередь: такой, который не сможет
+    /// выполниться никогда, лучше отклонить сразу
+    pub fn validate_request(&self, resource_name: &str, options: &RequestOptions) -> Result<(), String> {
+        let resource = self
+            .resources
+            .get(resource_name)
+            .ok_or_else(|| format!("Resource '{}' not found", resource_name))?;
+        if options.units == 0 {
+            return Err(format!("Resource '{}': units must be positive", resource_name));
+        }
+        // Запрос больше любой возможной ёмкости навсегда остался бы в очереди
+        // и при строгой политике задержал бы всех за собой
+        let peak = resource.capacity.max(resource.scheduled_peak);
+        match &resource.pool {
+            None if options.units > peak => Err(format!(
+                "Resource '{}' never has {} units: capacity is at most {}",
+                resource_name, options.units, peak
+            )),
+            Some(pool) if pool.capable(&options.skills) < options.units => Err(format!(
+                "Pool '{}' has fewer than {} units with skills {:?}",
+                resource_name, options.units, options.skills
+            )),
+            None if !options.skills.is_empty() => Err(format!(
+                "Resource '{}' has no named units to match skills {:?}",
+                resource_name, options.skills
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Учесть расписание смен ресурса: запросы до его наибольшей ёмкости
+    /// допустимы, даже если сейчас ёмкость меньше
+    pub fn note_capacity_schedule(&mut self, resource_name: &str, schedule: &CapacitySchedule) {
         if let Some(resource) = self.resources.get_mut(resource_name) {
-            if resource.available < resource.capacity {
-                resource.available += 1;
+            let peak = schedule.shifts().iter().map(|(_, capacity)| *capacity).max().unwrap_or(0);
+            resource.scheduled_peak = resource.scheduled_peak.max(peak);
+        }
+    }
+
+    pub fn exists(&self, resource_name: &str) -> bool {
+        self.resources.contains_key(resource_name)
+    }
+
+    /// Задать порядок обслуживания запросов на несколько единиц
+    pub fn set_batch_policy(&mut self, resource_name: &str, policy: BatchPolicy) -> Result<(), String> {
+        if !self.exists(resource_name) {
+            return Err(format!("Resource '{}' not found", resource_name));
+        }
+        self.batch_policies.insert(resource_name.to_string(), policy);
+        Ok(())
+    }
+
+    /// Задать дисциплину очереди ресурса. Применяется к запросам, которые
+    /// встанут в очередь после этого; уже ожидающие сохраняют свой порядок
+    pub fn set_queue_discipline(&mut self, resource_name: &str, discipline: QueueDiscipline) -> Result<(), String> {
+        if !self.exists(resource_name) {
+            return Err(format!("Resource '{}' not found", resource_name));
+        }
+        self.disciplines.insert(resource_name.to_string(), discipline);
+        Ok(())
+    }
+
+    /// Задать правило отказа от очереди для ресурса
+    pub fn set_balk_rule(&mut self, resource_name: &str, rule: BalkRule) -> Result<(), String> {
+        if !self.exists(resource_name) {
+            return Err(format!("Resource '{}' not found", resource_name));
+        }
+        self.balk_rules.insert(resource_name.to_string(), rule);
+        Ok(())
+    }
+
+    /// Создать контейнер с ёмкостью `capacity` и начальным уровнем `level`
+    pub fn create_container(&mut self, name: &str, capacity: f64, level: f64, now: SimTime) -> Result<(), String> {
+        if !capacity.is_finite() || capacity <= 0.0 {
+            return Err(format!("Container '{}': capacity must be positive, got {}", name, capacity));
+        }
+        if !level.is_finite() || level < 0.0 || level > capacity {
+            return Err(format!(
+                "Container '{}': initial level {} is outside [0, {}]",
+                name, level, capacity
+            ));
+        }
+        self.containers.insert(name.to_string(), Container::new(name, capacity, level, now));
+        Ok(())
+    }
+
+    pub fn container(&self, name: &str) -> Option<&Container> {
+        self.containers.get(name)
+    }
+
+    /// Положить `amount` в контейнер. Возвращает завершённые операции:
+    /// собственную (если места хватило сразу) и разблокированные ею `get`
+    pub fn put(
+        &mut self,
+        container_name: &str,
+        process_name: &str,
+        amount: f64,
+        now: SimTime,
+    ) -> Result<Vec<ContainerOp>, String> {
+        let container = self.checked_container(container_name, amount)?;
+        let done = container.put(process_name, amount, now);
+        self.track_ops(process_name, done.iter().map(|op| op.process.as_str()));
+        Ok(done)
+    }
+
+    /// Забрать `amount` из контейнера. Возвращает завершённые операции:
+    /// собственную (если содержимого хватило сразу) и разблокированные ею `put`
+    pub fn get(
+        &mut self,
+        container_name: &str,
+        process_name: &str,
+        amount: f64,
+        now: SimTime,
+    ) -> Result<Vec<ContainerOp>, String> {
+        let container = self.checked_container(container_name, amount)?;
+        let done = container.get(process_name, amount, now);
+        self.track_ops(process_name, done.iter().map(|op| op.process.as_str()));
+        Ok(done)
+    }
+
+    /// Создать хранилище предметов; `capacity = None` — без ограничения
+    pub fn create_store(
+        &mut self,
+        name: &str,
+        kind: StoreKind,
+        capacity: Option<usize>,
+        now: SimTime,
+    ) -> Result<(), String> {
+        if capacity == Some(0) {
+            return Err(format!("Store '{}': capacity must be positive", name));
+        }
+        self.stores.insert(name.to_string(), Store::new(name, kind, capacity, now));
+        Ok(())
+    }
+
+    pub fn store(&self, name: &str) -> Option<&Store> {
+        self.stores.get(name)
+    }
+
+    /// Положить предмет в хранилище. `priority` учитывается только PriorityStore.
+    /// `matches` проверяет фильтры ожидающих `get` на новом предмете
+    pub fn put_item(
+        &mut self,
+        store_name: &str,
+        process_name: &str,
+        item: Item,
+        priority: i64,
+        now: SimTime,
+        matches: &mut dyn FnMut(&str, &ItemFilter, &Item) -> bool,
+    ) -> Result<Vec<StoreOp>, String> {
+        let store = self
+            .stores
+            .get_mut(store_name)
+            .ok_or_else(|| format!("Store '{}' not found", store_name))?;
+        let done = store.put(process_name, item, priority, now, matches);
+        self.track_ops(process_name, done.iter().map(|op| op.process.as_str()));
+        Ok(done)
+    }
+
+    /// Забрать предмет из хранилища, при необходимости — подходящий под фильтр
+    pub fn get_item(
+        &mut self,
+        store_name: &str,
+        process_name: &str,
+        filter: Option<ItemFilter>,
+        now: SimTime,
+        matches: &mut dyn FnMut(&str, &ItemFilter, &Item) -> bool,
+    ) -> Result<Vec<StoreOp>, String> {
+        let store = self
+            .stores
+            .get_mut(store_name)
+            .ok_or_else(|| format!("Store '{}' not found", store_name))?;
+        if filter.is_some() && store.kind() != StoreKind::Filter {
+            return Err(format!("Store '{}' is not a FilterStore, get cannot take a filter", store_name));
+        }
+        let done = store.get(process_name, filter, now, matches);
+        self.track_ops(process_name, done.iter().map(|op| op.process.as_str()));
+        Ok(done)
+    }
+
+    /// Контейнер, для которого операция на `amount` когда-нибудь выполнима
+    fn checked_container(&mut self, name: &str, amount: f64) -> Result<&mut Container, String> {
+        let container = self
+            .containers
+            .get_mut(name)
+            .ok_or_else(|| format!("Container '{}' not found", name))?;
+        if !amount.is_finite() || amount <= 0.0 || amount > container.capacity() {
+            return Err(format!(
+                "Container '{}': amount must be in (0, {}], got {}",
+                name,
+                container.capacity(),
+                amount
+            ));
+        }
+        Ok(container)
+    }
+
+    /// Запрос ресурса процессом.
+    /// Возвращает выдачу, если ресурс получен немедленно, иначе процесс
+    /// ставится в очередь и получит ресурс при одном из `release`.
+    /// Если процесс отказался от очереди по правилу ресурса, возвращается `Err`.
+    /// Вытесненные при этом владельцы забираются через `take_preempted`
+    pub fn request(
+        &mut self,
+        resource_name: &str,
+        process_name: &str,
+        options: &RequestOptions,
+        now: SimTime,
+    ) -> Result<Option<Grant>, Denial> {
+        let (Some(resource), Some(queue), Some(holders)) = (
+            self.resources.get_mut(resource_name),
+            self.request_queues.get_mut(resource_name),
+            self.holders.get_mut(resource_name),
+        ) else {
+            return Ok(None);
+        };
+
+        let units = options.units.max(1);
+        let backfill = self.batch_policies.get(resource_name) == Some(&BatchPolicy::Backfill);
 
-                // Проверяем очередь
-                if let Some(queue) = self.request_queues.get_mut(resource_name) {
-                    resource.queue_length = queue.len();
+        // Свободные единицы достаются только если на них не претендуют
+        // ждущие раньше нас (при Backfill — если их просто хватает)
+        let free = unreserved(resource, queue, backfill);
+        let mut picked = resource.matching(&free, &options.skills);
+        let mut grantable = picked.len() >= units;
+
+        // Сломанный ресурс выдать нечего: вытеснение только освободило бы
+        // единицы, которые всё равно недоступны до ремонта
+        let can_preempt = resource.kind == ResourceKind::Preemptive && options.preempt && resource.down_since.is_none();
+        if !grantable && can_preempt {
+            // После снижения ёмкости занятых может быть больше, чем мест:
+            // освободить нужно и этот излишек
+            let needed = (resource.in_use + units).saturating_sub(resource.capacity);
+            if let Some(mut victims) = preemption_victims(holders, options.priority, needed) {
+                // Удаляем с конца, чтобы индексы оставшихся не сдвигались
+                victims.sort_unstable_by(|a, b| b.cmp(a));
+                for victim in victims {
+                    let evicted = evict(resource, queue, &mut self.queued, holders.remove(victim), "preempted", process_name, now);
+                    self.preempted.push(evicted);
                 }
+                picked = resource.free_units();
+                grantable = picked.len() >= units;
             }
         }
+
+        if grantable {
+            picked.truncate(units);
+            let assigned = resource.occupy(&picked, now);
+            resource.record_grant(now, now);
+            resource.observe(now, queue.len());
+            holders.push(Holder {
+                process: process_name.to_string(),
+                options: options.clone(),
+                units,
+                assigned: picked,
+                since: now,
+            });
+            return Ok(Some(Grant {
+                process: process_name.to_string(),
+                resource: resource_name.to_string(),
+                units,
+                assigned,
+                requested_at: now,
+                granted_at: now,
+            }));
+        }
+
+        if let Some(rule) = self.balk_rules.get(resource_name) {
+            let arrival = Arrival {
+                resource: resource_name,
+                process: process_name,
+                priority: options.priority,
+                queue_length: queue.len(),
+                available: resource.available(),
+                capacity: resource.capacity,
+                now,
+            };
+            if rule.balks(&arrival) {
+                resource.total_balked += 1;
+                return Err(Denial {
+                    process: process_name.to_string(),
+                    resource: resource_name.to_string(),
+                    reason: DenialReason::Balked,
+                    requested_at: now,
+                    denied_at: now,
+                });
+            }
+        }
+
+        let mut queued = QueuedRequest {
+            process: process_name.to_string(),
+            options: options.clone(),
+            requested_at: now,
+            key: 0.0,
+            requeued: false,
+        };
+        // Сначала решает приоритет (у обычного ресурса он не учитывается),
+        // внутри приоритета — дисциплина очереди
+        let (start, end) = match resource.kind {
+            ResourceKind::Standard => (0, queue.len()),
+            ResourceKind::Priority | ResourceKind::Preemptive => (
+                queue.partition_point(|q| q.options.priority < options.priority),
+                queue.partition_point(|q| q.options.priority <= options.priority),
+            ),
+        };
+        let position = match self.disciplines.get_mut(resource_name) {
+            Some(discipline) => {
+                queued.key = discipline.key(&queued.waiting(resource_name));
+                let group: Vec<&QueuedRequest> = queue.range(start..end).collect();
+                start + discipline.position(resource_name, &group, &queued)
+            }
+            None => end,
+        };
+        queue.insert(position, queued);
+        resource.observe(now, queue.len());
+        enqueued(&mut self.queued, process_name);
+        Ok(None)
     }
 
-    /// Добавить процесс в очередь ожидания
-    pub fn queue_request(&mut self, resource_name: &str, process_name: &str) {
-        if let Some(queue) = self.request_queues.get_mut(resource_name) {
-            queue.push_back(process_name.to_string());
+    /// Увести процесс из очереди ресурса по истечении его таймаута.
+    /// `None`, если процесса в очереди уже нет
+    pub fn renege(&mut self, resource_name: &str, process_name: &str, now: SimTime) -> Option<Denial> {
+        let resource = self.resources.get_mut(resource_name)?;
+        let queue = self.request_queues.get_mut(resource_name)?;
+        let index = queue.iter().position(|q| q.process == process_name)?;
+        let queued = queue.remove(index)?;
+        resource.total_reneged += 1;
+        resource.observe(now, queue.len());
+        dequeued(&mut self.queued, process_name);
+        self.unblock(resource_name, now);
 
-            if let Some(resource) = self.resources.get_mut(resource_name) {
-                resource.queue_length = queue.len();
+        Some(Denial {
+            process: process_name.to_string(),
+            resource: resource_name.to_string(),
+            reason: DenialReason::TimedOut,
+            requested_at: queued.requested_at,
+            denied_at: now,
+        })
+    }
+
+    /// Освободить ресурс, которым владеет процесс: `units` единиц или,
+    /// если `None`, всё, что выдано по самому раннему из его запросов.
+    /// Освободившиеся единицы сразу передаются ожидающим. Ошибка, если
+    /// ресурса нет или процесс не держит столько его единиц (например,
+    /// потому что его уже вытеснили)
+    pub fn release(
+        &mut self,
+        resource_name: &str,
+        process_name: &str,
+        units: Option<usize>,
+        now: SimTime,
+    ) -> Result<Vec<Grant>, String> {
+        let (Some(resource), Some(holders)) = (
+            self.resources.get_mut(resource_name),
+            self.holders.get_mut(resource_name),
+        ) else {
+            return Err(format!("Resource '{}' not found", resource_name));
+        };
+
+        let held: usize = holders.iter().filter(|h| h.process == process_name).map(|h| h.units).sum();
+        let Some(first) = holders.iter().position(|h| h.process == process_name) else {
+            return Err(format!("Process '{}' does not hold resource '{}'", process_name, resource_name));
+        };
+        let mut remaining = units.unwrap_or(holders[first].units);
+        if remaining > held {
+            return Err(format!(
+                "Process '{}' holds {} units of resource '{}', cannot release {}",
+                process_name, held, resource_name, remaining
+            ));
+        }
+        while remaining > 0 {
+            let Some(index) = holders.iter().position(|h| h.process == process_name) else { break };
+            let holder = &mut holders[index];
+            let freed = remaining.min(holder.units);
+            holder.units -= freed;
+            // Первыми освобождаются единицы пула, выданные последними
+            let kept = holder.assigned.len().saturating_sub(freed);
+            let vacated = holder.assigned.split_off(kept);
+            resource.vacate(&vacated, freed, now);
+            remaining -= freed;
+            if holder.units == 0 {
+                let holder = holders.remove(index);
+                resource.hold_times.record((now - holder.since).as_seconds());
             }
         }
+
+        // Совместные запросы получают только то, что осталось после
+        // очереди ресурса, и не обгоняют ждущих в ней
+        let mut grants = Vec::new();
+        while let Some(grant) = self.grant_next(resource_name, now) {
+            grants.push(grant);
+        }
+        self.serve_joint(now);
+        if let (Some(resource), Some(queue)) = (self.resources.get_mut(resource_name), self.request_queues.get(resource_name)) {
+            resource.observe(now, queue.len());
+        }
+        Ok(grants)
     }
 
-    /// Получить статистику по ресурсам
-    pub fn get_stats(&self) -> Vec<serde_json::Value> {
-        self.resources
-            .values()
-            .map(|r| {
-                serde_json::json!({
-                    "name": r.name,
-                    "capacity": r.capacity,
-                    "available": r.available,
-                    "utilization": (r.capacity - r.available) as f64 / r.capacity as f64,
-                    "queue_length": r.queue_length,
-                    "total_requests": r.total_requests,
-                })
+    /// Ресурсы, которые сейчас держит процесс, и число единиц каждого
+    pub fn holdings(&self, process_name: &str) -> Vec<(String, usize)> {
+        let mut holdings: Vec<(String, usize)> = self
+            .holders
+            .iter()
+            .filter_map(|(name, holders)| {
+                let units: usize = holders.iter().filter(|h| h.process == process_name).map(|h| h.units).sum();
+                (units > 0).then(|| (name.clone(), units))
             })
-            .collect()
+            .collect();
+        holdings.sort();
+        holdings
+    }
+
+    /// Освободить всё, что держит процесс (например, когда он завершился).
+    /// Возвращает освобождённые ресурсы и выдачи ожидающим
+    pub fn release_all(&mut self, process_name: &str, now: SimTime) -> (Vec<(String, usize)>, Vec<Grant>) {
+        let holdings = self.holdings(process_name);
+        let mut grants = Vec::new();
+        for (resource, units) in &holdings {
+            if let Ok(released) = self.release(resource, process_name, Some(*units), now) {
+                grants.extend(released);
+            }
+        }
+        (holdings, grants)
+    }
+
+    /// Совместный запрос нескольких ресурсов (`request_all` / `request_any`).
+    /// Возвращает выдачу, если её можно сделать немедленно, иначе процесс
+    /// ждёт и получит ресурсы через `take_joint_grants`
+    pub fn request_joint(
+        &mut self,
+        resource_names: &[String],
+        process_name: &str,
+        mode: JointMode,
+        now: SimTime,
+    ) -> Result<Option<JointGrant>, String> {
+        if resource_names.is_empty() {
+            return Err("joint request needs at least one resource".to_string());
+        }
+        for (i, name) in resource_names.iter().enumerate() {
+            if !self.exists(name) {
+                return Err(format!("Resource '{}' not found", name));
+            }
+            if resource_names[..i].contains(name) {
+                return Err(format!("Resource '{}' is listed twice", name));
+            }
+        }
+
+        let request = JointRequest {
+            process: process_name.to_string(),
+            resources: resource_names.to_vec(),
+            mode,
+            requested_at: now,
+        };
+        if let Some(grant) = self.try_joint(&request, now) {
+            return Ok(Some(grant));
+        }
+        enqueued(&mut self.queued, process_name);
+        self.joint_queue.push_back(request);
+        Ok(None)
+    }
+
+    /// Забрать совместные выдачи, сделанные с момента последнего вызова
+    pub fn take_joint_grants(&mut self) -> Vec<JointGrant> {
+        std::mem::take(&mut self.joint_granted)
+    }
+
+    /// Выдать ресурсы ожидающим совместным запросам, для которых это стало возможно
+    fn serve_joint(&mut self, now: SimTime) {
+        let mut index = 0;
+        while index < self.joint_queue.len() {
+            let request = self.joint_queue[index].clone();
+            match self.try_joint(&request, now) {
+                Some(grant) => {
+                    self.joint_queue.remove(index);
+                    dequeued(&mut self.queued, &grant.process);
+                    self.joint_granted.push(grant);
+                }
+                None => index += 1,
+            }
+        }
+    }
+
+    fn try_joint(&mut self, request: &JointRequest, now: SimTime) -> Option<JointGrant> {
+        let free = |name: &String| !self.joint_free_units(name).is_empty();
+        let chosen: Vec<String> = match request.mode {
+            JointMode::All if request.resources.iter().all(free) => request.resources.clone(),
+            JointMode::Any => request.resources.iter().find(|name| free(name)).cloned().into_iter().collect(),
+            JointMode::All => Vec::new(),
+        };
+        if chosen.is_empty() {
+            return None;
+        }
+
+        let grants = chosen
+            .iter()
+            .filter_map(|name| self.take_unit(name, &request.process, request.requested_at, now))
+            .collect();
+        Some(JointGrant {
+            process: request.process.clone(),
+            mode: request.mode,
+            grants,
+        })
+    }
+
+    /// Свободные единицы ресурса, доступные совместному запросу: при
+    /// `BatchPolicy::Strict` он не берёт единицы, зарезервированные очередью
+    fn joint_free_units(&self, resource_name: &str) -> Vec<usize> {
+        let backfill = self.batch_policies.get(resource_name) == Some(&BatchPolicy::Backfill);
+        match (self.resources.get(resource_name), self.request_queues.get(resource_name)) {
+            (Some(resource), Some(queue)) => unreserved(resource, queue, backfill),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Занять единицу ресурса, на которую не претендует его очередь
+    fn take_unit(&mut self, resource_name: &str, process_name: &str, requested_at: SimTime, now: SimTime) -> Option<Grant> {
+        let picked: Vec<usize> = self.joint_free_units(resource_name).into_iter().take(1).collect();
+        let resource = self.resources.get_mut(resource_name)?;
+        let queue = self.request_queues.get(resource_name)?;
+        if picked.is_empty() {
+            return None;
+        }
+        let assigned = resource.occupy(&picked, now);
+        resource.record_grant(requested_at, now);
+        resource.observe(now, queue.len());
+        self.holders.get_mut(resource_name)?.push(Holder {
+            process: process_name.to_string(),
+            options: RequestOptions::default(),
+            units: 1,
+            assigned: picked,
+            since: now,
+        });
+
+        Some(Grant {
+            process: process_name.to_string(),
+            resource: resource_name.to_string(),
+            units: 1,
+            assigned,
+            requested_at,
+            granted_at: now,
+        })
+    }
+
+    /// Изменить ёмкость ресурса. Освободившиеся единицы сразу выдаются
+    /// ожидающим; при падении ниже числа занятых единиц владельцы либо
+    /// дорабатывают, либо вытесняются (забираются через `take_preempted`)
+    pub fn set_capacity(
+        &mut self,
+        resource_name: &str,
+        capacity: usize,
+        policy: CapacityDropPolicy,
+        now: SimTime,
+    ) -> Result<Vec<Grant>, String> {
+        let (Some(resource), Some(queue), Some(holders)) = (
+            self.resources.get_mut(resource_name),
+            self.request_queues.get_mut(resource_name),
+            self.holders.get_mut(resource_name),
+        ) else {
+            return Err(format!("Resource '{}' not found", resource_name));
+        };
+        if resource.pool.is_some() {
+            return Err(format!("Pool '{}': capacity is defined by its units", resource_name));
+        }
+        resource.capacity = capacity;
+
+        if policy == CapacityDropPolicy::Preempt {
+            while resource.in_use > resource.capacity {
+                let Some(victim) = excess_holder(holders) else { break };
+                let evicted = evict(resource, queue, &mut self.queued, holders.remove(victim), "capacity", resource_name, now);
+                self.preempted.push(evicted);
+            }
+        }
+
+        let mut grants = Vec::new();
+        while let Some(grant) = self.grant_next(resource_name, now) {
+            grants.push(grant);
+        }
+        self.serve_joint(now);
+        if let (Some(resource), Some(queue)) = (self.resources.get_mut(resource_name), self.request_queues.get(resource_name)) {
+            resource.observe(now, queue.len());
+        }
+        Ok(grants)
+    }
+
+    /// Вывести ресурс из строя. Возвращает владельцев, продолжающих держать
+    /// ресурс (их работу при `FailurePolicy::Delay` приостанавливает симулятор);
+    /// при `Interrupt` владельцы вытесняются и забираются через `take_preempted`
+    pub fn break_down(&mut self, resource_name: &str, policy: FailurePolicy, now: SimTime) -> Result<Vec<String>, String> {
+        let (Some(resource), Some(queue), Some(holders)) = (
+            self.resources.get_mut(resource_name),
+            self.request_queues.get_mut(resource_name),
+            self.holders.get_mut(resource_name),
+        ) else {
+            return Err(format!("Resource '{}' not found", resource_name));
+        };
+        if resource.down_since.is_some() {
+            return Ok(Vec::new());
+        }
+
+        resource.down_since = Some(now);
+        resource.failures += 1;
+        resource.up_stats.update(now, 0.0);
+
+        if policy == FailurePolicy::Interrupt {
+            // Сначала наименее важные и получившие ресурс позже, как при вытеснении
+            while let Some(victim) = excess_holder(holders) {
+                let evicted = evict(resource, queue, &mut self.queued, holders.remove(victim), "breakdown", resource_name, now);
+                self.preempted.push(evicted);
+            }
+        }
+        resource.observe(now, queue.len());
+
+        Ok(holders.iter().map(|h| h.process.clone()).collect())
+    }
+
+    /// Вернуть ресурс в строй и выдать освободившиеся единицы ожидающим
+    pub fn repair(&mut self, resource_name: &str, now: SimTime) -> Result<Vec<Grant>, String> {
+        let resource = self
+            .resources
+            .get_mut(resource_name)
+            .ok_or_else(|| format!("Resource '{}' not found", resource_name))?;
+        let Some(down_since) = resource.down_since.take() else {
+            return Ok(Vec::new());
+        };
+        resource.downtime.record((now - down_since).as_seconds());
+        resource.total_downtime += now - down_since;
+        resource.up_stats.update(now, 1.0);
+
+        let mut grants = Vec::new();
+        while let Some(grant) = self.grant_next(resource_name, now) {
+            grants.push(grant);
+        }
+        self.serve_joint(now);
+        if let (Some(resource), Some(queue)) = (self.resources.get_mut(resource_name), self.request_queues.get(resource_name)) {
+            resource.observe(now, queue.len());
+        }
+        Ok(grants)
+    }
+
+    /// Выдать свободные единицы очередному запросу: первому в очереди,
+    /// которому их хватает. При `BatchPolicy::Strict` единицы, подходящие
+    /// запросу, который пока не обслужить, не достаются стоящим за ним
+    fn grant_next(&mut self, resource_name: &str, now: SimTime) -> Option<Grant> {
+        let backfill = self.batch_policies.get(resource_name) == Some(&BatchPolicy::Backfill);
+        let resource = self.resources.get_mut(resource_name)?;
+        let queue = self.request_queues.get_mut(resource_name)?;
+
+        let mut free = resource.free_units();
+        let mut chosen = None;
+        for (index, queued) in queue.iter().enumerate() {
+            if free.is_empty() {
+                break;
+            }
+            let units = queued.options.units.max(1);
+            let matching = resource.matching(&free, &queued.options.skills);
+            if matching.len() >= units {
+                chosen = Some((index, matching[..units].to_vec()));
+                break;
+            }
+            if !backfill {
+                free.retain(|unit| !matching.contains(unit));
+            }
+        }
+        let (index, picked) = chosen?;
+
+        let next = queue.remove(index)?;
+        dequeued(&mut self.queued, &next.process);
+        let units = picked.len();
+        let assigned = resource.occupy(&picked, now);
+        if next.requeued {
+            resource.record_wait(next.requested_at, now);
+        } else {
+            resource.record_grant(next.requested_at, now);
+        }
+        self.holders.get_mut(resource_name)?.push(Holder {
+            process: next.process.clone(),
+            options: next.options,
+            units,
+            assigned: picked,
+            since: now,
+        });
+
+        Some(Grant {
+            process: next.process,
+            resource: resource_name.to_string(),
+            units,
+            assigned,
+            requested_at: next.requested_at,
+            granted_at: now,
+        })
+    }
+
+    /// Убрать процесс из всех очередей (например, при прерывании)
+    pub fn withdraw(&mut self, process_name: &str, now: SimTime) {
+        if self.queued.remove(process_name).is_none() {
+            return;
+        }
+        let mut changed = Vec::new();
+        for (name, queue) in self.request_queues.iter_mut() {
+            let before = queue.len();
+            queue.retain(|q| q.process != process_name);
+            if queue.len() != before {
+                if let Some(resource) = self.resources.get_mut(name) {
+                    resource.observe(now, queue.len());
+                }
+                changed.push(name.clone());
+            }
+        }
+        for name in changed {
+            self.unblock(&name, now);
+        }
+        for container in self.containers.values_mut() {
+            for op in container.withdraw(process_name, now) {
+                dequeued(&mut self.queued, &op.process);
+                self.unblocked_ops.push(op);
+            }
+        }
+        for store in self.stores.values_mut() {
+            store.withdraw(process_name);
+        }
+        self.joint_queue.retain(|j| j.process != process_name);
+    }
+
+    /// Учесть операцию `process`, вставшую в очередь контейнера или
+    /// хранилища, и завершённые операции `done`, которые из очереди ушли
+    fn track_ops<'a>(&mut self, process: &str, done: impl Iterator<Item = &'a str>) {
+        enqueued(&mut self.queued, process);
+        for process in done {
+            dequeued(&mut self.queued, process);
+        }
+    }
+
+    /// Обслужить очередь после ухода из неё запроса: ушедший мог стоять
+    /// первым и не давать пройти запросам, которым единиц хватает
+    fn unblock(&mut self, resource_name: &str, now: SimTime) {
+        while let Some(grant) = self.grant_next(resource_name, now) {
+            self.unblocked.push(grant);
+        }
+        // Ушедший мог резервировать единицы и от совместных запросов
+        self.serve_joint(now);
+    }
+
+    /// Забрать выдачи, ставшие возможными после ухода запросов из очередей
+    pub fn take_unblocked(&mut self) -> Vec<Grant> {
+        std::mem::take(&mut self.unblocked)
+    }
+
+    /// Забрать операции с контейнерами, ставшие возможными после ухода
+    /// процессов из очередей
+    pub fn take_unblocked_ops(&mut self) -> Vec<ContainerOp> {
+        std::mem::take(&mut self.unblocked_ops)
+    }
+
+    /// Забрать владельцев, вытесненных с момента последнего вызова
+    pub fn take_preempted(&mut self) -> Vec<Preempted> {
+        std::mem::take(&mut self.preempted)
+    }
+
+    /// Есть ли процессы, ожидающие какой-либо ресурс
+    pub fn has_waiting(&self) -> bool {
+        self.request_queues.values().any(|q| !q.is_empty())
+            || self.containers.values().any(Container::has_waiting)
+            || self.stores.values().any(Store::has_waiting)
+            || !self.joint_queue.is_empty()
+    }
+
+    /// Получить статистику по ресурсам: текущее состояние, средние по времени
+    /// занятость и длину очереди на момент `now`, распределения ожидания и владения
+    pub fn get_stats(&self, now: SimTime) -> Vec<serde_json::Value> {
+        self.resources.values().map(|r| r.stats(now)).collect()
+    }
+
+    /// Статистика контейнеров, включая средний по времени уровень на момент `now`
+    pub fn get_container_stats(&self, now: SimTime) -> Vec<serde_json::Value> {
+        self.containers.values().map(|c| c.stats(now)).collect()
+    }
+
+    /// Статистика хранилищ, включая среднее по времени число предметов
+    pub fn get_store_stats(&self, now: SimTime) -> Vec<serde_json::Value> {
+        self.stores.values().map(|s| s.stats(now)).collect()
+    }
+}
+
+impl Default for ResourceManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Свободные единицы, на которые не претендуют запросы из очереди.
+/// При `Backfill` очередь никому не мешает, и свободны все
+fn unreserved(resource: &Resource, queue: &VecDeque<QueuedRequest>, backfill: bool) -> Vec<usize> {
+    let mut free = resource.free_units();
+    if !backfill {
+        for queued in queue {
+            if free.is_empty() {
+                break;
+            }
+            let matching = resource.matching(&free, &queued.options.skills);
+            free.retain(|unit| !matching.contains(unit));
+        }
+    }
+    free
+}
+
+/// Владельцы, которых может вытеснить запрос с приоритетом `priority`, чтобы
+/// освободить `needed` единиц: сначала наименее важные, а среди равных —
+/// получившие ресурс последними. `None`, если столько единиц не набрать
+fn preemption_victims(holders: &[Holder], priority: i32, needed: usize) -> Option<Vec<usize>> {
+    let mut candidates: Vec<usize> = (0..holders.len())
+        .filter(|&i| holders[i].options.priority > priority)
+        .collect();
+    candidates.sort_by_key(|&i| std::cmp::Reverse((holders[i].options.priority, i)));
+
+    let mut victims = Vec::new();
+    let mut freed = 0;
+    for i in candidates {
+        if freed >= needed {
+            break;
+        }
+        freed += holders[i].units;
+        victims.push(i);
+    }
+    (freed >= needed).then_some(victims)
+}
+
+/// Владелец, который теряет единицу при снижении ёмкости: наименее важный,
+/// а среди равных — получивший ресурс последним
+fn excess_holder(holders: &[Holder]) -> Option<usize> {
+    holders
+        .iter()
+        .enumerate()
+        .max_by_key(|(i, h)| (h.options.priority, *i))
+        .map(|(i, _)| i)
+}
+
+/// Процесс встал в очередь
+fn enqueued(queued: &mut HashMap<String, usize>, process: &str) {
+    *queued.entry(process.to_string()).or_default() += 1;
+}
+
+/// Процесс ушёл из очереди: получил ресурс, отказался или выполнил операцию
+fn dequeued(queued: &mut HashMap<String, usize>, process: &str) {
+    if let Some(count) = queued.get_mut(process) {
+        *count -= 1;
+        if *count == 0 {
+            queued.remove(process);
+        }
+    }
+}
+
+/// Отобрать единицу у владельца: вытесненный с `requeue` встаёт первым
+/// среди запросов своего приоритета, остальные получат прерывание `cause`
+fn evict(
+    resource: &mut Resource,
+    queue: &mut VecDeque<QueuedRequest>,
+    queued: &mut HashMap<String, usize>,
+    victim: Holder,
+    cause: &str,
+    by: &str,
+    now: SimTime,
+) -> Preempted {
+    resource.vacate(&victim.assigned, victim.units, now);
+    resource.total_preemptions += 1;
+    resource.hold_times.record((now - victim.since).as_seconds());
+
+    let requeued = victim.options.requeue;
+    if requeued {
+        let position = queue.partition_point(|q| q.options.priority < victim.options.priority);
+        queue.insert(position, QueuedRequest {
+            process: victim.process.clone(),
+            options: victim.options.clone(),
+            requested_at: now,
+            // Первым среди своего приоритета при любой дисциплине
+            key: f64::NEG_INFINITY,
+            requeued: true,
+        });
+        enqueued(queued, &victim.process);
+    }
+
+    Preempted {
+        process: victim.process,
+        cause: cause.to_string(),
+        preemption: Preemption {
+            resource: resource.name.clone(),
+            by: by.to_string(),
+            usage_since: victim.since,
+        },
+        requeued,
     }
 }
@@ -0,0 +1,122 @@
+//! Пул именованных единиц ресурса (операторы, станки) с атрибутами
+
+use serde::{Serialize, Deserialize};
+
+use super::{Item, TimeWeighted};
+use crate::core::SimTime;
+
+/// Именованная единица пула. Атрибуты — произвольный JSON-объект;
+/// навыки для маршрутизации запросов берутся из его поля `skills`
+/// (массив строк), например `{"skills": ["english", "billing"], "cost": 30}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Unit {
+    pub name: String,
+    pub attributes: Item,
+}
+
+impl Unit {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            attributes: Item::Null,
+        }
+    }
+
+    pub fn with_attributes(mut self, attributes: Item) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
+    /// Есть ли у единицы все навыки из `skills`
+    pub fn has_skills(&self, skills: &[String]) -> bool {
+        let own = self.attributes.get("skills").and_then(Item::as_array);
+        skills.iter().all(|skill| {
+            own.is_some_and(|own| own.iter().any(|s| s.as_str() == Some(skill.as_str())))
+        })
+    }
+}
+
+/// Единица пула вместе с её занятостью
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PoolUnit {
+    unit: Unit,
+    busy: bool,
+    busy_stats: TimeWeighted, // 1 — занята, 0 — свободна
+    served: u64,
+}
+
+/// Единицы ресурса, каждая из которых выдаётся и учитывается отдельно
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct Pool {
+    units: Vec<PoolUnit>,
+}
+
+impl Pool {
+    pub(super) fn new(units: Vec<Unit>, now: SimTime) -> Self {
+        let units = units
+            .into_iter()
+            .map(|unit| PoolUnit {
+                unit,
+                busy: false,
+                busy_stats: TimeWeighted::new(now, 0.0),
+                served: 0,
+            })
+            .collect();
+        Self { units }
+    }
+
+    /// Индексы свободных единиц в порядке их объявления
+    pub(super) fn free(&self) -> Vec<usize> {
+        (0..self.units.len()).filter(|&i| !self.units[i].busy).collect()
+    }
+
+    /// Сколько единиц пула вообще обладает навыками `skills`
+    pub(super) fn capable(&self, skills: &[String]) -> usize {
+        self.units.iter().filter(|u| u.unit.has_skills(skills)).count()
+    }
+
+    pub(super) fn matching(&self, candidates: &[usize], skills: &[String]) -> Vec<usize> {
+        candidates
+            .iter()
+            .copied()
+            .filter(|&i| self.units[i].unit.has_skills(skills))
+            .collect()
+    }
+
+    /// Занять единицы и вернуть их имена
+    pub(super) fn occupy(&mut self, indices: &[usize], now: SimTime) -> Vec<String> {
+        indices
+            .iter()
+            .map(|&i| {
+                let unit = &mut self.units[i];
+                unit.busy = true;
+                unit.served += 1;
+                unit.busy_stats.update(now, 1.0);
+                unit.unit.name.clone()
+            })
+            .collect()
+    }
+
+    pub(super) fn vacate(&mut self, indices: &[usize], now: SimTime) {
+        for &i in indices {
+            let unit = &mut self.units[i];
+            unit.busy = false;
+            unit.busy_stats.update(now, 0.0);
+        }
+    }
+
+    pub(super) fn stats(&self, now: SimTime) -> serde_json::Value {
+        self.units
+            .iter()
+            .map(|u| {
+                serde_json::json!({
+                    "name": u.unit.name,
+                    "attributes": u.unit.attributes,
+                    "busy": u.busy,
+                    "utilization": u.busy_stats.mean(now),
+                    "served": u.served,
+                })
+            })
+            .collect()
+    }
+}
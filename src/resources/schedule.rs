@@ -0,0 +1,77 @@
+//! Расписание ёмкости ресурса (смены)
+
+use crate::core::Duration;
+
+/// Что делать с владельцами, когда ёмкость падает ниже числа занятых единиц
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CapacityDropPolicy {
+    /// Владельцы дорабатывают, новые выдачи ждут, пока занятых не станет меньше ёмкости
+    #[default]
+    Finish,
+    /// Лишние владельцы (наименее важные, затем получившие позже) вытесняются
+    /// с причиной `"capacity"`; запрос с `requeue` возвращается в очередь
+    Preempt,
+}
+
+/// Ёмкость ресурса как ступенчатая функция времени.
+///
+/// Смены задаются смещением от момента установки расписания, например
+/// 2 кассира с 9 до 12 и 4 кассира с 12 до 14 (время в часах):
+/// `[(9, 2), (12, 4), (14, 0)]`, а с `repeating(24)` — каждый день.
+/// До первой смены действует ёмкость, с которой ресурс был создан
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapacitySchedule {
+    shifts: Vec<(Duration, usize)>,
+    cycle: Option<Duration>,
+    policy: CapacityDropPolicy,
+}
+
+impl CapacitySchedule {
+    pub fn new(shifts: Vec<(Duration, usize)>) -> Self {
+        Self {
+            shifts,
+            cycle: None,
+            policy: CapacityDropPolicy::default(),
+        }
+    }
+
+    /// Повторять смены с периодом `cycle`
+    pub fn repeating(mut self, cycle: Duration) -> Self {
+        self.cycle = Some(cycle);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: CapacityDropPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn shifts(&self) -> &[(Duration, usize)] {
+        &self.shifts
+    }
+
+    pub fn cycle(&self) -> Option<Duration> {
+        self.cycle
+    }
+
+    pub fn policy(&self) -> CapacityDropPolicy {
+        self.policy
+    }
+
+    /// Смены должны идти по возрастанию и укладываться в период
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.shifts.is_empty() {
+            return Err("capacity schedule has no shifts".to_string());
+        }
+        if self.shifts.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err("capacity schedule shifts must have strictly increasing start times".to_string());
+        }
+        if let Some(cycle) = self.cycle {
+            let last = self.shifts[self.shifts.len() - 1].0;
+            if cycle == Duration::ZERO || last >= cycle {
+                return Err(format!("capacity schedule shifts must start within the cycle of {}", cycle));
+            }
+        }
+        Ok(())
+    }
+}
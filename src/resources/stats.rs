@@ -0,0 +1,271 @@
+//! Статистика ресурсов по модельному времени
+
+use serde::{Serialize, Deserialize};
+
+use crate::core::SimTime;
+
+/// Показатель, усредняемый по времени (уровень, длина очереди, занятость).
+///
+/// Значение считается постоянным между вызовами `update`, поэтому среднее —
+/// это площадь под ступенчатой функцией, делённая на прошедшее время
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeWeighted {
+    start: SimTime,
+    last_time: SimTime,
+    value: f64,
+    area: f64,
+    min: f64,
+    max: f64,
+}
+
+impl TimeWeighted {
+    pub fn new(start: SimTime, value: f64) -> Self {
+        Self {
+            start,
+            last_time: start,
+            value,
+            area: 0.0,
+            min: value,
+            max: value,
+        }
+    }
+
+    /// Зафиксировать новое значение начиная с момента `now`
+    pub fn update(&mut self, now: SimTime, value: f64) {
+        self.area += self.value * (now - self.last_time).as_seconds();
+        self.last_time = self.last_time.max(now);
+        self.value = value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    /// Среднее по времени от начала наблюдения до `now`
+    pub fn mean(&self, now: SimTime) -> f64 {
+        let elapsed = (now - self.start).as_seconds();
+        if elapsed <= 0.0 {
+            return self.value;
+        }
+        let area = self.area + self.value * (now - self.last_time).as_seconds();
+        area / elapsed
+    }
+
+    pub fn current(&self) -> f64 {
+        self.value
+    }
+
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+}
+
+/// Перцентили, которые `Tally` оценивает для `summary`
+const TRACKED_PERCENTILES: [f64; 4] = [50.0, 90.0, 95.0, 99.0];
+
+/// Сколько первых наблюдений хранится: пока их не больше, перцентили точные
+const EXACT_SAMPLES: usize = 5;
+
+/// Набор наблюдений (время ожидания, время владения) с моментами и перцентилями.
+///
+/// Память не растёт с числом наблюдений: среднее и дисперсия считаются
+/// алгоритмом Уэлфорда, перцентили — оценками P² (Jain & Chlamtac)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tally {
+    count: u64,
+    mean: f64,
+    m2: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+    /// Первые наблюдения по возрастанию; из них же начинаются оценки P²
+    first: Vec<f64>,
+    quantiles: Vec<P2Quantile>,
+}
+
+impl Default for Tally {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+            min: None,
+            max: None,
+            first: Vec::with_capacity(EXACT_SAMPLES),
+            quantiles: Vec::new(),
+        }
+    }
+}
+
+impl Tally {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, value: f64) {
+        // Онлайн-алгоритм Уэлфорда: среднее и дисперсия без потери точности
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+        self.min = Some(self.min.map_or(value, |min| min.min(value)));
+        self.max = Some(self.max.map_or(value, |max| max.max(value)));
+
+        if self.first.len() < EXACT_SAMPLES {
+            let position = self.first.partition_point(|x| x.total_cmp(&value).is_lt());
+            self.first.insert(position, value);
+            if self.first.len() == EXACT_SAMPLES {
+                self.quantiles = TRACKED_PERCENTILES
+                    .iter()
+                    .map(|&p| P2Quantile::new(p / 100.0, &self.first))
+                    .collect();
+            }
+            return;
+        }
+        for quantile in &mut self.quantiles {
+            quantile.record(value);
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.count as usize
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Выборочная дисперсия (0, пока наблюдений меньше двух)
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            return 0.0;
+        }
+        self.m2 / (self.count - 1) as f64
+    }
+
+    pub fn min(&self) -> Option<f64> {
+        self.min
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        self.max
+    }
+
+    /// Перцентиль `p` в [0, 100]. Пока наблюдений не больше пяти, он точный
+    /// (с линейной интерполяцией между наблюдениями) для любого `p`; дальше
+    /// оценивается только для p50, p90, p95 и p99, для остальных — `None`
+    pub fn percentile(&self, p: f64) -> Option<f64> {
+        if self.first.is_empty() {
+            return None;
+        }
+        if self.count as usize <= EXACT_SAMPLES {
+            return Some(interpolate(&self.first, p));
+        }
+        TRACKED_PERCENTILES
+            .iter()
+            .position(|&tracked| tracked == p)
+            .map(|index| self.quantiles[index].estimate())
+    }
+
+    /// Сводка для `get_stats`
+    pub fn summary(&self) -> serde_json::Value {
+        serde_json::json!({
+            "count": self.count(),
+            "mean": self.mean(),
+            "variance": self.variance(),
+            "min": self.min(),
+            "max": self.max(),
+            "p50": self.percentile(50.0),
+            "p90": self.percentile(90.0),
+            "p95": self.percentile(95.0),
+            "p99": self.percentile(99.0),
+        })
+    }
+}
+
+fn interpolate(sorted: &[f64], p: f64) -> f64 {
+    let rank = (p.clamp(0.0, 100.0) / 100.0) * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+/// Оценка одного квантиля алгоритмом P²: пять маркеров, средний из которых
+/// сходится к квантилю, без хранения наблюдений
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct P2Quantile {
+    /// Высоты маркеров
+    heights: [f64; 5],
+    /// Фактические позиции маркеров (номера наблюдений, с 1)
+    positions: [f64; 5],
+    /// Желаемые позиции и их приращение на каждое наблюдение
+    desired: [f64; 5],
+    increments: [f64; 5],
+}
+
+impl P2Quantile {
+    /// Квантиль `p` в [0, 1], начиная с пяти упорядоченных наблюдений
+    fn new(p: f64, first: &[f64]) -> Self {
+        Self {
+            heights: [first[0], first[1], first[2], first[3], first[4]],
+            positions: [1.0, 2.0, 3.0, 4.0, 5.0],
+            desired: [1.0, 1.0 + 2.0 * p, 1.0 + 4.0 * p, 3.0 + 2.0 * p, 5.0],
+            increments: [0.0, p / 2.0, p, (1.0 + p) / 2.0, 1.0],
+        }
+    }
+
+    fn record(&mut self, value: f64) {
+        // Ячейка между маркерами, в которую попало наблюдение; крайние
+        // маркеры хранят минимум и максимум
+        let cell = if value < self.heights[0] {
+            self.heights[0] = value;
+            0
+        } else if value >= self.heights[4] {
+            self.heights[4] = value;
+            3
+        } else {
+            (1..5).find(|&i| value < self.heights[i]).map_or(3, |i| i - 1)
+        };
+
+        for position in &mut self.positions[cell + 1..] {
+            *position += 1.0;
+        }
+        for (desired, increment) in self.desired.iter_mut().zip(self.increments) {
+            *desired += increment;
+        }
+
+        // Сдвинуть средние маркеры к желаемым позициям
+        for i in 1..4 {
+            let offset = self.desired[i] - self.positions[i];
+            let room_right = self.positions[i + 1] - self.positions[i] > 1.0;
+            let room_left = self.positions[i - 1] - self.positions[i] < -1.0;
+            if (offset >= 1.0 && room_right) || (offset <= -1.0 && room_left) {
+                let step = offset.signum();
+                let parabolic = self.parabolic(i, step);
+                self.heights[i] = if self.heights[i - 1] < parabolic && parabolic < self.heights[i + 1] {
+                    parabolic
+                } else {
+                    self.linear(i, step)
+                };
+                self.positions[i] += step;
+            }
+        }
+    }
+
+    fn parabolic(&self, i: usize, step: f64) -> f64 {
+        let (q, n) = (&self.heights, &self.positions);
+        q[i] + step / (n[i + 1] - n[i - 1])
+            * ((n[i] - n[i - 1] + step) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                + (n[i + 1] - n[i] - step) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]))
+    }
+
+    fn linear(&self, i: usize, step: f64) -> f64 {
+        let j = if step > 0.0 { i + 1 } else { i - 1 };
+        self.heights[i] + step * (self.heights[j] - self.heights[i]) / (self.positions[j] - self.positions[i])
+    }
+
+    fn estimate(&self) -> f64 {
+        self.heights[2]
+    }
+}
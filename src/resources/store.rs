@@ -0,0 +1,256 @@
+//! Хранилища предметов: Store, FilterStore и PriorityStore
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use super::stats::TimeWeighted;
+use crate::core::{Duration, SimTime};
+
+/// Предмет в хранилище. Lua-таблицы переводятся в JSON-значение, поэтому
+/// предмет можно передать между процессами с разными Lua VM
+pub type Item = serde_json::Value;
+
+/// Порядок выдачи предметов
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum StoreKind {
+    /// В порядке поступления
+    Fifo,
+    /// В порядке поступления, `get` может выбирать предмет фильтром
+    Filter,
+    /// По приоритету предмета (меньше = раньше), при равенстве — по поступлению
+    Priority,
+}
+
+/// Условие, которому должен удовлетворять забираемый предмет
+pub enum ItemFilter {
+    /// Lua-функция `item -> bool` в VM запросившего процесса
+    Lua(mlua::RegistryKey),
+    /// Функция процесса на Rust
+    Native(Box<dyn Fn(&Item) -> bool>),
+}
+
+impl fmt::Debug for ItemFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ItemFilter::Lua(_) => f.write_str("ItemFilter::Lua"),
+            ItemFilter::Native(_) => f.write_str("ItemFilter::Native"),
+        }
+    }
+}
+
+/// Завершённая операция с хранилищем
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoreOp {
+    pub process: String,
+    pub store: String,
+    /// Полученный предмет (`None` для `put`)
+    pub item: Option<Item>,
+    pub requested_at: SimTime,
+    pub completed_at: SimTime,
+}
+
+impl StoreOp {
+    pub fn wait_time(&self) -> Duration {
+        self.completed_at - self.requested_at
+    }
+}
+
+#[derive(Debug)]
+struct StoredItem {
+    item: Item,
+    priority: i64,
+}
+
+#[derive(Debug)]
+struct PendingPut {
+    process: String,
+    item: StoredItem,
+    requested_at: SimTime,
+}
+
+#[derive(Debug)]
+struct PendingGet {
+    process: String,
+    filter: Option<ItemFilter>,
+    requested_at: SimTime,
+}
+
+/// Хранилище предметов с необязательной ёмкостью.
+///
+/// `put` ждёт свободного места, `get` — подходящего предмета. Запросы `get`
+/// с фильтром, которым пока ничего не подходит, не задерживают следующих
+#[derive(Debug)]
+pub struct Store {
+    name: String,
+    kind: StoreKind,
+    capacity: Option<usize>,
+    items: Vec<StoredItem>,
+    put_queue: VecDeque<PendingPut>,
+    get_queue: Vec<PendingGet>,
+    size_stats: TimeWeighted,
+    total_put: u64,
+    total_get: u64,
+}
+
+impl Store {
+    pub(crate) fn new(name: &str, kind: StoreKind, capacity: Option<usize>, now: SimTime) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            capacity,
+            items: Vec::new(),
+            put_queue: VecDeque::new(),
+            get_queue: Vec::new(),
+            size_stats: TimeWeighted::new(now, 0.0),
+            total_put: 0,
+            total_get: 0,
+        }
+    }
+
+    pub fn kind(&self) -> StoreKind {
+        self.kind
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub(crate) fn put(
+        &mut self,
+        process: &str,
+        item: Item,
+        priority: i64,
+        now: SimTime,
+        matches: &mut dyn FnMut(&str, &ItemFilter, &Item) -> bool,
+    ) -> Vec<StoreOp> {
+        self.put_queue.push_back(PendingPut {
+            process: process.to_string(),
+            item: StoredItem { item, priority },
+            requested_at: now,
+        });
+        self.trigger(now, matches)
+    }
+
+    pub(crate) fn get(
+        &mut self,
+        process: &str,
+        filter: Option<ItemFilter>,
+        now: SimTime,
+        matches: &mut dyn FnMut(&str, &ItemFilter, &Item) -> bool,
+    ) -> Vec<StoreOp> {
+        self.get_queue.push(PendingGet {
+            process: process.to_string(),
+            filter,
+            requested_at: now,
+        });
+        self.trigger(now, matches)
+    }
+
+    pub(crate) fn withdraw(&mut self, process: &str) {
+        self.put_queue.retain(|op| op.process != process);
+        self.get_queue.retain(|op| op.process != process);
+    }
+
+    pub(crate) fn has_waiting(&self) -> bool {
+        !self.put_queue.is_empty() || !self.get_queue.is_empty()
+    }
+
+    fn has_room(&self) -> bool {
+        self.capacity.is_none_or(|capacity| self.items.len() < capacity)
+    }
+
+    /// Принимать предметы, пока есть место, и раздавать их ожидающим,
+    /// пока хоть один `get` находит подходящий предмет
+    fn trigger(
+        &mut self,
+        now: SimTime,
+        matches: &mut dyn FnMut(&str, &ItemFilter, &Item) -> bool,
+    ) -> Vec<StoreOp> {
+        let mut done = Vec::new();
+
+        loop {
+            let mut progressed = false;
+
+            while self.has_room() {
+                let Some(put) = self.put_queue.pop_front() else { break };
+                self.insert(put.item);
+                self.total_put += 1;
+                done.push(StoreOp {
+                    process: put.process,
+                    store: self.name.clone(),
+                    item: None,
+                    requested_at: put.requested_at,
+                    completed_at: now,
+                });
+                progressed = true;
+            }
+
+            let mut index = 0;
+            while index < self.get_queue.len() && !self.items.is_empty() {
+                let get = &self.get_queue[index];
+                let found = match &get.filter {
+                    None => Some(0),
+                    Some(filter) => self
+                        .items
+                        .iter()
+                        .position(|stored| matches(&get.process, filter, &stored.item)),
+                };
+
+                match found {
+                    Some(position) => {
+                        let get = self.get_queue.remove(index);
+                        let stored = self.items.remove(position);
+                        self.total_get += 1;
+                        done.push(StoreOp {
+                            process: get.process,
+                            store: self.name.clone(),
+                            item: Some(stored.item),
+                            requested_at: get.requested_at,
+                            completed_at: now,
+                        });
+                        progressed = true;
+                    }
+                    None => index += 1,
+                }
+            }
+
+            if !progressed {
+                break;
+            }
+        }
+
+        if !done.is_empty() {
+            self.size_stats.update(now, self.items.len() as f64);
+        }
+        done
+    }
+
+    fn insert(&mut self, item: StoredItem) {
+        match self.kind {
+            StoreKind::Fifo | StoreKind::Filter => self.items.push(item),
+            StoreKind::Priority => {
+                let position = self.items.partition_point(|stored| stored.priority <= item.priority);
+                self.items.insert(position, item);
+            }
+        }
+    }
+
+    pub(crate) fn stats(&self, now: SimTime) -> serde_json::Value {
+        serde_json::json!({
+            "name": self.name,
+            "kind": self.kind,
+            "capacity": self.capacity,
+            "items": self.items.len(),
+            "mean_items": self.size_stats.mean(now),
+            "max_items": self.size_stats.max(),
+            "put_queue_length": self.put_queue.len(),
+            "get_queue_length": self.get_queue.len(),
+            "total_put": self.total_put,
+            "total_get": self.total_get,
+        })
+    }
+}
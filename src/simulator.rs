@@ -1,325 +1,1264 @@
 //! Полноценная симуляция с Lua скриптингом
 
-use crate::core::{Simulation, SimTime};
-use crate::lua::{LuaEngine, ProcessMessage, LuaCommand, LogLevel};
-use crate::resources::ResourceManager;
+use crate::core::{Simulation, SimTime, Duration, EventHandle, Priority, ProcessId, RandomStreams};
+use crate::lua::{LuaEngine, LuaFunction, ProcessMessage, ProcessState, LuaCommand, LogLevel};
+use crate::process::{Interrupt, NativeEngine, Preemption, ProcessCtx, ProcessHandle, ProcessOutcome, ProcessResult};
+use crate::resources::{
+    BalkRule, BatchPolicy, Breakdown, CapacityDropPolicy, CapacitySchedule, ContainerOp, Denial, DurationSampler,
+    FailurePolicy, Grant, Item, ItemFilter, JointGrant, Preempted, QueueDiscipline, ResourceManager, StoreKind, StoreOp,
+    Unit,
+};
 use crate::SimError;
 
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::rc::Rc;
+
 use tracing::{info, debug, warn, error};
 use serde_json::json;
 
+/// Служебное событие симулятора, не связанное с процессом
+enum Timer {
+    /// Очередная смена в расписании ёмкости ресурса
+    Capacity {
+        resource: String,
+        schedule: CapacitySchedule,
+        next: usize,
+        cycle_start: SimTime,
+    },
+    /// Очередной отказ или окончание ремонта ресурса
+    Breakdown {
+        resource: String,
+        breakdown: Breakdown,
+        broken: bool,
+    },
+}
+
+/// Симулятор, в котором процессы (Lua и Rust) возобновляются событиями ядра.
+///
+/// Каждое пробуждение процесса — ожидание, выдача ресурса или старт после
+/// spawn — планируется в очередь [`Simulation`], поэтому один шаг основного
+/// цикла обрабатывает ровно одно событие
 pub struct Simulator {
-    simulation: Arc<Mutex<Simulation>>,
-    lua_engine: Arc<Mutex<LuaEngine>>,
-    resources: Arc<Mutex<ResourceManager>>,
-    waiting_processes: Arc<Mutex<Vec<(String, String)>>>,
-    ready_queue: Arc<Mutex<Vec<String>>>,
-    waiting_for_time: Arc<Mutex<Vec<(String, f64)>>>, // (process_name, wake_time)
+    simulation: Simulation,
+    lua_engine: LuaEngine,
+    native_engine: NativeEngine,
+    resources: ResourceManager,
+    process_ids: HashMap<String, ProcessId>,
+    process_names: HashMap<ProcessId, String>,
+    next_process_id: u64,
+    /// Запланированные пробуждения процессов: (событие, время пробуждения)
+    pending_wakes: HashMap<String, (EventHandle, SimTime)>,
+    /// Недоработанное время wait у вытесненных процессов, вернувшихся в очередь
+    suspended_work: HashMap<String, Duration>,
+    /// Процессы в очереди с таймаутом: их пробуждение означает уход из очереди
+    reneging: HashMap<String, String>,
+    /// Служебные события; их идентификаторы берутся из пространства ProcessId
+    timers: HashMap<ProcessId, Timer>,
+    /// Приостановленная поломкой работа владельцев: ресурс -> (процесс, остаток wait)
+    paused_by_breakdown: HashMap<String, Vec<(String, Duration)>>,
+    /// Случайные потоки, выведенные из master seed
+    streams: Rc<RefCell<RandomStreams>>,
+    /// Чем закончились завершившиеся процессы. Сами процессы удаляются,
+    /// а результат остаётся для `join` и `outcome`
+    outcomes: HashMap<String, ProcessOutcome>,
+    /// Процессы, ждущие завершения другого процесса: процесс -> ждущие
+    joiners: HashMap<String, Vec<String>>,
+    /// Номер последнего сгенерированного имени `<функция>#<номер>`
+    auto_names: u64,
+    /// Прерывания процессов, которые ещё не забрали результат своей операции:
+    /// они доставляются при следующем ожидании процесса
+    deferred_interrupts: HashMap<String, VecDeque<Interrupt>>,
 }
 
 impl Simulator {
+    /// Симулятор со случайным master seed; его можно узнать через `seed()`,
+    /// чтобы потом повторить прогон с `with_seed`
     pub fn new() -> Self {
+        Self::with_seed(rand::random())
+    }
+
+    /// Симулятор, все случайные потоки которого (процессов, именованных,
+    /// моделей отказов) выводятся из `seed`: прогон с тем же seed повторяется
+    pub fn with_seed(seed: u64) -> Self {
+        info!("Master seed симуляции: {}", seed);
+        let streams = Rc::new(RefCell::new(RandomStreams::new(seed)));
         Self {
-            simulation: Arc::new(Mutex::new(Simulation::new())),
-            lua_engine: Arc::new(Mutex::new(LuaEngine::new())),
-            resources: Arc::new(Mutex::new(ResourceManager::new())),
-            waiting_processes: Arc::new(Mutex::new(Vec::new())),
-            ready_queue: Arc::new(Mutex::new(Vec::new())),
-            waiting_for_time: Arc::new(Mutex::new(Vec::new())),
+            simulation: Simulation::new(),
+            lua_engine: LuaEngine::with_streams(streams.clone()),
+            native_engine: NativeEngine::with_streams(streams.clone()),
+            resources: ResourceManager::new(),
+            process_ids: HashMap::new(),
+            process_names: HashMap::new(),
+            next_process_id: 0,
+            pending_wakes: HashMap::new(),
+            suspended_work: HashMap::new(),
+            reneging: HashMap::new(),
+            timers: HashMap::new(),
+            paused_by_breakdown: HashMap::new(),
+            streams,
+            outcomes: HashMap::new(),
+            joiners: HashMap::new(),
+            auto_names: 0,
+            deferred_interrupts: HashMap::new(),
         }
     }
 
+    /// Master seed, из которого выведены все случайные потоки
+    pub fn seed(&self) -> u64 {
+        self.streams.borrow().seed()
+    }
+
+    /// Выполнить `script` в общей Lua VM и запустить процесс из функции `function`.
+    /// Глобальные таблицы скрипта общие для всех Lua-процессов, `spawn` из Lua
+    /// создаёт процесс из уже загруженной функции без повторного выполнения скрипта
     pub async fn load_process(
-        &self,
+        &mut self,
         name: &str,
         script: &str,
         function: &str,
-    ) -> Result<(), SimError> {
-        let mut engine = self.lua_engine.lock().await;
-        engine.create_process(name.to_string(), script, function)?;
-        
-        // Добавляем процесс в ready_queue
-        let mut ready = self.ready_queue.lock().await;
-        ready.push(name.to_string());
+    ) -> Result<ProcessHandle, SimError> {
+        self.load_process_with_args(name, script, function, Vec::new()).await
+    }
+
+    /// То же, что `load_process`, но функция получает аргументы `args`
+    /// (как у `spawn(name, function, ...)` в Lua)
+    pub async fn load_process_with_args(
+        &mut self,
+        name: &str,
+        script: &str,
+        function: &str,
+        args: Vec<Item>,
+    ) -> Result<ProcessHandle, SimError> {
+        self.ensure_unique_name(name)?;
+        self.lua_engine.create_process(name.to_string(), script, function, args)?;
         
+        // Процесс стартует в текущий момент модельного времени
+        self.start_process(name)?;
+        Ok(ProcessHandle::new(name))
+    }
+
+    /// Добавить процесс, написанный на Rust.
+    ///
+    /// Процесс — это `async` функция от [`ProcessCtx`]; ожидания внутри неё
+    /// идут по модельному времени и используют те же ресурсы, что и Lua процессы.
+    /// То, что она вернёт (`()`, `Item` или `Vec<Item>`), получит `join`
+    pub async fn spawn_process<F, Fut, R>(&mut self, name: &str, process: F) -> Result<ProcessHandle, SimError>
+    where
+        F: FnOnce(ProcessCtx) -> Fut + 'static,
+        Fut: Future<Output = Result<R, SimError>> + 'static,
+        R: ProcessResult,
+    {
+        self.ensure_unique_name(name)?;
+        self.native_engine.create_process(name.to_string(), process)?;
+
+        self.start_process(name)?;
+        Ok(ProcessHandle::new(name))
+    }
+
+    /// Чем закончился процесс; `None`, пока он не завершился
+    pub fn outcome(&self, process: &ProcessHandle) -> Option<&ProcessOutcome> {
+        self.outcomes.get(process.name())
+    }
+
+    /// Имя завершившегося процесса остаётся занятым: по нему доступен результат
+    fn ensure_unique_name(&self, name: &str) -> Result<(), SimError> {
+        if self.process_ids.contains_key(name) || self.outcomes.contains_key(name) {
+            return Err(SimError::ProcessError(format!(
+                "Process with name '{}' already exists",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Свободное имя вида `<функция>#<номер>` для процесса, созданного без имени
+    fn auto_name(&mut self, function: &str) -> String {
+        loop {
+            self.auto_names += 1;
+            let name = format!("{}#{}", function, self.auto_names);
+            if self.ensure_unique_name(&name).is_ok() {
+                return name;
+            }
+        }
+    }
+
+    /// Выдать процессу идентификатор и запланировать его первый запуск
+    fn start_process(&mut self, name: &str) -> Result<(), SimError> {
+        let id = self.next_id();
+        self.process_ids.insert(name.to_string(), id);
+        self.process_names.insert(id, name.to_string());
+
+        // Старт важнее прочих событий того же момента, как Initialize в SimPy
+        self.schedule_wake(name, Duration::ZERO, Priority::High)
+    }
+
+    fn next_id(&mut self) -> ProcessId {
+        let id = ProcessId::new(self.next_process_id);
+        self.next_process_id += 1;
+        id
+    }
+
+    /// Запланировать возобновление процесса по имени
+    fn schedule_wake(&mut self, name: &str, delay: Duration, priority: Priority) -> Result<(), SimError> {
+        let id = *self.process_ids.get(name).ok_or_else(|| {
+            SimError::ProcessError(format!("Process '{}' not found", name))
+        })?;
+        let handle = self.simulation.schedule_process_after(delay, priority, id)?;
+        self.pending_wakes.insert(name.to_string(), (handle, self.simulation.now() + delay));
         Ok(())
     }
 
-    pub async fn create_resource(&self, name: &str, capacity: usize) {
-        let mut resources = self.resources.lock().await;
-        resources.create(name, capacity);
+    fn wake_process(&mut self, name: &str, delay: Duration) -> Result<(), SimError> {
+        self.schedule_wake(name, delay, Priority::Normal)
+    }
+
+    /// Отменить запланированное пробуждение. Возвращает, сколько оставалось ждать
+    fn cancel_wake(&mut self, name: &str) -> Option<Duration> {
+        let (handle, wake_time) = self.pending_wakes.remove(name)?;
+        self.simulation.cancel(handle);
+        Some(wake_time - self.simulation.now())
+    }
+
+    /// Прервать ожидание процесса: его событие отменяется, запросы снимаются
+    /// с очередей, а прерывание доставляется при немедленном возобновлении
+    fn interrupt_process(
+        &mut self,
+        name: &str,
+        cause: &str,
+        preemption: Option<Preemption>,
+    ) -> Result<(), SimError> {
+        // Операция процесса уже завершилась (выдан ресурс, получен предмет),
+        // но он ещё не возобновился: результат нельзя затереть прерыванием
+        if self.has_pending_command(name) {
+            let interrupt = Interrupt {
+                cause: cause.to_string(),
+                interrupted_at: self.simulation.now(),
+                remaining: None,
+                preemption,
+            };
+            info!("Прерывание процесса {} отложено до его следующего ожидания: {}", name, interrupt);
+            self.deferred_interrupts.entry(name.to_string()).or_default().push_back(interrupt);
+            return Ok(());
+        }
+
+        // Недоработанный wait: по таймеру, у вытесненного в очередь или
+        // у приостановленного поломкой владельца
+        let mut remaining = self.cancel_wake(name);
+        self.resources.withdraw(name, self.simulation.now());
+        self.deliver_unblocked()?;
+        if let Some(suspended) = self.suspended_work.remove(name) {
+            remaining = Some(suspended);
+        }
+        self.reneging.remove(name);
+        for waiting in self.joiners.values_mut() {
+            waiting.retain(|process| process != name);
+        }
+        for paused in self.paused_by_breakdown.values_mut() {
+            if let Some(index) = paused.iter().position(|(process, _)| process == name) {
+                remaining = Some(paused.remove(index).1);
+            }
+        }
+
+        let interrupt = Interrupt {
+            cause: cause.to_string(),
+            interrupted_at: self.simulation.now(),
+            remaining,
+            preemption,
+        };
+        info!("Процесс {} прерван: {}", name, interrupt);
+
+        self.send_command(name, LuaCommand::Interrupt(interrupt))?;
+        self.set_process_state(name, ProcessState::Active);
+        self.schedule_wake(name, Duration::ZERO, Priority::High)
+    }
+
+    /// Прервать процесс по запросу пользователя; завершившийся процесс прервать нельзя
+    fn interrupt_waiting(&mut self, name: &str, cause: &str) -> Result<(), SimError> {
+        if !self.process_ids.contains_key(name) && !self.outcomes.contains_key(name) {
+            return Err(SimError::ProcessError(format!("Process '{}' not found", name)));
+        }
+        if self.process_ended(name) {
+            return Err(SimError::ProcessError(format!("Process '{}' has already finished", name)));
+        }
+        self.interrupt_process(name, cause, None)
+    }
+
+    /// Обработать владельцев, у которых запрос отобрал ресурс
+    fn handle_preempted(&mut self, preempted: Vec<Preempted>) -> Result<(), SimError> {
+        for victim in preempted {
+            if victim.requeued && !self.in_timed_wait(&victim.process) {
+                // Процесс ждёт другой операции: повторная выдача пришла бы в неё,
+                // поэтому он уходит из очереди и узнаёт о вытеснении прерыванием
+                self.resources.withdraw(&victim.process, self.simulation.now());
+                self.deliver_unblocked()?;
+                self.interrupt_process(&victim.process, &victim.cause, Some(victim.preemption))?;
+            } else if victim.requeued {
+                // Процесс молча ждёт в очереди, остаток wait доработает после выдачи
+                if let Some(remaining) = self.cancel_wake(&victim.process) {
+                    self.suspended_work.insert(victim.process.clone(), remaining);
+                }
+                info!("Процесс {} вытеснен с {} и вернулся в очередь",
+                      victim.process, victim.preemption.resource);
+                self.set_process_state(
+                    &victim.process,
+                    ProcessState::WaitingForResource(victim.preemption.resource.clone()),
+                );
+            } else {
+                self.interrupt_process(&victim.process, &victim.cause, Some(victim.preemption))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn create_resource(&mut self, name: &str, capacity: usize) {
+        self.resources.create(name, capacity, self.simulation.now());
         debug!("Создан ресурс: {} (емкость: {})", name, capacity);
     }
 
-    pub async fn run(&mut self, duration: f64) -> Result<(), SimError> {
-        info!("Запуск симуляции на {} секунд", duration);
+    /// Создать пул именованных единиц (например, операторов) с атрибутами.
+    /// Запрос может потребовать навыки (`RequestOptions::skills`), а выдача
+    /// сообщает, какие единицы назначены
+    pub async fn create_pool(&mut self, name: &str, units: Vec<Unit>) -> Result<(), SimError> {
+        let count = units.len();
+        self.resources
+            .create_pool(name, units, self.simulation.now())
+            .map_err(SimError::ResourceError)?;
+        debug!("Создан пул: {} (единиц: {})", name, count);
+        Ok(())
+    }
+
+    /// Создать ресурс, очередь которого упорядочена по приоритету запросов
+    pub async fn create_priority_resource(&mut self, name: &str, capacity: usize) {
+        self.resources.create_priority(name, capacity, self.simulation.now());
+        debug!("Создан приоритетный ресурс: {} (емкость: {})", name, capacity);
+    }
+
+    /// Создать приоритетный ресурс, в котором важный запрос вытесняет владельца
+    pub async fn create_preemptive_resource(&mut self, name: &str, capacity: usize) {
+        self.resources.create_preemptive(name, capacity, self.simulation.now());
+        debug!("Создан вытесняющий ресурс: {} (емкость: {})", name, capacity);
+    }
+
+    /// Немедленно изменить ёмкость ресурса
+    pub async fn set_capacity(
+        &mut self,
+        resource: &str,
+        capacity: usize,
+        policy: CapacityDropPolicy,
+    ) -> Result<(), SimError> {
+        self.apply_capacity(resource, capacity, policy)
+    }
+
+    /// Менять ёмкость ресурса по расписанию смен, отсчитываемому от текущего момента
+    pub async fn set_capacity_schedule(&mut self, resource: &str, schedule: CapacitySchedule) -> Result<(), SimError> {
+        if !self.resources.exists(resource) {
+            return Err(SimError::ResourceError(format!("Resource '{}' not found", resource)));
+        }
+        if self.resources.is_pool(resource) {
+            return Err(SimError::ResourceError(format!("Pool '{}': capacity is defined by its units", resource)));
+        }
+        schedule.validate().map_err(SimError::ResourceError)?;
+        self.resources.note_capacity_schedule(resource, &schedule);
+
+        let id = self.next_id();
+        let cycle_start = self.simulation.now();
+        self.simulation.schedule_process_at(cycle_start + schedule.shifts()[0].0, Priority::Normal, id)?;
+        self.timers.insert(id, Timer::Capacity {
+            resource: resource.to_string(),
+            schedule,
+            next: 0,
+            cycle_start,
+        });
+        Ok(())
+    }
+
+    fn apply_capacity(&mut self, resource: &str, capacity: usize, policy: CapacityDropPolicy) -> Result<(), SimError> {
+        info!("Ёмкость ресурса {} теперь {}", resource, capacity);
+
+        let now = self.simulation.now();
+        let grants = self
+            .resources
+            .set_capacity(resource, capacity, policy, now)
+            .map_err(SimError::ResourceError)?;
+        let preempted = self.resources.take_preempted();
+        self.handle_preempted(preempted)?;
+        self.deliver_grants(grants)
+    }
+
+    /// Подключить к ресурсу модель отказов: первый отказ наступит через
+    /// время, выбранное из `time_to_failure`, отсчитанное от текущего момента.
+    /// У ресурса может быть только одна модель отказов
+    pub async fn set_breakdown(&mut self, resource: &str, mut breakdown: Breakdown) -> Result<(), SimError> {
+        if !self.resources.exists(resource) {
+            return Err(SimError::ResourceError(format!("Resource '{}' not found", resource)));
+        }
+        let attached = self.timers.values().any(|timer| {
+            matches!(timer, Timer::Breakdown { resource: other, .. } if other == resource)
+        });
+        if attached {
+            return Err(SimError::ResourceError(format!(
+                "Resource '{}' already has a breakdown model",
+                resource
+            )));
+        }
+
+        let id = self.next_id();
+        let rng = self.streams.borrow_mut().stream(&format!("breakdown:{}", resource));
+        let time_to_failure = breakdown.time_to_failure.sample(&mut rng.borrow_mut())?;
+        self.simulation.schedule_process_after(time_to_failure, Priority::Normal, id)?;
+        self.timers.insert(id, Timer::Breakdown {
+            resource: resource.to_string(),
+            breakdown,
+            broken: false,
+        });
+        Ok(())
+    }
+
+    /// Модель отказов на Lua: функции `time_to_failure` и `repair_time` из `script`
+    /// получают таблицу {resource = ...} и возвращают интервал в секундах
+    pub async fn set_breakdown_lua(
+        &mut self,
+        resource: &str,
+        script: &str,
+        time_to_failure: &str,
+        repair_time: &str,
+        policy: FailurePolicy,
+    ) -> Result<(), SimError> {
+        let breakdown = Breakdown::new(
+            lua_sampler(self.lua_function(script, time_to_failure, &format!("breakdown:{}", resource))?, resource),
+            lua_sampler(self.lua_function(script, repair_time, &format!("breakdown:{}", resource))?, resource),
+        )
+        .with_policy(policy);
+        self.set_breakdown(resource, breakdown).await
+    }
+
+    /// Lua-функция из `script`, у которой `math.random` берёт числа из потока `stream`
+    fn lua_function(&self, script: &str, function: &str, stream: &str) -> Result<LuaFunction, SimError> {
+        Ok(LuaFunction::with_streams(script, function, self.streams.clone(), stream)?)
+    }
+
+    /// Обработать служебное событие и запланировать следующее
+    fn fire_timer(&mut self, id: ProcessId, timer: Timer) -> Result<(), SimError> {
+        match timer {
+            Timer::Capacity { resource, schedule, next, mut cycle_start } => {
+                let (_, capacity) = schedule.shifts()[next];
+                self.apply_capacity(&resource, capacity, schedule.policy())?;
 
-        let sim = self.simulation.lock().await;
-        let start_time = sim.now().await;
-        let end_time = SimTime::new(start_time.as_seconds() + duration);
-        drop(sim);
-
-        // Основной цикл симуляции
-        while self.now().await < end_time {
-            // Обновляем время в Lua процессах
-            {
-                let current_time = self.now().await;
-                let mut engine = self.lua_engine.lock().await;
-                engine.update_time(current_time.as_seconds());
+                let mut next = next + 1;
+                if next == schedule.shifts().len() {
+                    let Some(cycle) = schedule.cycle() else {
+                        return Ok(());
+                    };
+                    next = 0;
+                    cycle_start += cycle;
+                }
+
+                self.simulation.schedule_process_at(cycle_start + schedule.shifts()[next].0, Priority::Normal, id)?;
+                self.timers.insert(id, Timer::Capacity { resource, schedule, next, cycle_start });
             }
 
-            // Проверяем процессы, ожидающие времени
-            self.check_waiting_for_time().await;
+            Timer::Breakdown { resource, mut breakdown, broken } => {
+                let delay = if broken {
+                    self.repair_resource(&resource)?;
+                    let rng = self.streams.borrow_mut().stream(&format!("breakdown:{}", resource));
+                    let delay = breakdown.time_to_failure.sample(&mut rng.borrow_mut())?;
+                    delay
+                } else {
+                    self.break_resource(&resource, breakdown.policy)?;
+                    let rng = self.streams.borrow_mut().stream(&format!("breakdown:{}", resource));
+                    let delay = breakdown.repair_time.sample(&mut rng.borrow_mut())?;
+                    delay
+                };
 
-            // Запускаем готовые процессы
-            self.run_ready_processes().await?;
+                self.simulation.schedule_process_after(delay, Priority::Normal, id)?;
+                self.timers.insert(id, Timer::Breakdown { resource, breakdown, broken: !broken });
+            }
+        }
+        Ok(())
+    }
 
-            // Обрабатываем сообщения от Lua процессов (ВАЖНО: после run_ready_processes)
-            self.process_lua_messages().await?;
+    fn break_resource(&mut self, resource: &str, policy: FailurePolicy) -> Result<(), SimError> {
+        warn!("Ресурс {} сломался", resource);
 
-            // Проверяем ресурсы
-            self.check_waiting_processes().await;
+        let now = self.simulation.now();
+        let holders = self
+            .resources
+            .break_down(resource, policy, now)
+            .map_err(SimError::ResourceError)?;
+        let preempted = self.resources.take_preempted();
+        self.handle_preempted(preempted)?;
 
-            // Обрабатываем события
-            let sim = self.simulation.lock().await;
-            let has_events = sim.has_events().await;
-            drop(sim);
+        if policy == FailurePolicy::Delay {
+            // Текущая работа владельцев замирает до конца ремонта
+            let mut paused = Vec::new();
+            for holder in holders {
+                if let Some(remaining) = self.cancel_wake(&holder) {
+                    paused.push((holder, remaining));
+                }
+            }
+            self.paused_by_breakdown.entry(resource.to_string()).or_default().extend(paused);
+        }
+        Ok(())
+    }
 
-            if has_events {
-                let sim = self.simulation.lock().await;
-                sim.process_next_event().await?;
-            } else {
-                // Проверяем, есть ли активность
-                let ready = self.ready_queue.lock().await;
-                let has_ready = !ready.is_empty();
-                drop(ready);
+    fn repair_resource(&mut self, resource: &str) -> Result<(), SimError> {
+        info!("Ресурс {} отремонтирован", resource);
 
-                let waiting = self.waiting_for_time.lock().await;
-                let has_waiting = !waiting.is_empty();
-                drop(waiting);
+        let now = self.simulation.now();
+        let grants = self
+            .resources
+            .repair(resource, now)
+            .map_err(SimError::ResourceError)?;
 
-                let waiting_procs = self.waiting_processes.lock().await;
-                let has_waiting_procs = !waiting_procs.is_empty();
-                drop(waiting_procs);
+        for (process, remaining) in self.paused_by_breakdown.remove(resource).unwrap_or_default() {
+            self.wake_process(&process, remaining)?;
+        }
+        self.deliver_grants(grants)
+    }
+
+    /// Задать, как очередь ресурса обслуживает запросы на несколько единиц:
+    /// строго по порядку (по умолчанию) или с обгоном теми, кому единиц хватает
+    pub async fn set_batch_policy(&mut self, resource: &str, policy: BatchPolicy) -> Result<(), SimError> {
+        self.resources
+            .set_batch_policy(resource, policy)
+            .map_err(SimError::ResourceError)
+    }
+
+    /// Задать дисциплину очереди ресурса: FIFO, LIFO, случайный порядок,
+    /// кратчайшая работа первой по атрибуту запроса или свой ключ/компаратор
+    pub async fn set_queue_discipline(&mut self, resource: &str, discipline: QueueDiscipline) -> Result<(), SimError> {
+        self.resources
+            .set_queue_discipline(resource, discipline)
+            .map_err(SimError::ResourceError)
+    }
+
+    /// Упорядочить очередь ресурса ключом из Lua-функции `function` в `script`:
+    /// она получает таблицу {resource, process, priority, units, attrs, requested_at}
+    /// и возвращает число, меньший ключ обслуживается раньше
+    pub async fn set_queue_key_lua(&mut self, resource: &str, script: &str, function: &str) -> Result<(), SimError> {
+        let key = self.lua_function(script, function, &format!("queue:{}", resource))?;
+        let discipline = QueueDiscipline::Key(Box::new(move |waiting| {
+            let args = json!({
+                "resource": waiting.resource,
+                "process": waiting.process,
+                "priority": waiting.priority,
+                "units": waiting.units,
+                "attrs": waiting.attributes,
+                "requested_at": waiting.requested_at.as_seconds(),
+            });
+            key.call_f64(&args).unwrap_or_else(|e| {
+                error!("Ошибка в ключе очереди {}: {}", waiting.resource, e);
+                f64::INFINITY
+            })
+        }));
+        self.set_queue_discipline(resource, discipline).await
+    }
+
+    /// Пришедшие процессы не встают в очередь длиной `max` и больше
+    pub async fn set_max_queue_length(&mut self, resource: &str, max: usize) -> Result<(), SimError> {
+        self.set_balk_rule(resource, BalkRule::MaxQueue(max)).await
+    }
+
+    /// Задать правило отказа от очереди (balking) функцией на Rust
+    pub async fn set_balk_rule(&mut self, resource: &str, rule: BalkRule) -> Result<(), SimError> {
+        self.resources
+            .set_balk_rule(resource, rule)
+            .map_err(SimError::ResourceError)
+    }
+
+    /// Задать правило отказа от очереди функцией Lua из `script`.
+    /// Функция получает таблицу {resource, process, priority, queue_length,
+    /// available, capacity, now} и возвращает true, если процесс уходит
+    pub async fn set_balk_predicate(&mut self, resource: &str, script: &str, function: &str) -> Result<(), SimError> {
+        let predicate = self.lua_function(script, function, &format!("balk:{}", resource))?;
+        let rule = BalkRule::Predicate(Box::new(move |arrival| {
+            let args = json!({
+                "resource": arrival.resource,
+                "process": arrival.process,
+                "priority": arrival.priority,
+                "queue_length": arrival.queue_length,
+                "available": arrival.available,
+                "capacity": arrival.capacity,
+                "now": arrival.now.as_seconds(),
+            });
+            predicate.call_bool(&args).unwrap_or_else(|e| {
+                error!("Ошибка в правиле отказа для {}: {}", arrival.resource, e);
+                false
+            })
+        }));
+        self.set_balk_rule(resource, rule).await
+    }
 
-                // Если нет никакой активности, завершаем симуляцию
-                if !has_ready && !has_waiting && !has_waiting_procs {
+    /// Создать контейнер для непрерывного количества с начальным уровнем `level`
+    pub async fn create_container(&mut self, name: &str, capacity: f64, level: f64) -> Result<(), SimError> {
+        let now = self.simulation.now();
+        self.resources
+            .create_container(name, capacity, level, now)
+            .map_err(SimError::ResourceError)?;
+        debug!("Создан контейнер: {} (емкость: {}, уровень: {})", name, capacity, level);
+        Ok(())
+    }
+
+    /// Создать хранилище предметов с выдачей в порядке поступления;
+    /// `capacity = None` — без ограничения
+    pub async fn create_store(&mut self, name: &str, capacity: Option<usize>) -> Result<(), SimError> {
+        self.create_store_with_kind(name, StoreKind::Fifo, capacity)
+    }
+
+    /// Создать хранилище, из которого `get` может забирать предметы по фильтру
+    pub async fn create_filter_store(&mut self, name: &str, capacity: Option<usize>) -> Result<(), SimError> {
+        self.create_store_with_kind(name, StoreKind::Filter, capacity)
+    }
+
+    /// Создать хранилище, выдающее предметы по приоритету
+    pub async fn create_priority_store(&mut self, name: &str, capacity: Option<usize>) -> Result<(), SimError> {
+        self.create_store_with_kind(name, StoreKind::Priority, capacity)
+    }
+
+    fn create_store_with_kind(&mut self, name: &str, kind: StoreKind, capacity: Option<usize>) -> Result<(), SimError> {
+        let now = self.simulation.now();
+        self.resources
+            .create_store(name, kind, capacity, now)
+            .map_err(SimError::ResourceError)?;
+        debug!("Создано хранилище: {} ({:?}, емкость: {:?})", name, kind, capacity);
+        Ok(())
+    }
+
+    /// Прервать ожидание процесса извне, как `process.interrupt(cause)` в SimPy:
+    /// его `wait`/`request`/`get`/`join` завершатся прерыванием с причиной `cause`.
+    /// Уже завершившуюся операцию прерывание не отменяет: процесс получит её
+    /// результат, а прерывание — на следующем ожидании. Процесс, который ещё
+    /// не стартовал, завершается прерыванием, не начав работу
+    pub async fn interrupt(&mut self, process: &ProcessHandle, cause: &str) -> Result<(), SimError> {
+        self.interrupt_waiting(process.name(), cause)
+    }
+
+    pub async fn run(&mut self, duration: f64) -> Result<(), SimError> {
+        info!("Запуск симуляции на {} секунд", duration);
+
+        let start_time = self.simulation.now();
+        let end_time = start_time + Duration::from_seconds(duration)?;
+
+        // Основной цикл симуляции: одно событие за шаг.
+        // Процессы в очередях ресурсов событий не имеют: разбудить их может
+        // только release от активного процесса, поэтому пустая очередь
+        // событий означает конец симуляции
+        loop {
+            match self.simulation.peek_next_time() {
+                None => {
                     info!("Нет активных процессов, завершаем симуляцию");
                     break;
                 }
-
-                if !has_ready {
-                    // Продвигаем время к следующему событию ожидания
-                    let mut waiting = self.waiting_for_time.lock().await;
-                    if !waiting.is_empty() {
-                        // Сортируем по времени пробуждения
-                        waiting.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
-                        let next_time = waiting[0].1;
-                        drop(waiting);
-                        
-                        // Устанавливаем время симуляции
-                        let sim = self.simulation.lock().await;
-                        sim.set_time(SimTime::new(next_time)).await;
-                    } else {
-                        drop(waiting);
-                        tokio::task::yield_now().await;
-                    }
-                } else {
-                    tokio::task::yield_now().await;
+                Some(time) if time > end_time => {
+                    self.simulation.set_time(end_time);
+                    break;
                 }
+                Some(_) => {}
+            }
+
+            if let Some(id) = self.simulation.process_next_event()? {
+                self.resume_process(id)?;
             }
         }
 
-        info!("Симуляция завершена. Время: {}", self.now().await);
+        info!("Симуляция завершена. Время: {}", self.simulation.now());
         Ok(())
     }
 
-    async fn run_ready_processes(&self) -> Result<(), SimError> {
-        let mut ready = self.ready_queue.lock().await;
-        let process_names: Vec<String> = ready.drain(..).collect();
-        drop(ready);
+    /// Возобновить процесс и обработать сообщения, которые он отправил
+    fn resume_process(&mut self, id: ProcessId) -> Result<(), SimError> {
+        if let Some(timer) = self.timers.remove(&id) {
+            return self.fire_timer(id, timer);
+        }
 
-        let mut engine = self.lua_engine.lock().await;
+        let Some(name) = self.process_names.get(&id).cloned() else {
+            warn!("Пробуждение неизвестного процесса {}", id.id());
+            return Ok(());
+        };
+        self.pending_wakes.remove(&name);
 
-        for name in process_names.iter() {
-            if let Some(process) = engine.get_process_mut(name) {
-                match process.resume() {
-                    Ok(true) => {
-                        // Процесс завершен
-                        debug!("Процесс {} завершен", name);
-                    }
-                    Ok(false) => {
-                        // Процесс приостановлен (yield) - не добавляем обратно в ready_queue
-                        // Он будет добавлен позже, когда условие ожидания выполнится
-                        debug!("Процесс {} приостановлен", name);
-                    }
-                    Err(e) => {
-                        error!("Ошибка в процессе {}: {}", name, e);
-                    }
+        // Процесс так и не дождался ресурса: уходит из очереди
+        if let Some(resource) = self.reneging.remove(&name) {
+            if let Some(denial) = self.resources.renege(&resource, &name, self.simulation.now()) {
+                info!("Процесс {} ушел из очереди к {} по таймауту", name, resource);
+                self.send_command(&name, LuaCommand::RequestDenied(denial))?;
+                self.set_process_state(&name, ProcessState::Active);
+                self.deliver_unblocked()?;
+            }
+        }
+
+        if self.native_engine.contains(&name) {
+            return self.run_native_process(&name);
+        }
+
+        let now = self.simulation.now();
+        let mut ended = None;
+        self.lua_engine.update_time(now.as_seconds());
+        if let Some(result) = self.lua_engine.resume_process(&name) {
+            match result {
+                Ok(true) => {
+                    // Процесс завершен
+                    debug!("Процесс {} завершен", name);
+                    ended = Some(ProcessOutcome::Finished(self.lua_engine.take_result(&name)));
+                }
+                Ok(false) => {
+                    // Процесс приостановлен (yield) и ждёт своего события
+                    debug!("Процесс {} приостановлен", name);
+                }
+                Err(e) => {
+                    error!("Ошибка в процессе {}: {}", name, e);
+                    ended = Some(ProcessOutcome::Failed(e.to_string()));
                 }
             }
         }
 
+        // Сообщения обрабатываем сразу после шага процесса, чтобы
+        // запросы ресурсов вставали в очередь в порядке выполнения
+        let messages = self.lua_engine.take_messages(&name);
+        self.handle_messages(&name, messages)?;
+
+        if let Some(outcome) = ended {
+            self.release_holdings(&name)?;
+            self.finish_process(&name, outcome)?;
+        }
         Ok(())
     }
 
-    async fn check_waiting_for_time(&self) {
-        let current_time = self.now().await.as_seconds();
-        let mut waiting = self.waiting_for_time.lock().await;
-        let mut ready = self.ready_queue.lock().await;
-        let mut to_remove = Vec::new();
+    fn run_native_process(&mut self, name: &str) -> Result<(), SimError> {
+        self.native_engine.update_time(self.simulation.now());
 
-        for (i, (name, wake_time)) in waiting.iter().enumerate() {
-            if current_time >= *wake_time {
-                debug!("Процесс {} пробужден (время: {})", name, current_time);
-                ready.push(name.clone());
-                to_remove.push(i);
+        let mut ended = None;
+        if let Some(process) = self.native_engine.get_process_mut(name) {
+            match process.resume() {
+                Ok(true) => {
+                    debug!("Процесс {} завершен", name);
+                    ended = Some(ProcessOutcome::Finished(process.take_result()));
+                }
+                Ok(false) => debug!("Процесс {} приостановлен", name),
+                Err(e) => {
+                    error!("Ошибка в процессе {}: {}", name, e);
+                    ended = Some(ProcessOutcome::Failed(e.to_string()));
+                }
             }
         }
 
-        for i in to_remove.into_iter().rev() {
-            waiting.remove(i);
+        let messages = self.native_engine.take_messages(name);
+        self.handle_messages(name, messages)?;
+        if let Some(outcome) = ended {
+            self.release_holdings(name)?;
+            self.finish_process(name, outcome)?;
         }
+
+        Ok(())
     }
 
-    async fn now(&self) -> SimTime {
-        let sim = self.simulation.lock().await;
-        sim.now().await
+    /// Обработать сообщения, отправленные процессом за один шаг. Отложенное
+    /// прерывание срабатывает вместо операции, на которой процесс стал ждать
+    fn handle_messages(&mut self, name: &str, messages: Vec<ProcessMessage>) -> Result<(), SimError> {
+        for message in messages {
+            if message.awaits_reply() && self.deliver_deferred_interrupt(name)? {
+                break;
+            }
+            if !self.handle_message(name, message)? {
+                break;
+            }
+        }
+        Ok(())
     }
 
-    async fn process_lua_messages(&self) -> Result<(), SimError> {
-        let mut engine = self.lua_engine.lock().await;
-        let messages = engine.process_messages().await;
-        drop(engine);
+    /// Доставить процессу отложенное прерывание, если оно есть
+    fn deliver_deferred_interrupt(&mut self, name: &str) -> Result<bool, SimError> {
+        let Some(deferred) = self.deferred_interrupts.get_mut(name) else {
+            return Ok(false);
+        };
+        let Some(interrupt) = deferred.pop_front() else {
+            return Ok(false);
+        };
+        if deferred.is_empty() {
+            self.deferred_interrupts.remove(name);
+        }
 
-        for (process_name, message) in messages {
-            match message {
-                ProcessMessage::Wait(seconds) => {
-                    debug!("Процесс {} ждет {} сек", process_name, seconds);
+        info!("Процесс {} прерван: {}", name, interrupt);
+        self.send_command(name, LuaCommand::Interrupt(interrupt))?;
+        self.set_process_state(name, ProcessState::Active);
+        self.schedule_wake(name, Duration::ZERO, Priority::High)?;
+        Ok(true)
+    }
 
-                    let mut engine = self.lua_engine.lock().await;
-                    engine.set_process_waiting(&process_name, seconds);
-                    drop(engine);
+    /// Освободить ресурсы, которые процесс так и не вернул до завершения
+    fn release_holdings(&mut self, name: &str) -> Result<(), SimError> {
+        let (released, grants) = self.resources.release_all(name, self.simulation.now());
+        for (resource, units) in &released {
+            warn!("Процесс {} завершился, не освободив {} ({} ед.): ресурс освобождён автоматически",
+                  name, resource, units);
+        }
+        self.deliver_grants(grants)
+    }
 
-                    // Вычисляем время пробуждения
-                    let current_time = self.now().await.as_seconds();
-                    let wake_time = current_time + seconds;
+    /// Запомнить, чем закончился процесс, разбудить тех, кто его ждал,
+    /// и удалить сам процесс: долгим прогонам не нужны тысячи завершённых
+    fn finish_process(&mut self, name: &str, outcome: ProcessOutcome) -> Result<(), SimError> {
+        for joiner in self.joiners.remove(name).unwrap_or_default() {
+            self.deliver_outcome(&joiner, name, &outcome)?;
+        }
+        self.outcomes.insert(name.to_string(), outcome);
 
-                    // Добавляем в список ожидающих
-                    let mut waiting = self.waiting_for_time.lock().await;
-                    waiting.push((process_name.clone(), wake_time));
-                    
-                    debug!("Процесс {} будет пробужден в {}", process_name, wake_time);
-                }
+        // Завершившийся процесс не должен остаться ни в одной очереди
+        self.resources.withdraw(name, self.simulation.now());
+        self.deliver_unblocked()?;
+        self.cancel_wake(name);
+        self.reneging.remove(name);
+        self.suspended_work.remove(name);
+        self.deferred_interrupts.remove(name);
+        if let Some(id) = self.process_ids.remove(name) {
+            self.process_names.remove(&id);
+        }
+        self.lua_engine.remove_process(name);
+        self.native_engine.remove_process(name);
+        Ok(())
+    }
 
-                ProcessMessage::Request(resource) => {
-                    debug!("Процесс {} запрашивает ресурс {}", process_name, resource);
+    /// Вернуть результат процесса `process` ждавшему его `joiner`
+    fn deliver_outcome(&mut self, joiner: &str, process: &str, outcome: &ProcessOutcome) -> Result<(), SimError> {
+        let command = match outcome {
+            ProcessOutcome::Finished(values) => LuaCommand::Joined(values.clone()),
+            ProcessOutcome::Failed(error) => LuaCommand::Error(SimError::ProcessError(format!(
+                "Process '{}' failed: {}",
+                process, error
+            ))),
+        };
+        self.send_command(joiner, command)?;
+        self.set_process_state(joiner, ProcessState::Active);
+        self.wake_process(joiner, Duration::ZERO)
+    }
 
-                    let mut resources = self.resources.lock().await;
-                    if resources.request(&resource) {
-                        drop(resources);
+    /// Процесс стоит в `wait` и проснётся по таймеру
+    fn in_timed_wait(&self, name: &str) -> bool {
+        let state = if self.native_engine.contains(name) {
+            self.native_engine.process_state(name)
+        } else {
+            self.lua_engine.process_state(name)
+        };
+        matches!(state, Some(ProcessState::Waiting(_)))
+            && self.pending_wakes.contains_key(name)
+            && !self.has_pending_command(name)
+    }
+
+    /// Ждёт ли процесс доставки результата своей операции
+    fn has_pending_command(&self, name: &str) -> bool {
+        if self.native_engine.contains(name) {
+            self.native_engine.has_pending_command(name)
+        } else {
+            self.lua_engine.has_pending_command(name)
+        }
+    }
+
+    /// Завершился ли процесс (или его уже нет)
+    fn process_ended(&self, name: &str) -> bool {
+        let state = if self.native_engine.contains(name) {
+            self.native_engine.process_state(name)
+        } else {
+            self.lua_engine.process_state(name)
+        };
+        state.is_none_or(|state| *state == ProcessState::Finished)
+    }
+
+    fn set_process_state(&mut self, name: &str, state: ProcessState) {
+        if self.native_engine.contains(name) {
+            self.native_engine.set_process_state(name, state);
+        } else {
+            self.lua_engine.set_process_state(name, state);
+        }
+    }
+
+    fn send_command(&mut self, name: &str, command: LuaCommand) -> Result<(), SimError> {
+        if self.native_engine.contains(name) {
+            self.native_engine.send_command(name, command)
+        } else {
+            self.lua_engine.send_command(name, command)
+        }
+        .map_err(SimError::ProcessError)
+    }
+
+    /// Обработать сообщение процесса. `false` — процесс получит ошибку,
+    /// и остальные его сообщения обрабатывать не нужно
+    fn handle_message(&mut self, process_name: &str, message: ProcessMessage) -> Result<bool, SimError> {
+        match message {
+            ProcessMessage::Wait(duration) => {
+                debug!("Процесс {} ждет {}", process_name, duration);
+
+                self.set_process_state(process_name, ProcessState::Waiting(duration));
+                self.wake_process(process_name, duration)?;
+                
+                debug!("Процесс {} будет пробужден в {}", process_name, self.simulation.now() + duration);
+            }
+
+            ProcessMessage::Request(resource, options) => {
+                debug!("Процесс {} запрашивает ресурс {} ({:?})", process_name, resource, options);
+
+                let now = self.simulation.now();
+                if let Err(message) = self.resources.validate_request(&resource, &options) {
+                    self.fail_operation(process_name, SimError::ResourceError(message))?;
+                } else {
+                    match self.resources.request(&resource, process_name, &options, now) {
                         // Ресурс получен немедленно
-                        let mut engine = self.lua_engine.lock().await;
-                        engine.send_command(&process_name, LuaCommand::ResourceGranted(resource))
-                            .map_err(|e| SimError::ProcessError(e))?;
-                    } else {
-                        drop(resources);
-                        let mut engine = self.lua_engine.lock().await;
-                        engine.set_process_waiting_for_resource(&process_name, resource.clone());
-                        drop(engine);
-                        let mut waiting = self.waiting_processes.lock().await;
-                        waiting.push((process_name.clone(), resource.clone()));
-                        debug!("Процесс {} встал в очередь к {}", process_name, resource);
+                        Ok(Some(grant)) => self.grant_resource(grant)?,
+                        Ok(None) => {
+                            self.set_process_state(process_name, ProcessState::WaitingForResource(resource.clone()));
+                            debug!("Процесс {} встал в очередь к {}", process_name, resource);
+
+                            // Пробуждение по таймауту означает уход из очереди
+                            if let Some(timeout) = options.timeout {
+                                self.wake_process(process_name, timeout)?;
+                                self.reneging.insert(process_name.to_string(), resource.clone());
+                            }
+                        }
+                        Err(denial) => self.deny_request(denial)?,
                     }
                 }
 
-                ProcessMessage::Release(resource) => {
-                    debug!("Процесс {} освобождает ресурс {}", process_name, resource);
+                let preempted = self.resources.take_preempted();
+                self.handle_preempted(preempted)?;
+            }
+
+            ProcessMessage::Release(resource, units) => {
+                debug!("Процесс {} освобождает ресурс {}", process_name, resource);
 
-                    let mut resources = self.resources.lock().await;
-                    resources.release(&resource);
+                // Освободившиеся единицы передаются ожидающим в очереди
+                let now = self.simulation.now();
+                match self.resources.release(&resource, process_name, units, now) {
+                    Ok(grants) => self.deliver_grants(grants)?,
+                    Err(message) => {
+                        // Ошибка вернётся из ближайшего ожидания процесса, а то,
+                        // что он успел отправить после release, отбрасывается
+                        self.fail_operation(process_name, SimError::ResourceError(message))?;
+                        return Ok(false);
+                    }
                 }
+            }
 
-                ProcessMessage::Log(message, level) => {
-                    match level {
-                        LogLevel::Info => info!("[{}] {}", process_name, message),
-                        LogLevel::Warning => warn!("[{}] {}", process_name, message),
-                        LogLevel::Error => error!("[{}] {}", process_name, message),
-                        LogLevel::Debug => debug!("[{}] {}", process_name, message),
+            ProcessMessage::RequestJoint(resources, mode) => {
+                debug!("Процесс {} запрашивает ресурсы {:?} ({:?})", process_name, resources, mode);
+
+                let now = self.simulation.now();
+                match self.resources.request_joint(&resources, process_name, mode, now) {
+                    Ok(Some(joint)) => self.grant_joint(joint)?,
+                    Ok(None) => {
+                        self.set_process_state(process_name, ProcessState::WaitingForResource(resources.join(",")));
                     }
+                    Err(message) => self.fail_operation(process_name, SimError::ResourceError(message))?,
                 }
+            }
 
-                ProcessMessage::Finished => {
-                    info!("Процесс {} завершен", process_name);
+            ProcessMessage::Put(container, amount) => {
+                debug!("Процесс {} кладет {} в {}", process_name, amount, container);
+                let now = self.simulation.now();
+                let result = self.resources.put(&container, process_name, amount, now);
+                self.handle_container_result(process_name, &container, result)?;
+            }
+
+            ProcessMessage::Get(container, amount) => {
+                debug!("Процесс {} забирает {} из {}", process_name, amount, container);
+                let now = self.simulation.now();
+                let result = self.resources.get(&container, process_name, amount, now);
+                self.handle_container_result(process_name, &container, result)?;
+            }
+
+            ProcessMessage::PutItem(target, item, priority) => {
+                debug!("Процесс {} кладет предмет в {}", process_name, target);
+                let now = self.simulation.now();
+
+                // Из Lua put приходит одинаково для контейнеров и хранилищ
+                if self.resources.container(&target).is_some() {
+                    let result = match item.as_f64() {
+                        Some(amount) => self.resources.put(&target, process_name, amount, now),
+                        None => Err(format!("Container '{}': put expects a number, got {}", target, item)),
+                    };
+                    self.handle_container_result(process_name, &target, result)?;
+                    return Ok(true);
                 }
 
-                ProcessMessage::Spawn(name, func) => {
-                    info!("Процесс {} создает новый процесс {} (функция: {})", process_name, name, func);
-                    
-                    let mut engine = self.lua_engine.lock().await;
-                    match engine.spawn_process(name.clone(), &func) {
-                        Ok(()) => {
-                            // Обновляем время в новом процессе
-                            let current_time = self.now().await;
-                            engine.update_time(current_time.as_seconds());
-                            
-                            // Добавляем в ready_queue
-                            drop(engine);
-                            let mut ready = self.ready_queue.lock().await;
-                            ready.push(name.clone());
-                            
-                            info!("Процесс {} добавлен в ready_queue", name);
-                        }
-                        Err(e) => {
-                            error!("Не удалось создать процесс {}: {}", name, e);
-                        }
-                    }
+                let lua = &self.lua_engine;
+                let result = self.resources.put_item(&target, process_name, item, priority, now, &mut |process, filter, item| {
+                    item_matches(lua, process, filter, item)
+                });
+                self.handle_store_result(process_name, &target, result)?;
+            }
+
+            ProcessMessage::GetItem(store, filter) => {
+                debug!("Процесс {} забирает предмет из {}", process_name, store);
+                let now = self.simulation.now();
+                let lua = &self.lua_engine;
+                let result = self.resources.get_item(&store, process_name, filter, now, &mut |process, filter, item| {
+                    item_matches(lua, process, filter, item)
+                });
+                self.handle_store_result(process_name, &store, result)?;
+            }
+
+            ProcessMessage::Log(message, level) => {
+                match level {
+                    LogLevel::Info => info!("[{}] {}", process_name, message),
+                    LogLevel::Warning => warn!("[{}] {}", process_name, message),
+                    LogLevel::Error => error!("[{}] {}", process_name, message),
+                    LogLevel::Debug => debug!("[{}] {}", process_name, message),
+                }
+            }
+
+            ProcessMessage::Interrupt(process, cause) => {
+                debug!("Процесс {} прерывает {}: {}", process_name, process, cause);
+
+                let interrupted = if process == process_name {
+                    Err(SimError::ProcessError(format!("Process '{}' cannot interrupt itself", process)))
+                } else {
+                    self.interrupt_waiting(&process, &cause)
+                };
+                if let Err(e) = interrupted {
+                    self.fail_operation(process_name, e)?;
+                    return Ok(false);
                 }
             }
+
+            ProcessMessage::Join(process) => {
+                debug!("Процесс {} ждет завершения {}", process_name, process);
+
+                if let Some(outcome) = self.outcomes.get(&process).cloned() {
+                    self.deliver_outcome(process_name, &process, &outcome)?;
+                } else if process == process_name || !self.process_ids.contains_key(&process) {
+                    let error = if process == process_name {
+                        format!("Process '{}' cannot join itself", process)
+                    } else {
+                        format!("Process '{}' not found", process)
+                    };
+                    self.fail_operation(process_name, SimError::ProcessError(error))?;
+                    return Ok(false);
+                } else {
+                    self.joiners.entry(process.clone()).or_default().push(process_name.to_string());
+                    self.set_process_state(process_name, ProcessState::WaitingForProcess(process));
+                }
+            }
+
+            ProcessMessage::Finished => {
+                info!("Процесс {} завершен", process_name);
+            }
+
+            ProcessMessage::Spawn(name, func, args) => {
+                let name = name.unwrap_or_else(|| self.auto_name(&func));
+                info!("Процесс {} создает новый процесс {} (функция: {})", process_name, name, func);
+
+                let spawned = self.ensure_unique_name(&name)
+                    .and_then(|()| self.lua_engine.spawn_process(name.clone(), &func, args).map_err(SimError::ProcessError));
+                self.complete_spawn(process_name, &name, spawned)?;
+            }
+
+            ProcessMessage::SpawnNative(name) => {
+                info!("Процесс {} создает новый процесс {}", process_name, name);
+
+                // Порождённый процесс забирается из движка, даже если имя занято
+                let factory = self.native_engine.take_spawn(&name);
+                let spawned = self.ensure_unique_name(&name).and_then(|()| {
+                    let factory = factory.ok_or_else(|| {
+                        SimError::ProcessError(format!("Process '{}' was not spawned", name))
+                    })?;
+                    self.native_engine.insert_process(name.clone(), factory)
+                });
+                self.complete_spawn(process_name, &name, spawned)?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Сообщить процессу, создан ли порождённый им процесс `name`. Родитель
+    /// продолжает работу раньше, чем новый процесс стартует в тот же момент
+    fn complete_spawn(&mut self, parent: &str, name: &str, spawned: Result<(), SimError>) -> Result<(), SimError> {
+        if let Err(e) = spawned {
+            error!("Не удалось создать процесс {}: {}", name, e);
+            return self.fail_operation(parent, e);
         }
 
+        self.send_command(parent, LuaCommand::Spawned(name.to_string()))?;
+        self.set_process_state(parent, ProcessState::Active);
+        self.schedule_wake(parent, Duration::ZERO, Priority::High)?;
+        self.start_process(name)?;
+        info!("Процесс {} запланирован к запуску", name);
         Ok(())
     }
 
-    async fn check_waiting_processes(&self) {
-        let mut waiting = self.waiting_processes.lock().await;
-        let mut to_remove = Vec::new();
+    /// Выдать ресурс процессу: информация о выдаче вернётся из `request`,
+    /// а сам процесс возобновится событием в текущий момент времени
+    fn grant_resource(&mut self, grant: Grant) -> Result<(), SimError> {
+        debug!("Ресурс {} выдан процессу {}", grant.resource, grant.process);
 
-        for (i, (process_name, resource_name)) in waiting.iter().enumerate() {
-            let mut resources = self.resources.lock().await;
-            if resources.request(resource_name) {
-                debug!("Ресурс {} доступен для {}", resource_name, process_name);
-                to_remove.push(i);
+        let process_name = grant.process.clone();
 
-                let mut engine = self.lua_engine.lock().await;
-                let _ = engine.send_command(process_name, LuaCommand::ResourceGranted(resource_name.clone()));
-                drop(engine);
-                
-                // Добавляем процесс в ready_queue
-                let mut ready = self.ready_queue.lock().await;
-                ready.push(process_name.clone());
-            }
+        // Ресурс выдан раньше таймаута — уход из очереди отменяется
+        if self.reneging.remove(&process_name).is_some() {
+            self.cancel_wake(&process_name);
         }
 
-        for i in to_remove.into_iter().rev() {
-            waiting.remove(i);
+        // Вытесненный процесс, вернувшийся в очередь, дорабатывает свой wait
+        if let Some(remaining) = self.suspended_work.remove(&process_name) {
+            self.set_process_state(&process_name, ProcessState::Waiting(remaining));
+            return self.wake_process(&process_name, remaining);
         }
+
+        self.send_command(&process_name, LuaCommand::ResourceGranted(grant))?;
+        self.set_process_state(&process_name, ProcessState::Active);
+        self.wake_process(&process_name, Duration::ZERO)
     }
 
-    pub async fn get_stats(&self) -> serde_json::Value {
-        let resources = self.resources.lock().await;
-        let engine = self.lua_engine.lock().await;
+    /// Выбросить ошибку операции в самом процессе
+    fn fail_operation(&mut self, process_name: &str, error: SimError) -> Result<(), SimError> {
+        warn!("Процесс {}: {}", process_name, error);
+        if self.process_ended(process_name) {
+            return Ok(());
+        }
+        self.send_command(process_name, LuaCommand::Error(error))?;
+        self.wake_process(process_name, Duration::ZERO)
+    }
 
+    /// Разбудить процессы, получившие ресурсы по совместным запросам
+    fn deliver_joint_grants(&mut self) -> Result<(), SimError> {
+        for joint in self.resources.take_joint_grants() {
+            self.grant_joint(joint)?;
+        }
+        Ok(())
+    }
+
+    /// Выдать ресурсы тем, кого задерживал ушедший из очереди запрос,
+    /// и завершить операции с контейнерами, стоявшие за ним
+    fn deliver_unblocked(&mut self) -> Result<(), SimError> {
+        let grants = self.resources.take_unblocked();
+        self.deliver_grants(grants)?;
+        for op in self.resources.take_unblocked_ops() {
+            let name = op.process.clone();
+            self.send_command(&name, LuaCommand::ContainerDone(op))?;
+            self.set_process_state(&name, ProcessState::Active);
+            self.wake_process(&name, Duration::ZERO)?;
+        }
+        Ok(())
+    }
+
+    /// Передать освободившиеся единицы: сначала очередям ресурсов, затем
+    /// совместным запросам
+    fn deliver_grants(&mut self, grants: Vec<Grant>) -> Result<(), SimError> {
+        for grant in grants {
+            self.grant_resource(grant)?;
+        }
+        self.deliver_joint_grants()
+    }
+
+    fn grant_joint(&mut self, joint: JointGrant) -> Result<(), SimError> {
+        debug!("Процесс {} получил ресурсы {:?}", joint.process,
+               joint.grants.iter().map(|g| g.resource.as_str()).collect::<Vec<_>>());
+
+        let process_name = joint.process.clone();
+        self.send_command(&process_name, LuaCommand::JointGranted(joint))?;
+        self.set_process_state(&process_name, ProcessState::Active);
+        self.wake_process(&process_name, Duration::ZERO)
+    }
+
+    /// Сообщить процессу, что ресурс он не получит
+    fn deny_request(&mut self, denial: Denial) -> Result<(), SimError> {
+        info!("Процесс {} не встал в очередь к {}", denial.process, denial.resource);
+
+        let process_name = denial.process.clone();
+        self.send_command(&process_name, LuaCommand::RequestDenied(denial))?;
+        self.set_process_state(&process_name, ProcessState::Active);
+        self.wake_process(&process_name, Duration::ZERO)
+    }
+
+    fn handle_container_result(
+        &mut self,
+        process_name: &str,
+        container: &str,
+        result: Result<Vec<ContainerOp>, String>,
+    ) -> Result<(), SimError> {
+        let completed = result.map(|ops| {
+            ops.into_iter()
+                .map(|op| (op.process.clone(), LuaCommand::ContainerDone(op)))
+                .collect()
+        });
+        self.complete_operations(process_name, container, completed)
+    }
+
+    fn handle_store_result(
+        &mut self,
+        process_name: &str,
+        store: &str,
+        result: Result<Vec<StoreOp>, String>,
+    ) -> Result<(), SimError> {
+        let completed = result.map(|ops| {
+            ops.into_iter()
+                .map(|op| (op.process.clone(), LuaCommand::StoreDone(op)))
+                .collect()
+        });
+        self.complete_operations(process_name, store, completed)
+    }
+
+    /// Возобновить процессы, чьи операции с контейнером или хранилищем
+    /// выполнены. Если собственная операция процесса не выполнилась, он ждёт в очереди
+    fn complete_operations(
+        &mut self,
+        process_name: &str,
+        target: &str,
+        result: Result<Vec<(String, LuaCommand)>, String>,
+    ) -> Result<(), SimError> {
+        let completed = match result {
+            Ok(completed) => completed,
+            Err(message) => return self.fail_operation(process_name, SimError::ResourceError(message)),
+        };
+
+        if !completed.iter().any(|(name, _)| name == process_name) {
+            self.set_process_state(process_name, ProcessState::WaitingForResource(target.to_string()));
+        }
+
+        for (name, command) in completed {
+            self.send_command(&name, command)?;
+            self.set_process_state(&name, ProcessState::Active);
+            self.wake_process(&name, Duration::ZERO)?;
+        }
+        Ok(())
+    }
+
+    pub async fn get_stats(&self) -> serde_json::Value {
         json!({
-            "time": self.now().await.as_seconds(),
-            "active_processes": engine.active_processes().len(),
-            "resources": resources.get_stats(),
+            "time": self.simulation.now().as_seconds(),
+            "active_processes": self.lua_engine.active_processes().len()
+                + self.native_engine.active_processes().len(),
+            "pending_events": self.simulation.pending_events(),
+            "resources": self.resources.get_stats(self.simulation.now()),
+            "containers": self.resources.get_container_stats(self.simulation.now()),
+            "stores": self.resources.get_store_stats(self.simulation.now()),
         })
     }
 }
 
+/// Интервал из Lua-функции модели отказов
+fn lua_sampler(function: LuaFunction, resource: &str) -> DurationSampler {
+    let args = json!({ "resource": resource });
+    DurationSampler::Custom(Box::new(move |_| {
+        let seconds = function.call_f64(&args)?;
+        if seconds < 0.0 {
+            return Err(SimError::ResourceError(format!(
+                "breakdown interval for {} cannot be negative: {}",
+                args["resource"], seconds
+            )));
+        }
+        Duration::from_seconds(seconds)
+    }))
+}
+
+/// Подходит ли предмет под фильтр ожидающего процесса
+fn item_matches(lua: &LuaEngine, process: &str, filter: &ItemFilter, item: &Item) -> bool {
+    match filter {
+        ItemFilter::Native(filter) => filter(item),
+        ItemFilter::Lua(key) => lua.matches_filter(process, key, item),
+    }
+}
+
 impl Default for Simulator {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,130 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use simpy_rs::core::{Simulation, SharedSimulation, Distribution, Duration, SimTime, Priority};
+
+#[tokio::test]
+async fn test_basic_simulation() {
+    let sim = SharedSimulation::new();
+
+    // Планируем несколько событий
+    let results = std::sync::Arc::new(tokio::sync::Mutex::new(Vec::new()));
+
+    for i in 0..5 {
+        let results = results.clone();
+        sim.schedule_after(
+            Duration::from_seconds(i as f64).unwrap(),
+            Priority::Normal,
+            move || {
+                let mut results = results.try_lock().unwrap();
+                results.push(i);
+            }
+        ).await.unwrap();
+    }
+
+    sim.run_for(Duration::from_seconds(10.0).unwrap()).await.unwrap();
+
+    let final_results = results.lock().await;
+    assert_eq!(*final_results, vec![0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn test_cancel_and_reschedule() {
+    let mut sim = Simulation::new();
+    let results = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+
+    let mut handles = Vec::new();
+    for i in 0..3 {
+        let results = results.clone();
+        let handle = sim.schedule_after(
+            Duration::from_seconds(i as f64 + 1.0).unwrap(),
+            Priority::Normal,
+            move || {
+                results.lock().unwrap().push(i);
+            }
+        ).unwrap();
+        handles.push(handle);
+    }
+
+    // Событие 0 отменяем, событие 1 переносим после события 2
+    assert!(sim.cancel(handles[0]));
+    assert!(!sim.cancel(handles[0]));
+    sim.reschedule(handles[1], SimTime::from_seconds(5.0).unwrap()).unwrap();
+    assert!(sim.is_scheduled(handles[1]));
+
+    sim.run_until(SimTime::from_seconds(5.0).unwrap()).unwrap();
+
+    assert_eq!(*results.lock().unwrap(), vec![2, 1]);
+    assert_eq!(sim.now(), SimTime::from_seconds(5.0).unwrap());
+    assert!(sim.reschedule(handles[1], SimTime::from_seconds(6.0).unwrap()).is_err());
+}
+
+#[test]
+fn test_run_until_stops_at_end_time() {
+    let mut sim = Simulation::new();
+    let fired = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+    for delay in [1.0, 2.0, 8.0] {
+        let fired = fired.clone();
+        sim.schedule_after(Duration::from_seconds(delay).unwrap(), Priority::Normal, move || {
+            fired.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }).unwrap();
+    }
+
+    sim.run_for(Duration::from_seconds(5.0).unwrap()).unwrap();
+
+    assert_eq!(fired.load(std::sync::atomic::Ordering::SeqCst), 2);
+    assert_eq!(sim.now(), SimTime::from_seconds(5.0).unwrap());
+    assert_eq!(sim.pending_events(), 1);
+}
+
+#[test]
+fn test_time_is_exact_and_validated() {
+    let step = Duration::from_seconds(0.1).unwrap();
+    let mut time = SimTime::ZERO;
+    for _ in 0..10 {
+        time += step;
+    }
+    assert_eq!(time, SimTime::from_seconds(1.0).unwrap());
+
+    assert!(SimTime::from_seconds(-1.0).is_err());
+    assert!(SimTime::from_seconds(f64::NAN).is_err());
+    assert!(Duration::from_seconds(f64::INFINITY).is_err());
+
+    let mut sim = Simulation::new();
+    sim.run_for(step).unwrap();
+    assert!(sim.schedule_at(SimTime::ZERO, Priority::Normal, || {}).is_err());
+}
+
+#[test]
+fn test_distributions_have_expected_means_and_validate_parameters() {
+    let cases = [
+        (Distribution::exponential(4.0).unwrap(), 4.0),
+        (Distribution::normal(10.0, 2.0).unwrap(), 10.0),
+        (Distribution::log_normal(0.0, 0.5).unwrap(), (0.125f64).exp()),
+        (Distribution::uniform(2.0, 6.0).unwrap(), 4.0),
+        (Distribution::triangular(0.0, 3.0, 6.0).unwrap(), 3.0),
+        (Distribution::erlang(3, 6.0).unwrap(), 6.0),
+        (Distribution::gamma(0.5, 4.0).unwrap(), 2.0),
+        (Distribution::gamma(2.5, 2.0).unwrap(), 5.0),
+        (Distribution::weibull(1.0, 3.0).unwrap(), 3.0),
+        (Distribution::poisson(7.0).unwrap(), 7.0),
+        (Distribution::empirical(vec![1.0, 2.0, 6.0]).unwrap(), 3.0),
+        (Distribution::discrete(vec![0.0, 10.0], &[3.0, 1.0]).unwrap(), 2.5),
+    ];
+
+    let mut rng = StdRng::seed_from_u64(1);
+    for (distribution, expected) in cases {
+        let n = 50_000;
+        let mean = (0..n).map(|_| distribution.sample(&mut rng)).sum::<f64>() / n as f64;
+        assert!((mean - expected).abs() < expected * 0.03, "{:?}: среднее {}", distribution, mean);
+    }
+
+    assert!(Distribution::poisson(3.0).unwrap().sample(&mut rng).fract() == 0.0);
+    assert!(Distribution::exponential(0.0).is_err());
+    assert!(Distribution::normal(0.0, -1.0).is_err());
+    assert!(Distribution::triangular(0.0, 5.0, 4.0).is_err());
+    assert!(Distribution::erlang(0, 1.0).is_err());
+    assert!(Distribution::empirical(vec![]).is_err());
+    assert!(Distribution::discrete(vec![1.0, 2.0], &[1.0]).is_err());
+    assert!(Distribution::discrete(vec![1.0], &[0.0]).is_err());
+}
@@ -0,0 +1,595 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rand::Rng;
+use serde_json::json;
+use simpy_rs::prelude::*;
+
+#[tokio::test]
+async fn test_native_processes_share_resources_with_lua() {
+    let mut sim = Simulator::new();
+    sim.create_resource("кассир", 1).await;
+
+    let log = Rc::new(RefCell::new(Vec::new()));
+
+    let lua_client = r#"
+        function client()
+            request("кассир")
+            wait(4)
+            release("кассир")
+        end
+    "#;
+    sim.load_process("lua_client", lua_client, "client").await.unwrap();
+
+    let events = log.clone();
+    sim.spawn_process("rust_client", move |ctx| async move {
+        ctx.timeout(Duration::from_seconds(1.0)?).await?;
+        let grant = ctx.request("кассир").await?;
+        events.borrow_mut().push((ctx.now().as_seconds(), grant.wait_time().as_seconds()));
+
+        let child_events = events.clone();
+        ctx.spawn("child", move |ctx| async move {
+            ctx.timeout(Duration::from_seconds(0.5)?).await?;
+            child_events.borrow_mut().push((ctx.now().as_seconds(), 0.0));
+            Ok(())
+        }).await?;
+
+        ctx.timeout(Duration::from_seconds(2.0)?).await?;
+        ctx.release("кассир")?;
+        Ok(())
+    }).await.unwrap();
+
+    sim.run(100.0).await.unwrap();
+
+    assert_eq!(*log.borrow(), vec![(4.0, 3.0), (4.5, 0.0)]);
+    let stats = sim.get_stats().await;
+    assert_eq!(stats["time"], 6.0);
+    assert_eq!(stats["resources"][0]["available"], 1);
+}
+
+#[tokio::test]
+async fn test_many_waiting_processes_finish_deterministically() {
+    let mut sim = Simulator::new();
+    sim.create_resource("склад", 10).await;
+
+    let finished = Rc::new(RefCell::new(Vec::new()));
+    for i in 0..20_000u64 {
+        let finished = finished.clone();
+        sim.spawn_process(&format!("p{}", i), move |ctx| async move {
+            ctx.timeout(Duration::from_ticks(i % 100)).await?;
+            ctx.request("склад").await?;
+            ctx.timeout(Duration::from_ticks(1)).await?;
+            ctx.release("склад")?;
+            finished.borrow_mut().push(i);
+            Ok(())
+        }).await.unwrap();
+    }
+
+    sim.run(1_000.0).await.unwrap();
+
+    assert_eq!(finished.borrow().len(), 20_000);
+    // Все ждут склад, поэтому последний освобождает его ровно через 2000 тиков
+    assert_eq!(sim.get_stats().await["time"], SimTime::from_ticks(2_000).as_seconds());
+    assert_eq!(sim.get_stats().await["pending_events"], 0);
+}
+
+/// Числа из Lua- и Rust-процессов симуляции с master seed `seed`
+async fn random_draws(seed: u64) -> Vec<f64> {
+    let mut sim = Simulator::with_seed(seed);
+    assert_eq!(sim.seed(), seed);
+    sim.create_store("числа", None).await.unwrap();
+
+    let script = r#"
+        function roller()
+            put("числа", math.random())
+            put("числа", math.random(6))
+            put("числа", random_stream("спрос"):random(1, 100))
+            math.randomseed(7)
+            put("числа", math.random(1000))
+        end
+    "#;
+    sim.load_process("roller", script, "roller").await.unwrap();
+
+    let draws = Rc::new(RefCell::new(Vec::new()));
+    let out = draws.clone();
+    sim.spawn_process("учёт", move |ctx| async move {
+        for _ in 0..4 {
+            let item = ctx.get_item("числа").await?;
+            out.borrow_mut().push(item.as_f64().unwrap());
+        }
+        let own = ctx.rng().gen::<f64>();
+        let demand = ctx.stream("спрос").borrow_mut().gen_range(1..=100) as f64;
+        out.borrow_mut().extend([own, demand]);
+        Ok(())
+    }).await.unwrap();
+
+    sim.run(10.0).await.unwrap();
+    let draws = draws.borrow().clone();
+    draws
+}
+
+#[tokio::test]
+async fn test_master_seed_makes_random_streams_reproducible() {
+    let first = random_draws(42).await;
+    assert_eq!(first.len(), 6);
+    assert!((0.0..1.0).contains(&first[0]));
+    assert!((1.0..=6.0).contains(&first[1]));
+    assert_eq!(first, random_draws(42).await);
+    assert_ne!(first, random_draws(43).await);
+}
+
+#[tokio::test]
+async fn test_lua_dist_draws_from_process_stream() {
+    let mut sim = Simulator::with_seed(5);
+    sim.create_store("интервалы", None).await.unwrap();
+
+    let script = r#"
+        function arrivals()
+            local total = 0
+            for _ = 1, 2000 do
+                total = total + dist.exp(2)
+            end
+            put("интервалы", total / 2000)
+            put("интервалы", dist.discrete({1, 2, 3}, {0, 1, 0}))
+            local ok = pcall(dist.normal, 1, -1)
+            put("интервалы", ok and 1 or 0)
+        end
+    "#;
+    sim.load_process("arrivals", script, "arrivals").await.unwrap();
+
+    let values = Rc::new(RefCell::new(Vec::new()));
+    let out = values.clone();
+    sim.spawn_process("учёт", move |ctx| async move {
+        for _ in 0..3 {
+            let item = ctx.get_item("интервалы").await?;
+            out.borrow_mut().push(item.as_f64().unwrap());
+        }
+        let service = ctx.sample(&Distribution::uniform(1.0, 2.0)?);
+        out.borrow_mut().push(service);
+        Ok(())
+    }).await.unwrap();
+
+    sim.run(10.0).await.unwrap();
+
+    let values = values.borrow();
+    assert!((values[0] - 2.0).abs() < 0.2, "среднее {}", values[0]);
+    assert_eq!(values[1..3], [2.0, 0.0]);
+    assert!((1.0..2.0).contains(&values[3]));
+}
+
+#[tokio::test]
+async fn test_lua_processes_share_one_vm() {
+    let mut sim = Simulator::with_seed(1);
+    sim.create_store("итог", None).await.unwrap();
+
+    let script = r#"
+        stats = {served = 0, names = {}}
+
+        function customer()
+            wait(1)
+            stats.served = stats.served + 1
+            stats.names[_process_name] = true
+        end
+
+        function generator()
+            for i = 1, 20000 do
+                spawn("c" .. i, "customer")
+            end
+            wait(10)
+            put("итог", stats.served)
+            put("итог", stats.names["c20000"] and 1 or 0)
+        end
+    "#;
+    sim.load_process("generator", script, "generator").await.unwrap();
+
+    let results = Rc::new(RefCell::new(Vec::new()));
+    let out = results.clone();
+    sim.spawn_process("учёт", move |ctx| async move {
+        for _ in 0..2 {
+            let item = ctx.get_item("итог").await?;
+            out.borrow_mut().push(item.as_i64().unwrap());
+        }
+        Ok(())
+    }).await.unwrap();
+
+    sim.run(100.0).await.unwrap();
+
+    assert_eq!(*results.borrow(), vec![20000, 1]);
+    // Завершённые процессы удалены, но их имена и результаты остаются
+    assert_eq!(sim.get_stats().await["active_processes"], 0);
+    assert_eq!(sim.outcome(&ProcessHandle::new("c20000")), Some(&ProcessOutcome::Finished(Vec::new())));
+    assert!(sim.load_process("c1", script, "customer").await.is_err());
+}
+
+#[tokio::test]
+async fn test_spawn_and_load_process_pass_arguments() {
+    let mut sim = Simulator::new();
+    sim.create_store("журнал", None).await.unwrap();
+
+    let script = r#"
+        function customer(parent, kind, order)
+            put("журнал", {parent = parent, kind = kind, items = #order.items, total = order.total})
+        end
+
+        function generator(count, kind)
+            for i = 1, count do
+                wait(1)
+                spawn("c" .. i, "customer", _process_name, kind, {items = {"хлеб", "сыр"}, total = i * 10})
+            end
+        end
+    "#;
+    sim.load_process_with_args("generator", script, "generator", vec![json!(2), json!("vip")])
+        .await
+        .unwrap();
+
+    let entries = Rc::new(RefCell::new(Vec::new()));
+    let out = entries.clone();
+    sim.spawn_process("учёт", move |ctx| async move {
+        for _ in 0..2 {
+            let item = ctx.get_item("журнал").await?;
+            out.borrow_mut().push(item);
+        }
+        Ok(())
+    }).await.unwrap();
+
+    sim.run(10.0).await.unwrap();
+
+    assert_eq!(*entries.borrow(), vec![
+        json!({"parent": "generator", "kind": "vip", "items": 2, "total": 10}),
+        json!({"parent": "generator", "kind": "vip", "items": 2, "total": 20}),
+    ]);
+}
+
+#[tokio::test]
+async fn test_join_returns_results_of_spawned_processes() {
+    let mut sim = Simulator::new();
+    sim.create_store("итоги", None).await.unwrap();
+
+    let script = r#"
+        function worker(duration, label)
+            wait(duration)
+            return label, duration * 2
+        end
+
+        function broken()
+            wait(1)
+            error("сломался")
+        end
+
+        function parent()
+            local slow = spawn(nil, "worker", 5, "медленный")
+            local fast = spawn(nil, "worker", 2, "быстрый")
+            local failing = spawn("сбой", "broken")
+
+            local label, doubled = join(slow)
+            put("итоги", {who = slow, label = label, doubled = doubled, at = now()})
+
+            -- быстрый уже завершился: join возвращает результат сразу
+            label = wait_for(fast)
+            put("итоги", {who = fast, label = label, at = now()})
+
+            local ok, err = pcall(join, failing)
+            put("итоги", {ok = ok, failed = string.find(err, "сломался") ~= nil})
+
+            ok = pcall(join, "нет такого")
+            put("итоги", {ok = ok})
+            return "готово"
+        end
+    "#;
+    let parent = sim.load_process("parent", script, "parent").await.unwrap();
+
+    let entries = Rc::new(RefCell::new(Vec::new()));
+    let out = entries.clone();
+    sim.spawn_process("учёт", move |ctx| async move {
+        let child = ctx.spawn("помощник", |ctx| async move {
+            ctx.timeout(Duration::from_seconds(3.0)?).await?;
+            Ok(json!({"помог": true}))
+        }).await?;
+        let values = ctx.join(&child).await?;
+        out.borrow_mut().push(json!({"native": values, "at": ctx.now().as_seconds()}));
+
+        let results = ctx.join(&ProcessHandle::new("parent")).await?;
+        out.borrow_mut().push(json!({"parent": results}));
+        for _ in 0..4 {
+            let item = ctx.get_item("итоги").await?;
+            out.borrow_mut().push(item);
+        }
+        Ok(())
+    }).await.unwrap();
+
+    sim.run(100.0).await.unwrap();
+
+    assert_eq!(*entries.borrow(), vec![
+        json!({"native": [{"помог": true}], "at": 3.0}),
+        json!({"parent": ["готово"]}),
+        json!({"who": "worker#1", "label": "медленный", "doubled": 10, "at": 5.0}),
+        json!({"who": "worker#2", "label": "быстрый", "at": 5.0}),
+        json!({"ok": false, "failed": true}),
+        json!({"ok": false}),
+    ]);
+    assert_eq!(sim.outcome(&parent), Some(&ProcessOutcome::Finished(vec![json!("готово")])));
+    assert!(matches!(sim.outcome(&ProcessHandle::new("сбой")), Some(ProcessOutcome::Failed(_))));
+    assert_eq!(
+        sim.outcome(&ProcessHandle::new("помощник")),
+        Some(&ProcessOutcome::Finished(vec![json!({"помог": true})]))
+    );
+}
+
+#[tokio::test]
+async fn test_interrupt_does_not_discard_completed_operations() {
+    let mut sim = Simulator::new();
+    sim.create_resource("станок", 1).await;
+    sim.create_store("склад", None).await.unwrap();
+    sim.create_container("бак", 10.0, 0.0).await.unwrap();
+
+    // В момент 5 операции ожидающих завершаются, и в тот же момент приходит
+    // прерывание: результат операции процесс получает, а прерывание
+    // срабатывает на следующем ожидании
+    let script = r#"
+        function holder()
+            request("станок")
+            wait(5)
+            release("станок")
+        end
+
+        function producer(target, value)
+            wait(5)
+            put(target, value)
+        end
+
+        function alarm()
+            -- Пробуждение в момент 5 планируется позже, чем у holder и producer
+            wait(1)
+            wait(4)
+            interrupt("customer", "тревога")
+            interrupt("consumer", "тревога")
+            interrupt("filler", "тревога")
+            interrupt("рабочий", "тревога")
+        end
+
+        function customer()
+            local grant = request("станок")
+            local ok, e = pcall(wait, 10)
+            release("станок")
+            return grant.granted, e.cause, now()
+        end
+
+        function consumer()
+            local item = get("склад")
+            local ok, e = pcall(wait, 10)
+            return item, e.cause, now()
+        end
+
+        function filler()
+            local done = get("бак", 3)
+            local ok, e = pcall(wait, 10)
+            return done.amount, e.cause, now()
+        end
+    "#;
+    sim.load_process("holder", script, "holder").await.unwrap();
+    let deliveries = [("склад", json!("деталь")), ("склад", json!("заготовка")), ("бак", json!(3))];
+    for (i, (target, value)) in deliveries.into_iter().enumerate() {
+        let name = format!("producer{}", i);
+        sim.load_process_with_args(&name, script, "producer", vec![json!(target), value]).await.unwrap();
+    }
+    for name in ["alarm", "customer", "consumer", "filler"] {
+        sim.load_process(name, script, name).await.unwrap();
+    }
+    let worker = sim.spawn_process("рабочий", |ctx| async move {
+        let item = ctx.get_item("склад").await?;
+        match ctx.timeout(Duration::from_seconds(10.0)?).await {
+            Err(SimError::Interrupted(interrupt)) => {
+                assert_eq!(interrupt.cause, "тревога");
+                assert_eq!(interrupt.remaining, None);
+                Ok(item)
+            }
+            other => panic!("ожидалось прерывание, получено {:?}", other),
+        }
+    }).await.unwrap();
+
+    sim.run(100.0).await.unwrap();
+
+    let finished = |values: serde_json::Value| Some(ProcessOutcome::Finished(values.as_array().unwrap().clone()));
+    assert_eq!(sim.outcome(&ProcessHandle::new("customer")).cloned(), finished(json!([true, "тревога", 5.0])));
+    assert_eq!(sim.outcome(&ProcessHandle::new("consumer")).cloned(), finished(json!(["деталь", "тревога", 5.0])));
+    assert_eq!(sim.outcome(&ProcessHandle::new("filler")).cloned(), finished(json!([3.0, "тревога", 5.0])));
+    assert_eq!(sim.outcome(&worker).cloned(), finished(json!(["заготовка"])));
+
+    let stats = sim.get_stats().await;
+    assert_eq!(stats["resources"][0]["in_use"], 0);
+    assert_eq!(stats["time"], 5.0);
+}
+
+#[tokio::test]
+async fn test_interrupt_before_start_fails_process_at_once() {
+    let mut sim = Simulator::new();
+
+    let script = r#"
+        function child()
+            local ok = pcall(wait, 10)
+            return ok, now()
+        end
+
+        function parent()
+            local c = spawn(nil, "child")
+            interrupt(c, "отмена")
+            local ok, err = pcall(join, c)
+            return ok, string.find(err, "отмена") ~= nil, now()
+        end
+    "#;
+    let parent = sim.load_process("parent", script, "parent").await.unwrap();
+
+    let started = Rc::new(RefCell::new(false));
+    let flag = started.clone();
+    let worker = sim.spawn_process("рабочий", move |ctx| async move {
+        *flag.borrow_mut() = true;
+        ctx.timeout(Duration::from_seconds(10.0)?).await
+    }).await.unwrap();
+    sim.interrupt(&worker, "рано").await.unwrap();
+
+    sim.run(100.0).await.unwrap();
+
+    assert_eq!(
+        sim.outcome(&parent),
+        Some(&ProcessOutcome::Finished(vec![json!(false), json!(true), json!(0.0)]))
+    );
+    assert!(matches!(sim.outcome(&ProcessHandle::new("child#1")),
+                     Some(ProcessOutcome::Failed(error)) if error.contains("отмена")));
+    assert!(matches!(sim.outcome(&worker), Some(ProcessOutcome::Failed(error)) if error.contains("рано")));
+    assert!(!*started.borrow());
+    assert_eq!(sim.get_stats().await["time"], 0.0);
+}
+
+#[tokio::test]
+async fn test_lua_joins_values_returned_by_native_process() {
+    let mut sim = Simulator::new();
+
+    let counter = sim.spawn_process("счётчик", |ctx| async move {
+        ctx.timeout(Duration::from_seconds(2.0)?).await?;
+        Ok(vec![json!("готово"), json!(ctx.now().as_seconds())])
+    }).await.unwrap();
+
+    let script = r#"
+        function reader()
+            local status, at = join("счётчик")
+            return {status = status, at = at, now = now()}
+        end
+    "#;
+    let reader = sim.load_process("reader", script, "reader").await.unwrap();
+
+    sim.run(100.0).await.unwrap();
+
+    assert_eq!(
+        sim.outcome(&counter),
+        Some(&ProcessOutcome::Finished(vec![json!("готово"), json!(2.0)]))
+    );
+    assert_eq!(
+        sim.outcome(&reader),
+        Some(&ProcessOutcome::Finished(vec![json!({"status": "готово", "at": 2.0, "now": 2.0})]))
+    );
+}
+
+#[tokio::test]
+async fn test_spawn_reports_taken_names_and_skips_them_for_auto_names() {
+    let mut sim = Simulator::new();
+    sim.create_store("итоги", None).await.unwrap();
+
+    let script = r#"
+        function worker(label)
+            wait(1)
+            return label
+        end
+
+        function parent()
+            local first = spawn("w", "worker", "first")
+            local ok, err = pcall(spawn, "w", "worker", "second")
+            put("итоги", {ok = ok, taken = string.find(err, "already exists") ~= nil})
+
+            ok = pcall(spawn, nil, "нет такой функции")
+            put("итоги", {ok = ok})
+
+            -- Имя worker#1 занято вручную, автоматическое его пропускает
+            spawn("worker#1", "worker", "manual")
+            local auto = spawn(nil, "worker", "auto")
+            put("итоги", {auto = auto, label = join(auto), first = join(first)})
+        end
+    "#;
+    sim.load_process("parent", script, "parent").await.unwrap();
+
+    let entries = Rc::new(RefCell::new(Vec::new()));
+    let out = entries.clone();
+    sim.spawn_process("учёт", move |ctx| async move {
+        ctx.spawn("помощник", |_| async move { Ok(()) }).await?;
+        let duplicate = ctx.spawn("помощник", |_| async move { Ok(()) }).await;
+        out.borrow_mut().push(json!({"native_duplicate": duplicate.is_err()}));
+        for _ in 0..3 {
+            let item = ctx.get_item("итоги").await?;
+            out.borrow_mut().push(item);
+        }
+        Ok(())
+    }).await.unwrap();
+
+    sim.run(100.0).await.unwrap();
+
+    assert_eq!(*entries.borrow(), vec![
+        json!({"native_duplicate": true}),
+        json!({"ok": false, "taken": true}),
+        json!({"ok": false}),
+        json!({"auto": "worker#2", "label": "auto", "first": "first"}),
+    ]);
+}
+
+#[tokio::test]
+async fn test_interrupt_wakes_waiting_processes_early() {
+    let mut sim = Simulator::new();
+    sim.create_resource("станок", 1).await;
+    sim.create_store("журнал", None).await.unwrap();
+
+    let script = r#"
+        function sleeper()
+            local ok, e = pcall(wait, 10)
+            put("журнал", {who = "sleeper", cause = e.cause, at = e.time, remaining = e.remaining})
+        end
+
+        function holder()
+            request("станок")
+            wait(20)
+            release("станок")
+        end
+
+        function customer()
+            local ok, e = pcall(request, "станок")
+            put("журнал", {who = "customer", cause = e.cause, at = now()})
+        end
+
+        function alarm()
+            wait(3)
+            interrupt("sleeper", "пожар")
+            interrupt("customer", "отмена")
+            wait(1)
+            interrupt("sleeper", "поздно")
+            local ok, err = pcall(wait, 1)
+            put("журнал", {who = "alarm", ok = ok, finished = string.find(err, "already finished") ~= nil})
+        end
+    "#;
+    for name in ["sleeper", "holder", "customer", "alarm"] {
+        sim.load_process(name, script, name).await.unwrap();
+    }
+
+    let entries = Rc::new(RefCell::new(Vec::new()));
+    let out = entries.clone();
+    let worker = sim.spawn_process("рабочий", |ctx| async move {
+        match ctx.timeout(Duration::from_seconds(50.0)?).await {
+            Err(SimError::Interrupted(interrupt)) => {
+                assert_eq!(interrupt.cause, "перерыв");
+                assert_eq!(interrupt.remaining, Some(Duration::from_seconds(42.0)?));
+                Ok(())
+            }
+            other => panic!("ожидалось прерывание, получено {:?}", other),
+        }
+    }).await.unwrap();
+    sim.spawn_process("учёт", move |ctx| async move {
+        for _ in 0..3 {
+            let item = ctx.get_item("журнал").await?;
+            out.borrow_mut().push(item);
+        }
+        Ok(())
+    }).await.unwrap();
+
+    assert!(sim.interrupt(&ProcessHandle::new("нет такого"), "тест").await.is_err());
+    sim.run(8.0).await.unwrap();
+    sim.interrupt(&worker, "перерыв").await.unwrap();
+    sim.run(100.0).await.unwrap();
+
+    assert_eq!(*entries.borrow(), vec![
+        json!({"who": "sleeper", "cause": "пожар", "at": 3.0, "remaining": 7.0}),
+        json!({"who": "customer", "cause": "отмена", "at": 3.0}),
+        json!({"who": "alarm", "ok": false, "finished": true}),
+    ]);
+    assert_eq!(sim.outcome(&worker), Some(&ProcessOutcome::Finished(Vec::new())));
+    assert!(sim.interrupt(&worker, "ещё раз").await.is_err());
+
+    let stats = sim.get_stats().await;
+    assert_eq!(stats["resources"][0]["queue_length"], 0);
+    assert_eq!(stats["time"], 20.0);
+}
@@ -0,0 +1,1549 @@
+use simpy_rs::Simulator;
+use simpy_rs::SimError;
+use simpy_rs::core::{Duration, SimTime};
+use simpy_rs::process::{ProcessHandle, ProcessOutcome};
+use simpy_rs::resources::{
+    BatchPolicy, Breakdown, CapacityDropPolicy, CapacitySchedule, DenialReason, DurationSampler, FailurePolicy,
+    QueueDiscipline, RequestOptions, Unit,
+};
+
+#[tokio::test]
+async fn test_request_blocks_until_granted() {
+    let mut sim = Simulator::new();
+    sim.create_resource("кассир", 1).await;
+
+    let script = r#"
+        function client()
+            local grant = request("кассир")
+            assert(grant.resource == "кассир")
+            wait(5)
+            release("кассир")
+        end
+    "#;
+
+    sim.load_process("client1", script, "client").await.unwrap();
+    sim.load_process("client2", script, "client").await.unwrap();
+    sim.run(20.0).await.unwrap();
+
+    let stats = sim.get_stats().await;
+    let cashier = &stats["resources"][0];
+    assert_eq!(stats["time"], 10.0);
+    assert_eq!(cashier["total_requests"], 2);
+    assert_eq!(cashier["total_wait_time"], 5.0);
+    assert_eq!(cashier["available"], 1);
+}
+
+#[tokio::test]
+async fn test_resource_time_weighted_stats_and_tallies() {
+    let mut sim = Simulator::new();
+    sim.create_resource("кассир", 1).await;
+
+    let script = r#"
+        function client()
+            request("кассир")
+            wait(5)
+            release("кассир")
+        end
+    "#;
+    for name in ["c1", "c2", "c3"] {
+        sim.load_process(name, script, "client").await.unwrap();
+    }
+    sim.run(15.0).await.unwrap();
+
+    let stats = sim.get_stats().await;
+    let cashier = &stats["resources"][0];
+    assert_eq!(cashier["mean_utilization"], 1.0);
+    // Очередь: 2 на [0, 5), 1 на [5, 10), 0 на [10, 15)
+    assert_eq!(cashier["mean_queue_length"], 1.0);
+    assert_eq!(cashier["max_queue_length"], 2.0);
+
+    // Ожидания 0, 5, 10
+    let wait = &cashier["wait_time"];
+    assert_eq!(wait["count"], 3);
+    assert_eq!(wait["mean"], 5.0);
+    assert_eq!(wait["variance"], 25.0);
+    assert_eq!(wait["p50"], 5.0);
+    assert_eq!(wait["p90"], 9.0);
+    assert_eq!(wait["max"], 10.0);
+    assert_eq!(cashier["hold_time"]["mean"], 5.0);
+}
+
+#[test]
+fn test_tally_estimates_percentiles_without_storing_samples() {
+    let mut tally = simpy_rs::resources::Tally::new();
+    // 0..100000 вперемешку: 7919 взаимно просто с 100000
+    for i in 0..100_000u64 {
+        tally.record(((i * 7919) % 100_000) as f64);
+    }
+
+    assert_eq!(tally.count(), 100_000);
+    assert_eq!(tally.min(), Some(0.0));
+    assert_eq!(tally.max(), Some(99_999.0));
+    assert!((tally.mean() - 49_999.5).abs() < 1e-6);
+    for p in [50.0, 90.0, 95.0, 99.0] {
+        let estimate = tally.percentile(p).unwrap();
+        let exact = p / 100.0 * 99_999.0;
+        assert!((estimate - exact).abs() < 1_000.0, "p{}: {} vs {}", p, estimate, exact);
+    }
+    // Вне отслеживаемых перцентилей оценки нет
+    assert_eq!(tally.percentile(75.0), None);
+}
+
+#[tokio::test]
+async fn test_priority_resource_serves_important_requests_first() {
+    let mut sim = Simulator::new();
+    sim.create_priority_resource("врач", 1).await;
+
+    let script = r#"
+        function holder()
+            request("врач")
+            wait(10)
+            release("врач")
+        end
+
+        -- Приходит раньше, но с низким приоритетом и коротким приёмом
+        function routine()
+            wait(1)
+            request("врач", {priority = 2})
+            wait(1)
+            release("врач")
+        end
+
+        function critical()
+            wait(2)
+            request("врач", {priority = 1})
+            wait(5)
+            release("врач")
+        end
+    "#;
+
+    sim.load_process("holder", script, "holder").await.unwrap();
+    sim.load_process("routine", script, "routine").await.unwrap();
+    sim.load_process("critical", script, "critical").await.unwrap();
+    sim.run(100.0).await.unwrap();
+
+    // critical: 2 -> 10 (8), routine: 1 -> 15 (14); в порядке FIFO было бы 9 + 9
+    let stats = sim.get_stats().await;
+    assert_eq!(stats["resources"][0]["total_wait_time"], 22.0);
+    assert_eq!(stats["time"], 16.0);
+}
+
+#[tokio::test]
+async fn test_preemptive_resource_interrupts_holder() {
+    let mut sim = Simulator::new();
+    sim.create_preemptive_resource("станок", 1).await;
+
+    let caught = std::rc::Rc::new(std::cell::RefCell::new(None));
+    let result = caught.clone();
+    sim.spawn_process("ремонт", move |ctx| async move {
+        ctx.request_with("станок", RequestOptions::with_priority(5)).await?;
+        match ctx.timeout(Duration::from_seconds(10.0)?).await {
+            Err(SimError::Interrupted(interrupt)) => *result.borrow_mut() = Some(interrupt),
+            other => other?,
+        }
+        // Ресурс уже отобран, release ничего не делает
+        ctx.release("станок")
+    }).await.unwrap();
+
+    let urgent = r#"
+        function urgent()
+            wait(3)
+            request("станок", {priority = 1})
+            wait(2)
+            release("станок")
+        end
+    "#;
+    sim.load_process("срочный", urgent, "urgent").await.unwrap();
+    sim.run(100.0).await.unwrap();
+
+    let interrupt = caught.borrow_mut().take().expect("holder was not interrupted");
+    let preemption = interrupt.preemption.unwrap();
+    assert_eq!(interrupt.interrupted_at, SimTime::from_seconds(3.0).unwrap());
+    assert_eq!(interrupt.remaining, Some(Duration::from_seconds(7.0).unwrap()));
+    assert_eq!(preemption.by, "срочный");
+    assert_eq!(preemption.usage_since, SimTime::ZERO);
+
+    let stats = sim.get_stats().await;
+    assert_eq!(stats["resources"][0]["total_preemptions"], 1);
+    assert_eq!(stats["resources"][0]["available"], 1);
+}
+
+#[tokio::test]
+async fn test_preempted_lua_process_requeues_and_finishes_work() {
+    let mut sim = Simulator::new();
+    sim.create_preemptive_resource("станок", 1).await;
+
+    let script = r#"
+        function job()
+            request("станок", {priority = 5, requeue = true})
+            wait(10)
+            release("станок")
+        end
+
+        function urgent()
+            wait(3)
+            request("станок", {priority = 1})
+            wait(5)
+            release("станок")
+        end
+    "#;
+    sim.load_process("job", script, "job").await.unwrap();
+    sim.load_process("urgent", script, "urgent").await.unwrap();
+    sim.run(100.0).await.unwrap();
+
+    // job: 0..3, вытеснен на 3..8, дорабатывает оставшиеся 7 секунд
+    let stats = sim.get_stats().await;
+    let machine = &stats["resources"][0];
+    assert_eq!(stats["time"], 15.0);
+    assert_eq!(machine["available"], 1);
+    // Повторная выдача job — не новый запрос, а ждал он с момента вытеснения
+    assert_eq!(machine["total_requests"], 2);
+    assert_eq!(machine["total_wait_time"], 5.0);
+    assert_eq!(machine["wait_time"]["max"], 5.0);
+}
+
+#[tokio::test]
+async fn test_requeued_holder_blocked_elsewhere_is_interrupted() {
+    let mut sim = Simulator::new();
+    sim.create_preemptive_resource("станок", 1).await;
+    sim.create_resource("оператор", 1).await;
+
+    // worker вытеснен, пока стоит в очереди к оператору: повторная выдача
+    // станка не должна прийти в его request("оператор")
+    let script = r#"
+        function busy()
+            request("оператор")
+            wait(20)
+            release("оператор")
+        end
+
+        function worker()
+            request("станок", {priority = 5, requeue = true})
+            local ok, e = pcall(request, "оператор")
+            return ok, e.resource, e.by, now()
+        end
+
+        function vip()
+            wait(5)
+            request("станок", {priority = 1})
+            wait(2)
+            release("станок")
+        end
+    "#;
+    for name in ["busy", "worker", "vip"] {
+        sim.load_process(name, script, name).await.unwrap();
+    }
+    sim.run(100.0).await.unwrap();
+
+    assert_eq!(
+        sim.outcome(&ProcessHandle::new("worker")),
+        Some(&ProcessOutcome::Finished(vec![
+            serde_json::json!(false),
+            serde_json::json!("станок"),
+            serde_json::json!("vip"),
+            serde_json::json!(5.0),
+        ]))
+    );
+    let stats = sim.get_stats().await;
+    assert_eq!(stats["time"], 20.0);
+    for resource in stats["resources"].as_array().unwrap() {
+        assert_eq!(resource["available"], 1);
+        assert_eq!(resource["queue_length"], 0);
+    }
+}
+
+#[tokio::test]
+async fn test_interrupting_blocked_container_head_serves_ops_behind_it() {
+    let mut sim = Simulator::new();
+    sim.create_container("бак", 100.0, 5.0).await.unwrap();
+
+    let script = r#"
+        function big()
+            pcall(get, "бак", 10)
+        end
+
+        function small()
+            wait(1)
+            local op = get("бак", 1)
+            return op.completed_at, op.level
+        end
+
+        function alarm()
+            wait(2)
+            interrupt("big", "отмена")
+        end
+    "#;
+    sim.load_process("big", script, "big").await.unwrap();
+    let small = sim.load_process("small", script, "small").await.unwrap();
+    sim.load_process("alarm", script, "alarm").await.unwrap();
+    sim.run(100.0).await.unwrap();
+
+    assert_eq!(
+        sim.outcome(&small),
+        Some(&ProcessOutcome::Finished(vec![serde_json::json!(2.0), serde_json::json!(4.0)]))
+    );
+    let stats = sim.get_stats().await;
+    assert_eq!(stats["containers"][0]["get_queue_length"], 0);
+    assert_eq!(stats["containers"][0]["level"], 4.0);
+}
+
+#[tokio::test]
+async fn test_interrupt_reports_work_left_by_requeued_and_paused_holders() {
+    let mut sim = Simulator::new();
+    sim.create_preemptive_resource("станок", 1).await;
+    sim.create_resource("пресс", 1).await;
+    // Пресс ломается в момент 2 и стоит в ремонте до 7
+    let fixed = |seconds: f64| DurationSampler::Fixed(Duration::from_seconds(seconds).unwrap());
+    sim.set_breakdown("пресс", Breakdown::new(fixed(2.0), fixed(5.0)).with_policy(FailurePolicy::Delay))
+        .await
+        .unwrap();
+
+    let script = r#"
+        function job(resource)
+            request(resource, {priority = 5, requeue = true})
+            local ok, e = pcall(wait, 10)
+            release(resource)
+            return e.remaining, now()
+        end
+
+        function urgent()
+            wait(3)
+            request("станок", {priority = 1})
+            wait(5)
+            release("станок")
+        end
+
+        function alarm()
+            wait(4)
+            interrupt("job", "отмена")
+            interrupt("press", "отмена")
+        end
+    "#;
+    let job = sim.load_process_with_args("job", script, "job", vec![serde_json::json!("станок")]).await.unwrap();
+    let press = sim.load_process_with_args("press", script, "job", vec![serde_json::json!("пресс")]).await.unwrap();
+    sim.load_process("urgent", script, "urgent").await.unwrap();
+    sim.load_process("alarm", script, "alarm").await.unwrap();
+    sim.run(100.0).await.unwrap();
+
+    // job вытеснен в 3 с остатком 7; press приостановлен поломкой в 2 с остатком 8
+    assert_eq!(
+        sim.outcome(&job),
+        Some(&ProcessOutcome::Finished(vec![serde_json::json!(7.0), serde_json::json!(4.0)]))
+    );
+    assert_eq!(
+        sim.outcome(&press),
+        Some(&ProcessOutcome::Finished(vec![serde_json::json!(8.0), serde_json::json!(4.0)]))
+    );
+}
+
+#[tokio::test]
+async fn test_container_get_blocks_until_level_is_sufficient() {
+    let mut sim = Simulator::new();
+    sim.create_container("бак", 100.0, 0.0).await.unwrap();
+
+    // Заправщик на Rust доливает по 20 каждые 10 секунд
+    sim.spawn_process("заправщик", |ctx| async move {
+        for _ in 0..2 {
+            ctx.timeout(Duration::from_seconds(10.0)?).await?;
+            ctx.put("бак", 20.0).await?;
+        }
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    let script = r#"
+        function consumer()
+            local op = get("бак", 30)
+            assert(op.wait_time == 20, "wait_time = " .. op.wait_time)
+            assert(op.level == 10)
+
+            local ok, err = pcall(get, "бак", 1000)
+            assert(not ok and string.find(err, "amount"), tostring(err))
+        end
+    "#;
+    sim.load_process("потребитель", script, "consumer").await.unwrap();
+    sim.run(25.0).await.unwrap();
+
+    let stats = sim.get_stats().await;
+    let tank = &stats["containers"][0];
+    assert_eq!(tank["level"], 10.0);
+    assert_eq!(tank["total_put"], 40.0);
+    assert_eq!(tank["total_get"], 30.0);
+    assert_eq!(tank["get_queue_length"], 0);
+    // 0 на [0, 10), 20 на [10, 20), 10 на [20, 25)
+    assert_eq!(tank["mean_level"], 10.0);
+    assert_eq!(tank["max_level"], 20.0);
+}
+
+#[tokio::test]
+async fn test_container_rejects_invalid_levels() {
+    let mut sim = Simulator::new();
+    assert!(matches!(
+        sim.create_container("бак", 10.0, 20.0).await,
+        Err(SimError::ResourceError(_))
+    ));
+    assert!(sim.create_container("бак", 0.0, 0.0).await.is_err());
+}
+
+#[tokio::test]
+async fn test_store_passes_tables_and_blocks_on_capacity() {
+    let mut sim = Simulator::new();
+    sim.create_store("склад", Some(1)).await.unwrap();
+    sim.create_store("принято", None).await.unwrap();
+
+    let script = r#"
+        function producer()
+            for i = 1, 3 do
+                put("склад", {id = i, parts = {"корпус", "крышка"}})
+                log("деталь " .. i .. " на складе в " .. now())
+            end
+            assert(now() == 10, "второй put ждал до " .. now())
+        end
+
+        function consumer()
+            for i = 1, 3 do
+                wait(5)
+                local part = get("склад")
+                assert(part.id == i and part.parts[2] == "крышка")
+                put("принято", part.id)
+            end
+        end
+    "#;
+    sim.load_process("producer", script, "producer").await.unwrap();
+    sim.load_process("consumer", script, "consumer").await.unwrap();
+    sim.run(100.0).await.unwrap();
+
+    let stats = sim.get_stats().await;
+    let store = stats["stores"].as_array().unwrap().iter().find(|s| s["name"] == "склад").unwrap();
+    let accepted = stats["stores"].as_array().unwrap().iter().find(|s| s["name"] == "принято").unwrap();
+    assert_eq!(stats["time"], 15.0);
+    assert_eq!(store["total_put"], 3);
+    assert_eq!(store["total_get"], 3);
+    assert_eq!(store["items"], 0);
+    // Проверки внутри consumer прошли для всех трёх деталей
+    assert_eq!(accepted["items"], 3);
+}
+
+#[tokio::test]
+async fn test_filter_and_priority_stores() {
+    let mut sim = Simulator::new();
+    sim.create_filter_store("заказы", None).await.unwrap();
+    sim.create_priority_store("срочность", None).await.unwrap();
+    sim.create_store("журнал", None).await.unwrap();
+
+    let script = r#"
+        function urgent_worker()
+            local order = get("заказы", function(o) return o.urgent end)
+            put("журнал", {id = order.id, at = now()})
+        end
+
+        function dispatcher()
+            for id = 1, 3 do
+                wait(1)
+                put("заказы", {id = id, urgent = id == 3})
+            end
+            put("срочность", "плановый", 5)
+            put("срочность", "аварийный", 1)
+        end
+    "#;
+    sim.load_process("urgent", script, "urgent_worker").await.unwrap();
+    sim.load_process("dispatcher", script, "dispatcher").await.unwrap();
+
+    let received = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let log = received.clone();
+    sim.spawn_process("regular", move |ctx| async move {
+        for _ in 0..2 {
+            let order = ctx.get_item_filtered("заказы", |o| o["urgent"] == false).await?;
+            log.borrow_mut().push(order["id"].clone());
+        }
+        ctx.timeout(Duration::from_seconds(10.0)?).await?;
+        let first = ctx.get_item("срочность").await?;
+        log.borrow_mut().push(first);
+        let entry = ctx.get_item("журнал").await?;
+        log.borrow_mut().push(entry);
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    sim.run(100.0).await.unwrap();
+
+    assert_eq!(*received.borrow(), vec![
+        serde_json::json!(1),
+        serde_json::json!(2),
+        serde_json::json!("аварийный"),
+        serde_json::json!({"id": 3, "at": 3.0}),
+    ]);
+}
+
+#[tokio::test]
+async fn test_sparse_and_mixed_tables_become_objects() {
+    let mut sim = Simulator::new();
+    sim.create_store("склад", None).await.unwrap();
+
+    let script = r#"
+        function producer()
+            local holes = {1, 2, 3}
+            holes[2] = nil
+            put("склад", holes)
+
+            local sparse = {1, 2, 3, 4}
+            sparse[2] = nil
+            sparse[10] = 1
+            put("склад", sparse)
+
+            local mixed = {1, 2, 3, 4}
+            mixed[2] = nil
+            mixed.x = "kept"
+            put("склад", mixed)
+
+            put("склад", {[0] = "zero", "one"})
+            put("склад", {[-1] = "minus", "one"})
+        end
+    "#;
+    sim.load_process("producer", script, "producer").await.unwrap();
+
+    let received = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let log = received.clone();
+    sim.spawn_process("consumer", move |ctx| async move {
+        for _ in 0..5 {
+            let item = ctx.get_item("склад").await?;
+            log.borrow_mut().push(item);
+        }
+        Ok(())
+    })
+    .await
+    .unwrap();
+    sim.run(10.0).await.unwrap();
+
+    assert_eq!(*received.borrow(), vec![
+        serde_json::json!([1, null, 3]),
+        serde_json::json!({"1": 1, "3": 3, "4": 4, "10": 1}),
+        serde_json::json!({"1": 1, "3": 3, "4": 4, "x": "kept"}),
+        serde_json::json!({"0": "zero", "1": "one"}),
+        serde_json::json!({"-1": "minus", "1": "one"}),
+    ]);
+}
+
+#[tokio::test]
+async fn test_reneging_and_balking_by_queue_length() {
+    let mut sim = Simulator::new();
+    sim.create_resource("кассир", 1).await;
+    sim.set_max_queue_length("кассир", 2).await.unwrap();
+    sim.create_store("итоги", None).await.unwrap();
+
+    let script = r#"
+        function holder()
+            request("кассир")
+            wait(10)
+            release("кассир")
+        end
+
+        function impatient()
+            wait(1)
+            local r = request("кассир", {timeout = 3})
+            assert(not r.granted and r.reason == "timeout" and now() == 4)
+            put("итоги", r.wait_time)
+        end
+
+        function patient()
+            wait(2)
+            local r = request("кассир")
+            assert(r.granted and now() == 10)
+            put("итоги", "обслужен")
+            release("кассир")
+        end
+
+        function balker()
+            wait(3)
+            local r = request("кассир")
+            assert(not r.granted and r.reason == "balked" and now() == 3)
+            put("итоги", "отказ")
+        end
+    "#;
+    for name in ["holder", "impatient", "patient", "balker"] {
+        sim.load_process(name, script, name).await.unwrap();
+    }
+    sim.run(100.0).await.unwrap();
+
+    let stats = sim.get_stats().await;
+    let cashier = &stats["resources"][0];
+    assert_eq!(cashier["total_reneged"], 1);
+    assert_eq!(cashier["total_balked"], 1);
+    assert_eq!(cashier["total_requests"], 2);
+    assert_eq!(cashier["queue_length"], 0);
+    // Все проверки внутри процессов прошли
+    assert_eq!(stats["stores"][0]["items"], 3);
+}
+
+#[tokio::test]
+async fn test_balking_lua_predicate_and_rust_try_request() {
+    let mut sim = Simulator::new();
+    sim.create_resource("врач", 1).await;
+    sim.set_balk_predicate(
+        "врач",
+        "function picky(a) return a.priority > 1 and a.available == 0 end",
+        "picky",
+    )
+    .await
+    .unwrap();
+
+    let outcomes = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    sim.spawn_process("пациент", |ctx| async move {
+        ctx.request("врач").await?;
+        ctx.timeout(Duration::from_seconds(10.0)?).await?;
+        ctx.release("врач")
+    })
+    .await
+    .unwrap();
+
+    let log = outcomes.clone();
+    sim.spawn_process("посетитель", move |ctx| async move {
+        let outcome = ctx.try_request("врач", RequestOptions::with_priority(5)).await?;
+        log.borrow_mut().push(outcome.map(|_| ()).map_err(|d| (d.reason, d.wait_time())));
+
+        let options = RequestOptions { timeout: Some(Duration::from_seconds(4.0)?), ..Default::default() };
+        let outcome = ctx.try_request("врач", options).await?;
+        log.borrow_mut().push(outcome.map(|_| ()).map_err(|d| (d.reason, d.wait_time())));
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    sim.run(100.0).await.unwrap();
+
+    assert_eq!(*outcomes.borrow(), vec![
+        Err((DenialReason::Balked, Duration::ZERO)),
+        Err((DenialReason::TimedOut, Duration::from_seconds(4.0).unwrap())),
+    ]);
+}
+
+#[tokio::test]
+async fn test_capacity_schedule_changes_capacity_over_time() {
+    let mut sim = Simulator::new();
+    sim.create_resource("кассир", 1).await;
+    // 3 кассы с 10 до 20, затем одна; работающие кассиры дообслуживают клиентов
+    let schedule = CapacitySchedule::new(vec![
+        (Duration::from_seconds(10.0).unwrap(), 3),
+        (Duration::from_seconds(20.0).unwrap(), 1),
+    ]);
+    sim.set_capacity_schedule("кассир", schedule).await.unwrap();
+
+    let script = r#"
+        function client()
+            request("кассир")
+            wait(15)
+            release("кассир")
+        end
+    "#;
+    for name in ["c1", "c2", "c3"] {
+        sim.load_process(name, script, "client").await.unwrap();
+    }
+    sim.run(100.0).await.unwrap();
+
+    let stats = sim.get_stats().await;
+    let cashier = &stats["resources"][0];
+    assert_eq!(stats["time"], 25.0);
+    assert_eq!(cashier["capacity"], 1);
+    assert_eq!(cashier["in_use"], 0);
+    assert_eq!(cashier["wait_time"]["max"], 10.0);
+    // Ёмкость 1, 3, 1 на [0, 10), [10, 20), [20, 25)
+    assert_eq!(cashier["mean_capacity"], 1.8);
+    // Занято 1, 3, 2 на [0, 10), [10, 15), [15, 25) — ровно вся ёмкость
+    assert_eq!(cashier["mean_utilization"], 1.0);
+}
+
+#[tokio::test]
+async fn test_capacity_drop_preempts_latest_holder() {
+    let mut sim = Simulator::new();
+    sim.create_resource("станок", 2).await;
+    sim.create_store("прерывания", None).await.unwrap();
+    let schedule = CapacitySchedule::new(vec![(Duration::from_seconds(5.0).unwrap(), 1)])
+        .with_policy(CapacityDropPolicy::Preempt);
+    sim.set_capacity_schedule("станок", schedule).await.unwrap();
+
+    let script = r#"
+        function worker()
+            request("станок")
+            local ok, err = pcall(wait, 10)
+            if ok then
+                release("станок")
+            else
+                assert(err.cause == "capacity" and now() == 5)
+                put("прерывания", err.resource)
+            end
+        end
+
+        function late_worker()
+            wait(1)
+            worker()
+        end
+    "#;
+    sim.load_process("first", script, "worker").await.unwrap();
+    sim.load_process("second", script, "late_worker").await.unwrap();
+    sim.run(100.0).await.unwrap();
+
+    let stats = sim.get_stats().await;
+    let machine = stats["resources"].as_array().unwrap().iter().find(|r| r["name"] == "станок").unwrap();
+    assert_eq!(machine["total_preemptions"], 1);
+    assert_eq!(machine["in_use"], 0);
+    assert_eq!(stats["stores"][0]["items"], 1);
+    assert_eq!(stats["time"], 10.0);
+}
+
+#[tokio::test]
+async fn test_preemption_respects_reduced_capacity() {
+    let mut sim = Simulator::new();
+    sim.create_preemptive_resource("станок", 2).await;
+    sim.create_store("выдачи", None).await.unwrap();
+    // С 2 до 12 мест нет, владельцы дорабатывают
+    let schedule = CapacitySchedule::new(vec![
+        (Duration::from_seconds(2.0).unwrap(), 0),
+        (Duration::from_seconds(12.0).unwrap(), 1),
+    ]);
+    sim.set_capacity_schedule("станок", schedule).await.unwrap();
+
+    let script = r#"
+        function holder()
+            request("станок", {priority = 5})
+            wait(10)
+            release("станок")
+        end
+
+        function urgent()
+            wait(5)
+            local grant = request("станок", {priority = 0})
+            put("выдачи", grant.granted_at)
+            release("станок")
+        end
+    "#;
+    sim.load_process("a", script, "holder").await.unwrap();
+    sim.load_process("b", script, "holder").await.unwrap();
+    sim.load_process("urgent", script, "urgent").await.unwrap();
+
+    sim.run(11.0).await.unwrap();
+    let stats = sim.get_stats().await;
+    let machine = stats["resources"].as_array().unwrap().iter().find(|r| r["name"] == "станок").unwrap();
+    // Вытеснение не создаёт единицу сверх ёмкости 0
+    assert_eq!(machine["total_preemptions"], 0);
+    assert_eq!(machine["in_use"], 0);
+    assert_eq!(machine["queue_length"], 1);
+
+    sim.run(10.0).await.unwrap();
+    let stats = sim.get_stats().await;
+    let machine = stats["resources"].as_array().unwrap().iter().find(|r| r["name"] == "станок").unwrap();
+    assert_eq!(machine["total_requests"], 3);
+    assert_eq!(machine["wait_time"]["max"], 7.0);
+    assert_eq!(stats["stores"][0]["items"], 1);
+}
+
+#[tokio::test]
+async fn test_breakdown_delays_service_and_tracks_downtime() {
+    let mut sim = Simulator::new();
+    sim.create_resource("станок", 1).await;
+    // Отказ через 10 после ремонта, ремонт 5
+    let breakdown = Breakdown::new(
+        DurationSampler::Fixed(Duration::from_seconds(10.0).unwrap()),
+        DurationSampler::Fixed(Duration::from_seconds(5.0).unwrap()),
+    );
+    sim.set_breakdown("станок", breakdown).await.unwrap();
+    sim.create_store("готово", None).await.unwrap();
+
+    let script = r#"
+        function first()
+            request("станок")
+            wait(12)
+            release("станок")
+            put("готово", now())
+        end
+
+        function second()
+            wait(11)
+            local grant = request("станок")
+            put("готово", grant.granted_at)
+            wait(3)
+            release("станок")
+        end
+    "#;
+    sim.load_process("first", script, "first").await.unwrap();
+    sim.load_process("second", script, "second").await.unwrap();
+
+    let done = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let log = done.clone();
+    sim.spawn_process("учёт", move |ctx| async move {
+        for _ in 0..2 {
+            let time = ctx.get_item("готово").await?;
+            log.borrow_mut().push(time);
+        }
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    sim.run(22.0).await.unwrap();
+
+    // Первая деталь простояла 5 секунд ремонта, вторая получила станок сразу после неё
+    assert_eq!(*done.borrow(), vec![serde_json::json!(17.0), serde_json::json!(17.0)]);
+
+    let stats = sim.get_stats().await;
+    let machine = stats["resources"].as_array().unwrap().iter().find(|r| r["name"] == "станок").unwrap();
+    assert_eq!(machine["failures"], 1);
+    assert_eq!(machine["broken"], false);
+    assert_eq!(machine["total_downtime"], 5.0);
+    assert_eq!(machine["downtime"]["mean"], 5.0);
+    assert_eq!(machine["availability"], 17.0 / 22.0);
+}
+
+#[tokio::test]
+async fn test_broken_preemptive_resource_does_not_preempt() {
+    let mut sim = Simulator::new();
+    sim.create_preemptive_resource("станок", 1).await;
+    // Единственный отказ на 2..7, владелец при этом приостанавливается
+    let mut failures = 0;
+    let breakdown = Breakdown::new(
+        DurationSampler::Custom(Box::new(move |_| {
+            failures += 1;
+            Duration::from_seconds(if failures == 1 { 2.0 } else { 100.0 })
+        })),
+        DurationSampler::Fixed(Duration::from_seconds(5.0).unwrap()),
+    )
+    .with_policy(FailurePolicy::Delay);
+    sim.set_breakdown("станок", breakdown).await.unwrap();
+    sim.create_store("выдачи", None).await.unwrap();
+
+    let script = r#"
+        function holder()
+            request("станок", {priority = 5})
+            wait(10)
+            release("станок")
+        end
+
+        function urgent()
+            wait(3)
+            local grant = request("станок", {priority = 0})
+            put("выдачи", grant.granted_at)
+            wait(2)
+            release("станок")
+        end
+    "#;
+    sim.load_process("holder", script, "holder").await.unwrap();
+    sim.load_process("urgent", script, "urgent").await.unwrap();
+    sim.run(30.0).await.unwrap();
+
+    // Пока станок в ремонте, вытеснять нечего: срочный ждёт конца работы
+    // владельца, продлённой ремонтом до 15
+    let stats = sim.get_stats().await;
+    let machine = stats["resources"].as_array().unwrap().iter().find(|r| r["name"] == "станок").unwrap();
+    assert_eq!(machine["total_preemptions"], 0);
+    assert_eq!(machine["total_requests"], 2);
+    assert_eq!(machine["wait_time"]["max"], 12.0);
+    assert_eq!(machine["in_use"], 0);
+    assert_eq!(stats["stores"][0]["items"], 1);
+}
+
+#[tokio::test]
+async fn test_negative_distribution_samples_are_truncated_to_zero() {
+    let mut sim = Simulator::with_seed(7);
+    sim.create_resource("станок", 1).await;
+    // У нормального распределения с таким разбросом много отрицательных значений
+    let repair = simpy_rs::core::Distribution::normal(0.5, 2.0).unwrap();
+    let breakdown = Breakdown::new(
+        DurationSampler::Fixed(Duration::from_seconds(1.0).unwrap()),
+        DurationSampler::Distribution(repair),
+    );
+    sim.set_breakdown("станок", breakdown).await.unwrap();
+
+    sim.run(1_000.0).await.unwrap();
+
+    let stats = sim.get_stats().await;
+    assert!(stats["resources"][0]["failures"].as_u64().unwrap() > 100);
+}
+
+#[tokio::test]
+async fn test_second_breakdown_model_is_rejected() {
+    let mut sim = Simulator::new();
+    sim.create_resource("станок", 1).await;
+    let fixed = |seconds: f64| DurationSampler::Fixed(Duration::from_seconds(seconds).unwrap());
+    sim.set_breakdown("станок", Breakdown::new(fixed(2.0), fixed(5.0)).with_policy(FailurePolicy::Delay))
+        .await
+        .unwrap();
+    // Вторая модель сломала бы станок во время ремонта и потеряла
+    // приостановленную работу владельца
+    let second = sim
+        .set_breakdown("станок", Breakdown::new(fixed(4.0), fixed(1.0)).with_policy(FailurePolicy::Delay))
+        .await;
+    assert!(matches!(second, Err(SimError::ResourceError(message)) if message.contains("already has a breakdown model")));
+
+    let script = r#"
+        function holder()
+            request("станок")
+            wait(3)
+            release("станок")
+            return now()
+        end
+    "#;
+    let holder = sim.load_process("holder", script, "holder").await.unwrap();
+    sim.run(30.0).await.unwrap();
+
+    // Отказ на 2..7 продлевает работу с 3 до 8
+    assert_eq!(sim.outcome(&holder), Some(&ProcessOutcome::Finished(vec![serde_json::json!(8.0)])));
+}
+
+#[tokio::test]
+async fn test_lua_breakdown_interrupts_holder() {
+    let mut sim = Simulator::new();
+    sim.create_resource("станок", 1).await;
+    sim.create_store("прерывания", None).await.unwrap();
+    sim.set_breakdown_lua(
+        "станок",
+        r#"
+            function ttf(args) return 3 end
+            function ttr(args) return 2 end
+        "#,
+        "ttf",
+        "ttr",
+        FailurePolicy::Interrupt,
+    )
+    .await
+    .unwrap();
+
+    let script = r#"
+        function worker()
+            request("станок")
+            local ok, err = pcall(wait, 10)
+            assert(not ok and err.cause == "breakdown" and err.resource == "станок" and now() == 3)
+            put("прерывания", err.remaining)
+        end
+    "#;
+    sim.load_process("worker", script, "worker").await.unwrap();
+    sim.run(4.0).await.unwrap();
+
+    let stats = sim.get_stats().await;
+    let machine = &stats["resources"][0];
+    assert_eq!(machine["broken"], true);
+    assert_eq!(machine["in_use"], 0);
+    assert_eq!(machine["total_downtime"], 1.0);
+    assert_eq!(stats["stores"][0]["items"], 1);
+}
+
+#[tokio::test]
+async fn test_request_all_is_atomic_and_request_any_takes_free_pool() {
+    let mut sim = Simulator::new();
+    for name in ["хирург", "медсестра", "операционная", "перевязочная"] {
+        sim.create_resource(name, 1).await;
+    }
+    sim.create_store("журнал", None).await.unwrap();
+
+    sim.spawn_process("медсестра занята", |ctx| async move {
+        ctx.request("медсестра").await?;
+        ctx.timeout(Duration::from_seconds(5.0)?).await?;
+        ctx.release("медсестра")
+    })
+    .await
+    .unwrap();
+
+    let script = r#"
+        function surgery()
+            local r = request_all({"хирург", "медсестра", "операционная"})
+            put("журнал", {what = "операция", at = r.granted_at, n = #r.resources})
+            wait(10)
+            for _, name in ipairs(r.resources) do release(name) end
+        end
+
+        -- Хирург свободен, пока операция ждёт медсестру
+        function consult()
+            wait(1)
+            local r = request("хирург")
+            put("журнал", {what = "консультация", at = r.granted_at})
+            wait(2)
+            release("хирург")
+        end
+
+        function dressing()
+            wait(6)
+            local r = request_any({"операционная", "перевязочная"})
+            put("журнал", {what = r.resource, at = r.granted_at})
+            release(r.resource)
+        end
+    "#;
+    for name in ["surgery", "consult", "dressing"] {
+        sim.load_process(name, script, name).await.unwrap();
+    }
+
+    let journal = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let log = journal.clone();
+    sim.spawn_process("учёт", move |ctx| async move {
+        for _ in 0..3 {
+            let entry = ctx.get_item("журнал").await?;
+            log.borrow_mut().push(entry);
+        }
+        // Rust-процесс ждёт операционную вместе с хирургом
+        let grants = ctx.request_all(&["хирург", "операционная"]).await?;
+        log.borrow_mut().push(serde_json::json!(grants[0].granted_at.as_seconds()));
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    sim.run(100.0).await.unwrap();
+
+    assert_eq!(*journal.borrow(), vec![
+        serde_json::json!({"what": "консультация", "at": 1.0}),
+        serde_json::json!({"what": "операция", "at": 5.0, "n": 3}),
+        serde_json::json!({"what": "перевязочная", "at": 6.0}),
+        serde_json::json!(15.0),
+    ]);
+
+    let stats = sim.get_stats().await;
+    for resource in stats["resources"].as_array().unwrap() {
+        // «Учёт» не освобождает ресурсы сам: это происходит при его завершении
+        assert_eq!(resource["in_use"], 0);
+    }
+}
+
+#[tokio::test]
+async fn test_joint_requests_do_not_overtake_resource_queue() {
+    let mut sim = Simulator::new();
+    sim.create_resource("a", 1).await;
+    sim.create_resource("b", 1).await;
+    sim.create_priority_resource("c", 1).await;
+    sim.create_store("журнал", None).await.unwrap();
+
+    let script = r#"
+        function holder(resource)
+            request(resource)
+            wait(5)
+            release(resource)
+        end
+
+        function early(resource, priority)
+            wait(1)
+            local r = request(resource, {priority = priority})
+            put("журнал", {who = "early " .. resource, at = r.granted_at})
+            wait(10)
+            release(resource)
+        end
+
+        -- b свободен всё время, a и c заняты до 5 и ждут раньше пришедшие
+        function late_all()
+            wait(2)
+            local r = request_all({"a", "b"})
+            put("журнал", {who = "late all", at = r.granted_at})
+            for _, name in ipairs(r.resources) do release(name) end
+        end
+
+        function late_any()
+            wait(2)
+            local r = request_any({"c"})
+            put("журнал", {who = "late any", at = r.granted_at})
+            release(r.resource)
+        end
+    "#;
+    for resource in ["a", "c"] {
+        sim.load_process_with_args(&format!("holder {}", resource), script, "holder", vec![resource.into()])
+            .await
+            .unwrap();
+    }
+    // У c очередь по приоритету: ждущий с приоритетом 7 всё равно пришёл раньше
+    sim.load_process_with_args("early a", script, "early", vec!["a".into(), 0.into()]).await.unwrap();
+    sim.load_process_with_args("early c", script, "early", vec!["c".into(), 7.into()]).await.unwrap();
+    sim.load_process("late all", script, "late_all").await.unwrap();
+    sim.load_process("late any", script, "late_any").await.unwrap();
+
+    let journal = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let log = journal.clone();
+    sim.spawn_process("учёт", move |ctx| async move {
+        for _ in 0..4 {
+            let entry = ctx.get_item("журнал").await?;
+            log.borrow_mut().push((entry["who"].as_str().unwrap().to_string(), entry["at"].as_f64().unwrap()));
+        }
+        Ok(())
+    })
+    .await
+    .unwrap();
+    sim.run(100.0).await.unwrap();
+
+    assert_eq!(*journal.borrow(), vec![
+        ("early a".to_string(), 5.0),
+        ("early c".to_string(), 5.0),
+        ("late all".to_string(), 15.0),
+        ("late any".to_string(), 15.0),
+    ]);
+}
+
+#[tokio::test]
+async fn test_multi_unit_requests_follow_batch_policy() {
+    let script = r#"
+        function big()
+            wait(1)
+            local r = request("причал", 3)
+            put("журнал", {who = "big", at = r.granted_at, units = r.units})
+            wait(4)
+            release("причал", 1)
+            wait(1)
+            release("причал")
+        end
+
+        function small()
+            wait(2)
+            local r = request("причал", {units = 1})
+            put("журнал", {who = "small", at = r.granted_at})
+            wait(10)
+            release("причал")
+        end
+    "#;
+
+    for (policy, expected) in [
+        // Большой запрос не обгоняют: маленькие ждут за ним
+        (BatchPolicy::Strict, [("big", 5.0), ("small", 5.0), ("small", 9.0)]),
+        // Маленькие проходят сразу, а большой ждёт, пока освободятся три единицы
+        (BatchPolicy::Backfill, [("small", 2.0), ("small", 2.0), ("big", 12.0)]),
+    ] {
+        let mut sim = Simulator::new();
+        sim.create_resource("причал", 4).await;
+        sim.set_batch_policy("причал", policy).await.unwrap();
+        sim.create_store("журнал", None).await.unwrap();
+
+        sim.spawn_process("разгрузка", |ctx| async move {
+            let grant = ctx.request_with("причал", RequestOptions::with_units(2)).await?;
+            assert_eq!(grant.units, 2);
+            ctx.timeout(Duration::from_seconds(5.0)?).await?;
+            ctx.release_units("причал", 2)
+        })
+        .await
+        .unwrap();
+        sim.load_process("big", script, "big").await.unwrap();
+        sim.load_process("small 1", script, "small").await.unwrap();
+        sim.load_process("small 2", script, "small").await.unwrap();
+
+        let journal = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let log = journal.clone();
+        sim.spawn_process("учёт", move |ctx| async move {
+            for _ in 0..3 {
+                let entry = ctx.get_item("журнал").await?;
+                log.borrow_mut().push((entry["who"].as_str().unwrap().to_string(), entry["at"].as_f64().unwrap()));
+                if entry["who"] == "big" {
+                    assert_eq!(entry["units"], 3);
+                }
+            }
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        sim.run(100.0).await.unwrap();
+
+        let expected: Vec<_> = expected.iter().map(|(who, at)| (who.to_string(), *at)).collect();
+        assert_eq!(*journal.borrow(), expected, "{:?}", policy);
+
+        let stats = sim.get_stats().await;
+        assert_eq!(stats["resources"][0]["in_use"], 0);
+    }
+}
+
+/// Чем закончится `request_all`, если зарезервировавший единицу запрос
+/// уходит из очереди в момент 5: по таймауту (`renege`) или по прерыванию
+async fn joint_outcome_after_reserving_request_leaves(renege: bool) -> ProcessOutcome {
+    let mut sim = Simulator::new();
+    sim.create_resource("причал", 2).await;
+
+    let script = r#"
+        function holder()
+            request("причал")
+            wait(20)
+            release("причал")
+        end
+
+        function big(timeout)
+            pcall(request, "причал", 2, {timeout = timeout})
+        end
+
+        function alarm()
+            wait(5)
+            interrupt("big", "отмена")
+        end
+
+        function joint()
+            wait(2)
+            request_all({"причал"})
+            return now()
+        end
+    "#;
+    sim.load_process("holder", script, "holder").await.unwrap();
+    if renege {
+        sim.load_process_with_args("big", script, "big", vec![serde_json::json!(5)]).await.unwrap();
+    } else {
+        sim.load_process("big", script, "big").await.unwrap();
+        sim.load_process("alarm", script, "alarm").await.unwrap();
+    }
+    let joint = sim.load_process("joint", script, "joint").await.unwrap();
+    sim.run(100.0).await.unwrap();
+
+    sim.outcome(&joint).cloned().unwrap()
+}
+
+#[tokio::test]
+async fn test_joint_request_is_served_when_reserving_request_leaves_queue() {
+    let served_at_5 = ProcessOutcome::Finished(vec![serde_json::json!(5.0)]);
+    assert_eq!(joint_outcome_after_reserving_request_leaves(true).await, served_at_5);
+    assert_eq!(joint_outcome_after_reserving_request_leaves(false).await, served_at_5);
+}
+
+#[tokio::test]
+async fn test_request_above_any_capacity_is_rejected() {
+    let mut sim = Simulator::new();
+    sim.create_resource("причал", 2).await;
+    sim.create_resource("смена", 1).await;
+    // С 10 до 20 у смены три места: запрос на три единицы дождётся их
+    let schedule = CapacitySchedule::new(vec![
+        (Duration::from_seconds(10.0).unwrap(), 3),
+        (Duration::from_seconds(20.0).unwrap(), 1),
+    ]);
+    sim.set_capacity_schedule("смена", schedule).await.unwrap();
+
+    let script = r#"
+        function big()
+            local ok, err = pcall(request, "причал", 3)
+            return ok, string.find(err, "never has 3 units") ~= nil
+        end
+
+        function small()
+            wait(1)
+            local grant = request("причал", 1)
+            release("причал")
+            return grant.granted_at
+        end
+
+        function crew()
+            local grant = request("смена", 3)
+            release("смена", 3)
+            return grant.granted_at
+        end
+    "#;
+    let big = sim.load_process("big", script, "big").await.unwrap();
+    let small = sim.load_process("small", script, "small").await.unwrap();
+    let crew = sim.load_process("crew", script, "crew").await.unwrap();
+    sim.run(100.0).await.unwrap();
+
+    let finished = |values: Vec<serde_json::Value>| Some(ProcessOutcome::Finished(values));
+    assert_eq!(sim.outcome(&big).cloned(), finished(vec![serde_json::json!(false), serde_json::json!(true)]));
+    assert_eq!(sim.outcome(&small).cloned(), finished(vec![serde_json::json!(1.0)]));
+    assert_eq!(sim.outcome(&crew).cloned(), finished(vec![serde_json::json!(10.0)]));
+}
+
+#[tokio::test]
+async fn test_joint_request_honours_strict_reservation() {
+    let mut sim = Simulator::new();
+    sim.create_resource("причал", 3).await;
+    sim.create_store("журнал", None).await.unwrap();
+
+    sim.spawn_process("разгрузка", |ctx| async move {
+        ctx.request_with("причал", RequestOptions::with_units(2)).await?;
+        ctx.timeout(Duration::from_seconds(5.0)?).await?;
+        ctx.release_units("причал", 2)
+    })
+    .await
+    .unwrap();
+
+    let script = r#"
+        function big()
+            wait(1)
+            local r = request("причал", 3)
+            put("журнал", {who = "big", at = r.granted_at})
+            wait(5)
+            release("причал")
+        end
+
+        -- Свободная единица зарезервирована за big, раньше пришедшим в очередь
+        function any()
+            wait(2)
+            local r = request_any({"причал"})
+            put("журнал", {who = "any", at = r.granted_at})
+            wait(50)
+            release(r.resource)
+        end
+    "#;
+    sim.load_process("big", script, "big").await.unwrap();
+    sim.load_process("any", script, "any").await.unwrap();
+
+    let journal = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let log = journal.clone();
+    sim.spawn_process("учёт", move |ctx| async move {
+        for _ in 0..2 {
+            let entry = ctx.get_item("журнал").await?;
+            log.borrow_mut().push((entry["who"].as_str().unwrap().to_string(), entry["at"].as_f64().unwrap()));
+        }
+        Ok(())
+    })
+    .await
+    .unwrap();
+    sim.run(100.0).await.unwrap();
+
+    assert_eq!(*journal.borrow(), vec![("big".to_string(), 5.0), ("any".to_string(), 10.0)]);
+}
+
+#[tokio::test]
+async fn test_holdings_are_released_when_process_ends_and_invalid_release_fails() {
+    let mut sim = Simulator::new();
+    sim.create_resource("кассир", 1).await;
+    sim.create_resource("окно", 1).await;
+    sim.create_store("журнал", None).await.unwrap();
+
+    let script = r#"
+        -- Завершается, не освободив кассира
+        function forgetful()
+            request("кассир")
+            wait(2)
+        end
+
+        -- Падает с ошибкой, держа окно
+        function crashing()
+            request("окно")
+            wait(3)
+            error("сбой")
+        end
+
+        function customer()
+            wait(1)
+            local cashier = request("кассир")
+            local window = request("окно")
+            put("журнал", {cashier = cashier.granted_at, window = window.granted_at})
+
+            -- Ошибка release возвращается из ближайшего ожидания
+            release("кассир")
+            release("кассир")
+            local ok, err = pcall(wait, 1)
+            put("журнал", {ok = ok, held = string.find(err, "does not hold") ~= nil})
+            release("окно")
+        end
+    "#;
+    for name in ["forgetful", "crashing", "customer"] {
+        sim.load_process(name, script, name).await.unwrap();
+    }
+
+    let journal = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let entries = journal.clone();
+    sim.spawn_process("учёт", move |ctx| async move {
+        for _ in 0..2 {
+            let entry = ctx.get_item("журнал").await?;
+            entries.borrow_mut().push(entry);
+        }
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    let errors = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let log = errors.clone();
+    sim.spawn_process("rust", move |ctx| async move {
+        ctx.timeout(Duration::from_seconds(10.0)?).await?;
+        let unknown = ctx.request("склад").await.err();
+        log.borrow_mut().push(unknown);
+        ctx.release("склад")?;
+        let invalid_release = ctx.timeout(Duration::from_seconds(1.0)?).await.err();
+        log.borrow_mut().push(invalid_release);
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    sim.run(100.0).await.unwrap();
+
+    // Кассир освободился, когда forgetful завершился, окно — когда crashing упал
+    assert_eq!(*journal.borrow(), vec![
+        serde_json::json!({"cashier": 2.0, "window": 3.0}),
+        serde_json::json!({"ok": false, "held": true}),
+    ]);
+
+    assert_eq!(errors.borrow().len(), 2);
+    for error in errors.borrow().iter() {
+        assert!(matches!(error, Some(SimError::ResourceError(_))), "{:?}", error);
+    }
+
+    let stats = sim.get_stats().await;
+    for resource in stats["resources"].as_array().unwrap() {
+        assert_eq!(resource["in_use"], 0);
+    }
+}
+
+/// Кассир занят до 10, к нему приходят a, b, c, d с длительностью обслуживания 4, 1, 3, 2
+async fn queue_discipline_sim() -> Simulator {
+    let mut sim = Simulator::new();
+    sim.create_resource("кассир", 1).await;
+    sim.create_store("порядок", None).await.unwrap();
+
+    let script = r#"
+        function busy()
+            request("кассир")
+            wait(10)
+            release("кассир")
+        end
+
+        function customer(delay, service)
+            return function()
+                wait(delay)
+                request("кассир", {attrs = {service = service}})
+                put("порядок", _process_name)
+                wait(1)
+                release("кассир")
+            end
+        end
+
+        a = customer(1, 4)
+        b = customer(2, 1)
+        c = customer(3, 3)
+        d = customer(4, 2)
+    "#;
+    for name in ["busy", "a", "b", "c", "d"] {
+        sim.load_process(name, script, name).await.unwrap();
+    }
+    sim
+}
+
+async fn service_order(mut sim: Simulator) -> Vec<String> {
+    let order = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let log = order.clone();
+    sim.spawn_process("учёт", move |ctx| async move {
+        for _ in 0..4 {
+            let name = ctx.get_item("порядок").await?;
+            log.borrow_mut().push(name.as_str().unwrap().to_string());
+        }
+        Ok(())
+    })
+    .await
+    .unwrap();
+    sim.run(100.0).await.unwrap();
+    let order = order.borrow().clone();
+    order
+}
+
+#[tokio::test]
+async fn test_queue_disciplines_change_service_order() {
+    let cases: Vec<(QueueDiscipline, [&str; 4])> = vec![
+        (QueueDiscipline::Fifo, ["a", "b", "c", "d"]),
+        (QueueDiscipline::Lifo, ["d", "c", "b", "a"]),
+        (QueueDiscipline::shortest_job("service"), ["b", "d", "c", "a"]),
+        (
+            QueueDiscipline::Compare(Box::new(|x, y| y.process.cmp(x.process))),
+            ["d", "c", "b", "a"],
+        ),
+    ];
+    for (discipline, expected) in cases {
+        let mut sim = queue_discipline_sim().await;
+        let name = format!("{:?}", discipline);
+        sim.set_queue_discipline("кассир", discipline).await.unwrap();
+        assert_eq!(service_order(sim).await, expected, "{}", name);
+    }
+
+    // Самая длинная работа первой через ключ на Lua
+    let mut sim = queue_discipline_sim().await;
+    sim.set_queue_key_lua("кассир", "function key(r) return -r.attrs.service end", "key")
+        .await
+        .unwrap();
+    assert_eq!(service_order(sim).await, ["a", "c", "d", "b"]);
+
+    // Случайный порядок воспроизводится при том же seed
+    let mut orders = Vec::new();
+    for _ in 0..2 {
+        let mut sim = queue_discipline_sim().await;
+        sim.set_queue_discipline("кассир", QueueDiscipline::random(7)).await.unwrap();
+        orders.push(service_order(sim).await);
+    }
+    assert_eq!(orders[0], orders[1]);
+    let mut served = orders[0].clone();
+    served.sort();
+    assert_eq!(served, ["a", "b", "c", "d"]);
+
+    let mut sim = Simulator::new();
+    assert!(matches!(
+        sim.set_queue_discipline("нет такого", QueueDiscipline::Lifo).await,
+        Err(SimError::ResourceError(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_pool_routes_requests_by_skill_and_tracks_units() {
+    let mut sim = Simulator::new();
+    sim.create_pool("операторы", vec![
+        Unit::new("alice").with_attributes(serde_json::json!({"skills": ["english", "billing"], "cost": 30})),
+        Unit::new("boris").with_attributes(serde_json::json!({"skills": ["russian"]})),
+        Unit::new("carol").with_attributes(serde_json::json!({"skills": ["english"]})),
+    ])
+    .await
+    .unwrap();
+    sim.create_store("журнал", None).await.unwrap();
+    assert!(sim.set_capacity("операторы", 5, CapacityDropPolicy::Finish).await.is_err());
+
+    let script = r#"
+        function call(delay, skills)
+            return function()
+                wait(delay)
+                local r = request("операторы", {skills = skills})
+                put("журнал", {who = _process_name, unit = r.unit, at = r.granted_at})
+                wait(10)
+                release("операторы")
+            end
+        end
+
+        c1 = call(0, {"english"})
+        -- billing умеет только занятая alice: carol ей не подходит
+        c2 = call(1, {"english", "billing"})
+
+        function c3()
+            wait(2)
+            local ok, err = pcall(request, "операторы", {skills = {"german"}})
+            put("журнал", {who = "c3", ok = ok, reason = string.find(err, "fewer than") ~= nil})
+            call(0, {"english"})()
+        end
+    "#;
+    for name in ["c1", "c2", "c3"] {
+        sim.load_process(name, script, name).await.unwrap();
+    }
+
+    let assigned = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let log = assigned.clone();
+    sim.spawn_process("c4", move |ctx| async move {
+        ctx.timeout(Duration::from_seconds(3.0)?).await?;
+        let grant = ctx.request_with("операторы", RequestOptions::with_skills(&["russian"])).await?;
+        log.borrow_mut().extend(grant.assigned);
+        ctx.timeout(Duration::from_seconds(10.0)?).await?;
+        ctx.release("операторы")
+    })
+    .await
+    .unwrap();
+
+    let journal = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let entries = journal.clone();
+    sim.spawn_process("учёт", move |ctx| async move {
+        for _ in 0..4 {
+            let entry = ctx.get_item("журнал").await?;
+            entries.borrow_mut().push(entry);
+        }
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    sim.run(30.0).await.unwrap();
+
+    assert_eq!(*journal.borrow(), vec![
+        serde_json::json!({"who": "c1", "unit": "alice", "at": 0.0}),
+        serde_json::json!({"who": "c3", "ok": false, "reason": true}),
+        serde_json::json!({"who": "c3", "unit": "carol", "at": 2.0}),
+        serde_json::json!({"who": "c2", "unit": "alice", "at": 10.0}),
+    ]);
+    assert_eq!(*assigned.borrow(), vec!["boris".to_string()]);
+
+    // Последний звонок завершается в 20, на этом симуляция и останавливается
+    let stats = sim.get_stats().await;
+    assert_eq!(stats["time"], 20.0);
+    let units = stats["resources"][0]["units"].as_array().unwrap();
+    let unit = |name: &str| units.iter().find(|u| u["name"] == name).unwrap();
+    assert_eq!(unit("alice")["served"], 2);
+    assert_eq!(unit("alice")["utilization"], 1.0);
+    assert_eq!(unit("alice")["attributes"]["cost"], 30);
+    assert_eq!(unit("carol")["utilization"], 0.5);
+    assert_eq!(unit("boris")["served"], 1);
+    assert_eq!(stats["resources"][0]["in_use"], 0);
+}